@@ -0,0 +1,146 @@
+use crate::error::GraphicsError;
+
+/// How to pick the graphics adapter and draw with it. Native builds set these from the command
+/// line, and the web build uses the defaults.
+#[derive(Debug, Clone)]
+pub struct GraphicsOptions {
+    pub backends: wgpu::Backends,
+    /// The index of the adapter in `--list-adapters`, or part of its name. The best adapter
+    /// for the window is picked if this is `None`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub adapter: Option<String>,
+    pub present_mode: wgpu::PresentMode,
+    /// Samples per pixel, where 1 turns multisampling off.
+    pub msaa: u32,
+}
+
+impl Default for GraphicsOptions {
+    fn default() -> Self {
+        Self {
+            // Browsers without WebGPU fall back to WebGL2.
+            backends: wgpu::Backends::PRIMARY | wgpu::Backends::GL,
+            #[cfg(not(target_arch = "wasm32"))]
+            adapter: None,
+            present_mode: wgpu::PresentMode::Fifo,
+            msaa: 1,
+        }
+    }
+}
+
+pub async fn instance(options: &GraphicsOptions) -> wgpu::Instance {
+    wgpu::util::new_instance_with_webgpu_detection(&wgpu::InstanceDescriptor {
+        backends: options.backends,
+        ..Default::default()
+    })
+    .await
+}
+
+/// The adapter named in `options`, or the best one otherwise. Either must be able to draw to
+/// `surface` if there is one.
+pub async fn request_adapter(
+    instance: &wgpu::Instance,
+    options: &GraphicsOptions,
+    surface: Option<&wgpu::Surface<'_>>,
+) -> Result<wgpu::Adapter, GraphicsError> {
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(name) = &options.adapter {
+        let adapter = find_adapter(instance, options.backends, name)
+            .ok_or_else(|| GraphicsError::UnknownAdapter(name.clone()))?;
+        if let Some(surface) = surface
+            && !adapter.is_surface_supported(surface)
+        {
+            return Err(GraphicsError::IncompatibleAdapter(adapter.get_info().name));
+        }
+        return Ok(adapter);
+    }
+    #[cfg(target_arch = "wasm32")]
+    let _ = options;
+
+    instance
+        .request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            compatible_surface: surface,
+            force_fallback_adapter: false,
+        })
+        .await
+        .map_err(GraphicsError::NoAdapter)
+}
+
+/// The adapter at `name` in [`list_adapters`], if it is a number, or else the first whose name
+/// contains it, ignoring case.
+#[cfg(not(target_arch = "wasm32"))]
+fn find_adapter(
+    instance: &wgpu::Instance,
+    backends: wgpu::Backends,
+    name: &str,
+) -> Option<wgpu::Adapter> {
+    let mut adapters = instance.enumerate_adapters(backends).into_iter();
+    match name.parse::<usize>() {
+        Ok(index) => adapters.nth(index),
+        Err(_) => {
+            let name = name.to_lowercase();
+            adapters.find(|adapter| adapter.get_info().name.to_lowercase().contains(&name))
+        }
+    }
+}
+
+/// Every adapter on `backends`, in the order `--adapter` counts them.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn list_adapters(backends: wgpu::Backends) -> Vec<wgpu::AdapterInfo> {
+    let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+        backends,
+        ..Default::default()
+    });
+    instance
+        .enumerate_adapters(backends)
+        .iter()
+        .map(wgpu::Adapter::get_info)
+        .collect()
+}
+
+/// Adapters that fall short of the default limits, such as WebGL2 ones, get by without
+/// compute shaders and trace the strands on the CPU instead. Timestamp queries are asked for
+/// if the adapter has them, to time passes with.
+pub async fn request_device(
+    adapter: &wgpu::Adapter,
+) -> Result<(wgpu::Device, wgpu::Queue), GraphicsError> {
+    let required_limits = if wgpu::Limits::default().check_limits(&adapter.limits()) {
+        wgpu::Limits::default()
+    } else {
+        let limits = wgpu::Limits::downlevel_webgl2_defaults().using_resolution(adapter.limits());
+        GraphicsError::check_limits(&limits, &adapter.limits())?;
+        limits
+    };
+    log::info!("Adapter: {:?}", adapter.get_info());
+
+    adapter
+        .request_device(&wgpu::DeviceDescriptor {
+            label: None,
+            required_features: adapter.features() & wgpu::Features::TIMESTAMP_QUERY,
+            experimental_features: wgpu::ExperimentalFeatures::disabled(),
+            required_limits,
+            memory_hints: Default::default(),
+            trace: wgpu::Trace::Off,
+        })
+        .await
+        .map_err(GraphicsError::Device)
+}
+
+/// `requested` samples per pixel if `adapter` can multisample `format` and the depth buffer that
+/// many times, and no multisampling otherwise.
+pub fn sample_count(adapter: &wgpu::Adapter, format: wgpu::TextureFormat, requested: u32) -> u32 {
+    let supported = [format, wgpu::TextureFormat::Depth32Float]
+        .into_iter()
+        .all(|format| {
+            adapter
+                .get_texture_format_features(format)
+                .flags
+                .sample_count_supported(requested)
+        });
+    if supported {
+        requested
+    } else {
+        log::warn!("The adapter cannot draw with {requested}× MSAA, so it is turned off");
+        1
+    }
+}
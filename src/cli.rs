@@ -0,0 +1,334 @@
+use anyhow::anyhow;
+use clap::{Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
+use winit::dpi::PhysicalSize;
+
+use crate::adapter::GraphicsOptions;
+use crate::config::Config;
+use crate::pipelines::Pipelines;
+use crate::quality::Quality;
+use crate::scene::Scene;
+
+/// Tubes growing through curl noise. Without a subcommand, the demo starts with the launcher.
+/// Options given here override those saved from the launcher last time.
+#[derive(Debug, Parser)]
+#[command(version)]
+pub struct Cli {
+    /// Opens a window instead of filling the screen.
+    #[arg(long)]
+    pub windowed: bool,
+    /// Size of the window, the screen mode or the rendered frames, such as 1920x1080. Full
+    /// screen keeps the desktop's mode if this is not given.
+    #[arg(long, global = true, value_parser = parse_resolution)]
+    pub resolution: Option<PhysicalSize<u32>>,
+    /// How frames are shown, overriding vsync.
+    #[arg(long, value_enum)]
+    pub present_mode: Option<PresentMode>,
+    #[arg(long, global = true, value_enum, default_value_t = Backend::Auto)]
+    pub backend: Backend,
+    /// The adapter to draw with, by its index in --list-adapters or part of its name.
+    #[arg(long, global = true)]
+    pub adapter: Option<String>,
+    /// Samples per pixel for antialiasing, where 1 turns it off.
+    #[arg(long, global = true, value_parser = clap::value_parser!(u32).range(1..=16))]
+    pub msaa: Option<u32>,
+    /// low, medium, high or ultra, setting the strand count, segments per strand, tube sides
+    /// and integrator.
+    #[arg(long, global = true, value_parser = parse_quality)]
+    pub quality: Option<Quality>,
+    /// A TOML scene file to start with, as dropped on the window.
+    #[arg(long, global = true)]
+    pub scene: Option<PathBuf>,
+    /// Seconds of demo time to start from.
+    #[arg(long, global = true, default_value_t = 0.0)]
+    pub start: f32,
+    /// Starts the demo straight away, without the launcher.
+    #[arg(long)]
+    pub skip_launcher: bool,
+    /// Lists the graphics adapters on the chosen backends and exits.
+    #[arg(long)]
+    pub list_adapters: bool,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Renders frames without a window and writes them as numbered PNG files.
+    Render {
+        /// The directory to write the frames to.
+        #[arg(long, short, default_value = "frames")]
+        output: PathBuf,
+        #[arg(long, default_value_t = 1)]
+        frames: u32,
+        /// Frames per second of demo time.
+        #[arg(long, default_value_t = 30.0, value_parser = parse_fps)]
+        fps: f32,
+    },
+    /// Simulates the strands without a window and exports them, as the export keys do.
+    Export {
+        /// The files to write, each in the format of its extension: obj, ply, glb, stl, svg,
+        /// hpgl, usda, json or csv.
+        #[arg(required = true)]
+        outputs: Vec<PathBuf>,
+    },
+    /// Times the compute and render passes without a window for every combination of strand
+    /// count, segments per strand and tube sides, with strands at full length.
+    Bench {
+        #[arg(
+            long,
+            value_delimiter = ',',
+            default_values_t = [256, 512, 1024],
+            value_parser = clap::value_parser!(u32).range(1..=Pipelines::NUM_STRANDS as i64),
+        )]
+        strands: Vec<u32>,
+        #[arg(
+            long,
+            value_delimiter = ',',
+            default_values_t = [16, 32, 64],
+            value_parser = clap::value_parser!(u32).range(1..=Pipelines::SEGMENTS_PER_STRAND as i64),
+        )]
+        segments: Vec<u32>,
+        #[arg(
+            long,
+            value_delimiter = ',',
+            default_values_t = [4, 8, 12],
+            value_parser = clap::value_parser!(u32).range(3..=64),
+        )]
+        sides: Vec<u32>,
+        /// Frames timed for each combination.
+        #[arg(long, default_value_t = 30)]
+        frames: u32,
+        /// Frames drawn before timing each combination, which are left out of the results.
+        #[arg(long, default_value_t = 5)]
+        warmup: u32,
+        /// The files to write the results to, each in the format of its extension: csv or
+        /// json. The results are printed as CSV if none are given.
+        outputs: Vec<PathBuf>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum PresentMode {
+    AutoVsync,
+    AutoNoVsync,
+    Fifo,
+    FifoRelaxed,
+    Immediate,
+    Mailbox,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Backend {
+    /// Vulkan, Metal or DirectX 12, or else OpenGL.
+    Auto,
+    Vulkan,
+    Metal,
+    Dx12,
+    Gl,
+}
+
+impl From<PresentMode> for wgpu::PresentMode {
+    fn from(mode: PresentMode) -> Self {
+        match mode {
+            PresentMode::AutoVsync => Self::AutoVsync,
+            PresentMode::AutoNoVsync => Self::AutoNoVsync,
+            PresentMode::Fifo => Self::Fifo,
+            PresentMode::FifoRelaxed => Self::FifoRelaxed,
+            PresentMode::Immediate => Self::Immediate,
+            PresentMode::Mailbox => Self::Mailbox,
+        }
+    }
+}
+
+impl From<Backend> for wgpu::Backends {
+    fn from(backend: Backend) -> Self {
+        match backend {
+            Backend::Auto => Self::PRIMARY | Self::GL,
+            Backend::Vulkan => Self::VULKAN,
+            Backend::Metal => Self::METAL,
+            Backend::Dx12 => Self::DX12,
+            Backend::Gl => Self::GL,
+        }
+    }
+}
+
+impl Cli {
+    /// The graphics options from `config`, with those given here in their place.
+    pub fn graphics(&self, config: &Config) -> GraphicsOptions {
+        GraphicsOptions {
+            backends: self.backend.into(),
+            adapter: self.adapter.clone(),
+            present_mode: self
+                .present_mode
+                .map_or_else(|| config.present_mode(), Into::into),
+            msaa: self.msaa.unwrap_or(config.msaa),
+        }
+    }
+
+    /// Puts the window and quality options given here in place of those in `config`.
+    pub fn override_config(&self, config: &mut Config) {
+        if self.windowed {
+            config.fullscreen = false;
+        }
+        if let Some(resolution) = self.resolution {
+            config.resolution = Some([resolution.width, resolution.height]);
+        }
+        if let Some(msaa) = self.msaa {
+            config.msaa = msaa;
+        }
+        if let Some(quality) = self.quality {
+            config.quality = quality;
+        }
+    }
+
+    /// The scene given with `--scene`, or the default one.
+    pub fn scene(&self) -> anyhow::Result<Scene> {
+        match &self.scene {
+            Some(path) => Scene::load(path),
+            None => Ok(Scene::default()),
+        }
+    }
+}
+
+/// Prints the adapters on `backends`, numbered as `--adapter` takes them.
+pub fn list_adapters(backends: wgpu::Backends) {
+    let adapters = crate::adapter::list_adapters(backends);
+    if adapters.is_empty() {
+        println!("No graphics adapters found");
+    }
+    for (index, info) in adapters.iter().enumerate() {
+        println!(
+            "{index}: {} ({:?}, {:?}, driver {} {})",
+            info.name, info.backend, info.device_type, info.driver, info.driver_info
+        );
+    }
+}
+
+fn parse_quality(text: &str) -> anyhow::Result<Quality> {
+    Quality::ALL
+        .into_iter()
+        .find(|quality| quality.name() == text)
+        .ok_or_else(|| anyhow!("{text} is not low, medium, high or ultra"))
+}
+
+fn parse_fps(text: &str) -> anyhow::Result<f32> {
+    match text.trim().parse::<f32>() {
+        Ok(fps) if fps > 0.0 && fps.is_finite() => Ok(fps),
+        _ => Err(anyhow!(
+            "{text} is not a positive number of frames per second"
+        )),
+    }
+}
+
+fn parse_resolution(text: &str) -> anyhow::Result<PhysicalSize<u32>> {
+    let (width, height) = text
+        .split_once(['x', 'X', '×'])
+        .ok_or_else(|| anyhow!("{text} is not a width and height such as 1920x1080"))?;
+    let parse = |side: &str| -> anyhow::Result<u32> {
+        match side.trim().parse() {
+            Ok(0) | Err(_) => Err(anyhow!("{side} is not a size in pixels")),
+            Ok(pixels) => Ok(pixels),
+        }
+    };
+    Ok(PhysicalSize::new(parse(width)?, parse(height)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn arguments_parse() {
+        Cli::command().debug_assert();
+        let cli = Cli::try_parse_from([
+            "noodles",
+            "--windowed",
+            "--resolution",
+            "1280x720",
+            "--present-mode",
+            "mailbox",
+            "--backend",
+            "vulkan",
+            "--adapter",
+            "1",
+            "--msaa",
+            "4",
+            "--quality",
+            "low",
+            "--start",
+            "12.5",
+        ])
+        .unwrap();
+        let mut config = Config::default();
+        cli.override_config(&mut config);
+        assert!(!config.fullscreen);
+        assert_eq!(config.resolution, Some([1280, 720]));
+        assert_eq!(config.quality, Quality::Low);
+        let graphics = cli.graphics(&config);
+        assert_eq!(graphics.present_mode, wgpu::PresentMode::Mailbox);
+        assert_eq!(graphics.backends, wgpu::Backends::VULKAN);
+        assert_eq!(graphics.adapter.as_deref(), Some("1"));
+        assert_eq!(graphics.msaa, 4);
+        assert_eq!(cli.start, 12.5);
+
+        let cli =
+            Cli::try_parse_from(["noodles", "render", "--frames", "3", "--msaa", "2"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::Render { frames: 3, .. })
+        ));
+        assert_eq!(cli.msaa, Some(2));
+
+        let cli = Cli::try_parse_from(["noodles"]).unwrap();
+        let config = Config {
+            vsync: false,
+            msaa: 8,
+            ..Default::default()
+        };
+        let graphics = cli.graphics(&config);
+        assert_eq!(graphics.present_mode, wgpu::PresentMode::AutoNoVsync);
+        assert_eq!(graphics.msaa, 8);
+
+        let cli = Cli::try_parse_from([
+            "noodles",
+            "bench",
+            "--strands",
+            "64,1024",
+            "--sides",
+            "6",
+            "bench.csv",
+        ])
+        .unwrap();
+        let Some(Command::Bench {
+            strands,
+            segments,
+            sides,
+            outputs,
+            ..
+        }) = cli.command
+        else {
+            panic!("not a bench command");
+        };
+        assert_eq!(strands, [64, 1024]);
+        assert_eq!(segments, [16, 32, 64]);
+        assert_eq!(sides, [6]);
+        assert_eq!(outputs, [PathBuf::from("bench.csv")]);
+        assert!(Cli::try_parse_from(["noodles", "bench", "--strands", "4096"]).is_err());
+        assert!(Cli::try_parse_from(["noodles", "bench", "--sides", "2"]).is_err());
+
+        let cli = Cli::try_parse_from(["noodles", "render", "--fps", "59.94"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::Render { fps: 59.94, .. })
+        ));
+        for fps in ["0", "-30", "inf", "NaN"] {
+            assert!(Cli::try_parse_from(["noodles", "render", "--fps", fps]).is_err());
+        }
+
+        assert!(Cli::try_parse_from(["noodles", "--resolution", "1920"]).is_err());
+        assert!(Cli::try_parse_from(["noodles", "--resolution", "0x1080"]).is_err());
+        assert!(Cli::try_parse_from(["noodles", "export"]).is_err());
+    }
+}
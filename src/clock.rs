@@ -0,0 +1,41 @@
+use web_time::Instant;
+
+/// Demo time in seconds, which can be paused and run faster or slower than real time.
+pub struct Clock {
+    /// Demo time at `last_tick`.
+    seconds: f32,
+    last_tick: Instant,
+    pub speed: f32,
+    pub paused: bool,
+}
+
+impl Clock {
+    pub fn new() -> Self {
+        Self::starting_at(0.0)
+    }
+
+    /// A clock that has already run for `seconds` of demo time.
+    pub fn starting_at(seconds: f32) -> Self {
+        Self {
+            seconds,
+            last_tick: Instant::now(),
+            speed: 1.0,
+            paused: false,
+        }
+    }
+
+    /// Advances the clock by the real time since the last tick, returning the demo time.
+    pub fn tick(&mut self) -> f32 {
+        let now = Instant::now();
+        if !self.paused {
+            self.seconds += self.speed * (now - self.last_tick).as_secs_f32();
+        }
+        self.last_tick = now;
+        self.seconds
+    }
+
+    /// The demo time at the last tick.
+    pub fn seconds(&self) -> f32 {
+        self.seconds
+    }
+}
@@ -0,0 +1,74 @@
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use winit::dpi::PhysicalSize;
+
+use crate::quality::Quality;
+
+/// How the demo is set up to run, as chosen in the launcher and kept in [`Config::PATH`] between
+/// runs. Missing keys take their default values.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Width and height of the window, or of the screen mode when full screen. The desktop's
+    /// is kept if this is missing.
+    pub resolution: Option<[u32; 2]>,
+    pub fullscreen: bool,
+    pub vsync: bool,
+    /// Samples per pixel, where 1 turns multisampling off.
+    pub msaa: u32,
+    pub quality: Quality,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            resolution: None,
+            fullscreen: true,
+            vsync: true,
+            msaa: 1,
+            quality: Quality::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Relative to the working directory, as saved scenes are.
+    pub const PATH: &str = "noodles.toml";
+
+    /// The saved config, or the defaults if there is none or it cannot be read.
+    pub fn load() -> Self {
+        let path = Path::new(Self::PATH);
+        if !path.exists() {
+            return Self::default();
+        }
+        let config = std::fs::read_to_string(path)
+            .with_context(|| format!("Unable to read {}", path.display()))
+            .and_then(|text| {
+                toml::from_str(&text).with_context(|| format!("Unable to parse {}", path.display()))
+            });
+        config.unwrap_or_else(|e| {
+            log::error!("{:#}, so the defaults are used", e);
+            Self::default()
+        })
+    }
+
+    #[cfg(feature = "launcher")]
+    pub fn save(&self) -> anyhow::Result<()> {
+        std::fs::write(Self::PATH, toml::to_string(self)?)
+            .with_context(|| format!("Unable to write {}", Self::PATH))
+    }
+
+    pub fn resolution(&self) -> Option<PhysicalSize<u32>> {
+        self.resolution
+            .map(|[width, height]| PhysicalSize::new(width, height))
+    }
+
+    pub fn present_mode(&self) -> wgpu::PresentMode {
+        if self.vsync {
+            wgpu::PresentMode::Fifo
+        } else {
+            wgpu::PresentMode::AutoNoVsync
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use std::fmt;
+
+/// Why the demo cannot draw, worded for whoever is watching it.
+#[derive(Debug)]
+pub enum GraphicsError {
+    Window(winit::error::OsError),
+    Surface(wgpu::CreateSurfaceError),
+    NoAdapter(wgpu::RequestAdapterError),
+    /// No adapter has the index or name given with `--adapter`.
+    #[cfg(not(target_arch = "wasm32"))]
+    UnknownAdapter(String),
+    /// The adapter given with `--adapter`, by its full name, cannot draw to the window.
+    #[cfg(not(target_arch = "wasm32"))]
+    IncompatibleAdapter(String),
+    /// The adapter falls short of even the WebGL2 limits, starting with `limit`.
+    UnsupportedLimits {
+        limit: &'static str,
+        required: u64,
+        available: u64,
+    },
+    Device(wgpu::RequestDeviceError),
+    /// The device stopped working, with the driver's explanation.
+    DeviceLost(String),
+}
+
+impl GraphicsError {
+    /// Fails with the first of the `required` limits that `available` falls short of.
+    pub fn check_limits(required: &wgpu::Limits, available: &wgpu::Limits) -> Result<(), Self> {
+        let mut shortfall = None;
+        required.check_limits_with_fail_fn(available, true, |limit, required, available| {
+            shortfall = Some(Self::UnsupportedLimits {
+                limit,
+                required,
+                available,
+            });
+        });
+        shortfall.map_or(Ok(()), Err)
+    }
+
+    /// The native binary exits with this, so scripts can tell the failures apart. Other errors
+    /// exit with 1.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Self::Window(_) => 2,
+            Self::Surface(_) => 3,
+            Self::NoAdapter(_) => 4,
+            Self::UnsupportedLimits { .. } => 5,
+            Self::Device(_) => 6,
+            Self::DeviceLost(_) => 7,
+            Self::UnknownAdapter(_) => 8,
+            Self::IncompatibleAdapter(_) => 9,
+        }
+    }
+}
+
+impl fmt::Display for GraphicsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Window(_) => write!(f, "Unable to open a window to draw in"),
+            Self::Surface(_) => write!(f, "Unable to draw to the window"),
+            Self::NoAdapter(_) if cfg!(target_arch = "wasm32") => write!(
+                f,
+                "No graphics adapter can draw the demo, which needs a browser with WebGPU or \
+                 WebGL2 turned on"
+            ),
+            Self::NoAdapter(_) => write!(
+                f,
+                "No graphics adapter can draw the demo, which needs a driver for Vulkan, Metal, \
+                 DirectX 12 or OpenGL"
+            ),
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::UnknownAdapter(name) => write!(
+                f,
+                "No graphics adapter is called {name}, run with --list-adapters to see them all"
+            ),
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::IncompatibleAdapter(name) => write!(f, "{name} cannot draw to the window"),
+            Self::UnsupportedLimits {
+                limit,
+                required,
+                available,
+            } => write!(
+                f,
+                "The graphics adapter is too limited to draw the demo, as its {limit} is \
+                 {available} and at least {required} is needed"
+            ),
+            Self::Device(_) => write!(f, "Unable to start the graphics device"),
+            Self::DeviceLost(message) => write!(f, "The graphics device was lost, {message}"),
+        }
+    }
+}
+
+impl std::error::Error for GraphicsError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Window(e) => Some(e),
+            Self::Surface(e) => Some(e),
+            Self::NoAdapter(e) => Some(e),
+            Self::Device(e) => Some(e),
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::UnknownAdapter(_) | Self::IncompatibleAdapter(_) => None,
+            Self::UnsupportedLimits { .. } | Self::DeviceLost(_) => None,
+        }
+    }
+}
@@ -0,0 +1,343 @@
+pub mod curves;
+mod gltf;
+mod obj;
+pub mod plot;
+mod ply;
+pub mod stl;
+
+use anyhow::{Context, bail, ensure};
+use glam::Vec3;
+use std::collections::HashMap;
+use std::f32::consts::TAU;
+use std::path::Path;
+
+use crate::pipelines::{Pipelines, TubeInstance};
+
+/// A continuous centreline read back from the instance buffer, with the frame and radius the
+/// tube is drawn with at each point.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Strand {
+    pub points: Vec<StrandPoint>,
+    pub colour: Vec3,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StrandPoint {
+    pub position: Vec3,
+    pub normal: Vec3,
+    pub bitangent: Vec3,
+    pub radius: f32,
+}
+
+/// Indexed triangles with a normal and a linear RGB colour per vertex.
+#[derive(Debug, Default, Clone)]
+pub struct Mesh {
+    pub positions: Vec<Vec3>,
+    pub normals: Vec<Vec3>,
+    pub colours: Vec<Vec3>,
+    pub triangles: Vec<[u32; 3]>,
+}
+
+/// The strands `pipelines` last drew, read back from the GPU.
+pub fn read_strands(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    pipelines: &Pipelines,
+) -> anyhow::Result<Vec<Strand>> {
+    let instances = pipelines.read_instances(device, queue)?;
+    if pipelines.has_uploaded_instances() {
+        // Uploaded strands vary in length, so they are only told apart by where they break.
+        Ok(strands(&instances, instances.len(), false))
+    } else {
+        Ok(strands(
+            &instances,
+            pipelines.segments_per_strand(),
+            pipelines.draws_trails(),
+        ))
+    }
+}
+
+/// Joins the segments of each strand into polylines. Collapsed segments, such as those past
+/// the end of a strand that stalled or a trail that has not yet grown, are dropped. When the
+/// strands are `wrapped` ring buffers of trails, they are unrolled so the oldest segment comes
+/// first.
+pub fn strands(
+    instances: &[TubeInstance],
+    segments_per_strand: usize,
+    wrapped: bool,
+) -> Vec<Strand> {
+    let mut strands = Vec::new();
+    for segments in instances.chunks(segments_per_strand) {
+        let mut runs: Vec<Vec<&TubeInstance>> = Vec::new();
+        for segment in segments {
+            if segment.radius <= 0.0 || segment.start_position == segment.end_position {
+                continue;
+            }
+            match runs.last_mut() {
+                Some(run) if run.last().unwrap().end_position == segment.start_position => {
+                    run.push(segment)
+                }
+                _ => runs.push(vec![segment]),
+            }
+        }
+        if wrapped {
+            runs = join_runs(runs);
+        }
+        strands.extend(runs.iter().map(|run| Strand::from_segments(run)));
+    }
+    strands
+}
+
+/// Joins runs that continue one another, as the two halves of a wrapped trail do.
+fn join_runs(mut runs: Vec<Vec<&TubeInstance>>) -> Vec<Vec<&TubeInstance>> {
+    let key = |position: Vec3| position.to_array().map(f32::to_bits);
+    let starts: HashMap<_, usize> = runs
+        .iter()
+        .enumerate()
+        .map(|(index, run)| (key(run[0].start_position), index))
+        .collect();
+    let next: Vec<Option<usize>> = runs
+        .iter()
+        .enumerate()
+        .map(|(index, run)| {
+            let end = key(run.last().unwrap().end_position);
+            starts.get(&end).copied().filter(|&next| next != index)
+        })
+        .collect();
+    let mut continued = vec![false; runs.len()];
+    for &index in next.iter().flatten() {
+        continued[index] = true;
+    }
+
+    // Follow each run that nothing continues into, then whatever is left of any loop.
+    let mut joined = Vec::new();
+    let mut taken = vec![false; runs.len()];
+    for first in (0..runs.len())
+        .filter(|&index| !continued[index])
+        .chain(0..runs.len())
+    {
+        let mut run = Vec::new();
+        let mut index = Some(first);
+        while let Some(current) = index
+            && !taken[current]
+        {
+            taken[current] = true;
+            run.append(&mut runs[current]);
+            index = next[current];
+        }
+        if !run.is_empty() {
+            joined.push(run);
+        }
+    }
+    joined
+}
+
+impl Strand {
+    fn from_segments(segments: &[&TubeInstance]) -> Self {
+        let first = segments[0];
+        let points = std::iter::once(StrandPoint {
+            position: first.start_position,
+            normal: first.start_normal,
+            bitangent: first.start_bitangent,
+            radius: first.radius,
+        })
+        .chain(segments.iter().map(|segment| StrandPoint {
+            position: segment.end_position,
+            normal: segment.end_normal,
+            bitangent: segment.end_bitangent,
+            radius: segment.radius,
+        }))
+        .collect();
+        Self {
+            points,
+            colour: first.colour,
+        }
+    }
+}
+
+impl Mesh {
+    /// The open cylinders drawn by `vs_main`, with one ring of `sides` vertices per point so
+    /// consecutive segments of a strand share their vertices.
+    pub fn tubes(strands: &[Strand], sides: usize) -> Self {
+        let mut mesh = Self::default();
+        for strand in strands {
+            let first_ring = mesh.positions.len() as u32;
+            for point in &strand.points {
+                for side in 0..sides {
+                    let angle = TAU * (side as f32) / (sides as f32);
+                    let offset = angle.cos() * point.normal + angle.sin() * point.bitangent;
+                    mesh.positions.push(point.position + offset * point.radius);
+                    mesh.normals.push(offset.normalize_or_zero());
+                    mesh.colours.push(strand.colour);
+                }
+            }
+            for ring in 0..strand.points.len() as u32 - 1 {
+                let start = first_ring + ring * sides as u32;
+                let end = start + sides as u32;
+                for side in 0..sides as u32 {
+                    let next = (side + 1) % sides as u32;
+                    // Wound the same way as the triangle strip the renderer draws.
+                    mesh.triangles
+                        .push([start + side, end + side, start + next]);
+                    mesh.triangles.push([end + side, end + next, start + next]);
+                }
+            }
+        }
+        mesh
+    }
+
+    /// Picks Wavefront OBJ, binary PLY or binary glTF by the extension of `path`.
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        ensure!(!self.triangles.is_empty(), "There are no tubes to export");
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase);
+        let bytes = match extension.as_deref() {
+            Some("obj") => obj::write(self),
+            Some("ply") => ply::write(self),
+            Some("glb") => gltf::write(self),
+            _ => bail!("Unknown mesh format {}", path.display()),
+        };
+        std::fs::write(path, bytes).with_context(|| format!("Unable to write {}", path.display()))
+    }
+}
+
+/// Encodes a linear colour channel for formats that conventionally store sRGB.
+fn linear_to_srgb(value: f32) -> f32 {
+    let value = value.clamp(0.0, 1.0);
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(from: f32, to: f32) -> TubeInstance {
+        TubeInstance {
+            start_position: Vec3::new(from, 0.0, 0.0),
+            start_normal: Vec3::Y,
+            start_bitangent: Vec3::Z,
+            end_position: Vec3::new(to, 0.0, 0.0),
+            end_normal: Vec3::Y,
+            end_bitangent: Vec3::Z,
+            colour: Vec3::ONE,
+            radius: 0.5,
+            ..Default::default()
+        }
+    }
+
+    fn xs(strand: &Strand) -> Vec<f32> {
+        strand.points.iter().map(|point| point.position.x).collect()
+    }
+
+    #[test]
+    fn only_wrapped_trails_are_joined() {
+        // A ring buffer whose oldest segment is the third, and a strand that collapsed.
+        let instances = [
+            segment(2.0, 3.0),
+            segment(3.0, 4.0),
+            segment(0.0, 1.0),
+            segment(1.0, 2.0),
+            segment(5.0, 6.0),
+            segment(6.0, 6.0),
+            segment(0.0, 0.0),
+            segment(0.0, 0.0),
+        ];
+        let trails = strands(&instances, 4, true);
+        assert_eq!(trails.len(), 2);
+        assert_eq!(xs(&trails[0]), [0.0, 1.0, 2.0, 3.0, 4.0]);
+        assert_eq!(xs(&trails[1]), [5.0, 6.0]);
+
+        // Uploaded strands that happen to meet end to start stay apart.
+        let uploaded = strands(&instances[..4], 4, false);
+        assert_eq!(uploaded.len(), 2);
+        assert_eq!(xs(&uploaded[0]), [2.0, 3.0, 4.0]);
+        assert_eq!(xs(&uploaded[1]), [0.0, 1.0, 2.0]);
+    }
+
+    fn two_segments() -> Mesh {
+        let strand = strands(&[segment(0.0, 1.0), segment(1.0, 2.0)], 2, false);
+        Mesh::tubes(&strand, 4)
+    }
+
+    #[test]
+    fn tubes_share_vertices_at_joints() {
+        let mesh = two_segments();
+        assert_eq!(mesh.positions.len(), 3 * 4);
+        assert_eq!(mesh.normals.len(), 3 * 4);
+        assert_eq!(mesh.colours.len(), 3 * 4);
+        assert_eq!(mesh.triangles.len(), 2 * 4 * 2);
+
+        let (first, second) = mesh.triangles.split_at(8);
+        let used = |triangles: &[[u32; 3]]| {
+            let mut indices: Vec<u32> = triangles.iter().flatten().copied().collect();
+            indices.sort();
+            indices.dedup();
+            indices
+        };
+        assert_eq!(used(first), (0..8).collect::<Vec<_>>());
+        assert_eq!(used(second), (4..12).collect::<Vec<_>>());
+        for joint in &mesh.positions[4..8] {
+            assert_eq!(joint.x, 1.0);
+            assert!((joint.with_x(0.0).length() - 0.5).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn obj_is_written() {
+        let text = String::from_utf8(obj::write(&two_segments())).unwrap();
+        let count = |prefix: &str| text.lines().filter(|line| line.starts_with(prefix)).count();
+        assert_eq!(count("v "), 12);
+        assert_eq!(count("vn "), 12);
+        assert_eq!(count("f "), 16);
+        assert!(text.contains("f 1//1 5//5 2//2\n"));
+    }
+
+    #[test]
+    fn ply_is_written() {
+        let bytes = ply::write(&two_segments());
+        let end = b"end_header\n";
+        let body = bytes
+            .windows(end.len())
+            .position(|window| window == end)
+            .unwrap()
+            + end.len();
+        let header = std::str::from_utf8(&bytes[..body]).unwrap();
+        assert!(header.starts_with("ply\nformat binary_little_endian 1.0\n"));
+        assert!(header.contains("element vertex 12\n"));
+        assert!(header.contains("element face 16\n"));
+        // Six floats and three bytes a vertex, then a count and three indices a face.
+        assert_eq!(bytes.len() - body, 12 * (6 * 4 + 3) + 16 * (1 + 3 * 4));
+        assert_eq!(bytes[body + 12 * 27], 3);
+    }
+
+    #[test]
+    fn glb_is_written() {
+        let bytes = gltf::write(&two_segments());
+        let word =
+            |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+        assert_eq!(word(0), 0x4654_6c67);
+        assert_eq!(word(4), 2);
+        assert_eq!(word(8) as usize, bytes.len());
+
+        let json_length = word(12) as usize;
+        assert_eq!(word(16), 0x4e4f_534a);
+        assert_eq!(json_length % 4, 0);
+        let json = std::str::from_utf8(&bytes[20..20 + json_length]).unwrap();
+
+        let bin = 20 + json_length;
+        let bin_length = word(bin) as usize;
+        assert_eq!(word(bin + 4), 0x004e_4942);
+        assert_eq!(bin + 8 + bin_length, bytes.len());
+        // Positions, normals and colours, then the indices.
+        let buffer_length = 3 * 12 * 12 + 16 * 3 * 4;
+        assert_eq!(bin_length, buffer_length);
+        assert!(json.contains(&format!(r#""buffers":[{{"byteLength":{buffer_length}}}]"#)));
+        assert!(json.contains(r#""count":48,"type":"SCALAR""#));
+    }
+}
@@ -0,0 +1,315 @@
+use anyhow::{Context, ensure};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use std::path::Path;
+
+use super::{Strand, StrandPoint};
+
+/// What the curve exports cover.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CurveOptions {
+    /// Writes the strands at a range of times rather than as they are on screen.
+    pub animation: Option<Animation>,
+}
+
+/// Times in seconds since the demo started, as used for the obstacles and the noise. Trails
+/// start from their seeds at `start`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Animation {
+    pub start: f32,
+    pub end: f32,
+    pub frames_per_second: f32,
+}
+
+/// USD type and name of an attribute, and how to write its value for a set of strands.
+type Attribute = (&'static str, &'static str, fn(&[Strand]) -> String);
+
+/// The strands as they were at one time, in seconds.
+#[derive(Debug, Clone)]
+pub struct CurveFrame {
+    pub time: f32,
+    pub strands: Vec<Strand>,
+}
+
+impl Default for Animation {
+    fn default() -> Self {
+        Self {
+            start: 0.0,
+            end: 10.0,
+            frames_per_second: 24.0,
+        }
+    }
+}
+
+impl Animation {
+    /// Every frame time from `start` up to and including `end`.
+    pub fn times(&self) -> impl Iterator<Item = f32> {
+        let frames = ((self.end - self.start) * self.frames_per_second)
+            .floor()
+            .max(0.0) as u32;
+        (0..=frames).map(move |frame| self.start + frame as f32 / self.frames_per_second)
+    }
+}
+
+/// Saves `frames` as linear `BasisCurves` with per-vertex widths and display colours. A single
+/// frame is written as default values, and several as time samples, with one time code per
+/// frame at `frames_per_second`.
+pub fn save_usda(frames: &[CurveFrame], frames_per_second: f32, path: &Path) -> anyhow::Result<()> {
+    let usda = usda(frames, frames_per_second)?;
+    std::fs::write(path, usda).with_context(|| format!("Unable to write {}", path.display()))
+}
+
+fn usda(frames: &[CurveFrame], frames_per_second: f32) -> anyhow::Result<String> {
+    ensure_finite(frames)?;
+    let time_code = |frame: &CurveFrame| (frame.time * frames_per_second).round();
+    let mut usda = String::from("#usda 1.0\n(\n    defaultPrim = \"Noodles\"\n");
+    if let (Some(first), Some(last)) = (frames.first(), frames.last())
+        && frames.len() > 1
+    {
+        let _ = write!(
+            usda,
+            "    startTimeCode = {}\n    endTimeCode = {}\n    timeCodesPerSecond = {}\n",
+            time_code(first),
+            time_code(last),
+            frames_per_second
+        );
+    }
+    usda.push_str(
+        "    upAxis = \"Z\"\n)\n\n\
+         def Xform \"Noodles\"\n{\n    def BasisCurves \"Strands\"\n    {\n\
+         \x20       uniform token type = \"linear\"\n\
+         \x20       uniform token wrap = \"nonperiodic\"\n",
+    );
+
+    // Strands can change in number and length between frames, so every attribute is sampled.
+    let attributes: [Attribute; 5] = [
+        ("int[]", "curveVertexCounts", |strands| {
+            list(strands.iter().map(|strand| strand.points.len().to_string()))
+        }),
+        ("point3f[]", "points", |strands| {
+            list(points(strands).map(|(point, _)| tuple(point.position.to_array())))
+        }),
+        ("float3[]", "extent", |strands| {
+            let (min, max) = points(strands).fold(
+                (glam::Vec3::INFINITY, glam::Vec3::NEG_INFINITY),
+                |(min, max), (point, _)| (min.min(point.position), max.max(point.position)),
+            );
+            list(
+                [min, max]
+                    .map(|corner| tuple(corner.to_array()))
+                    .into_iter(),
+            )
+        }),
+        ("float[]", "widths", |strands| {
+            list(points(strands).map(|(point, _)| (2.0 * point.radius).to_string()))
+        }),
+        ("color3f[]", "primvars:displayColor", |strands| {
+            list(points(strands).map(|(_, strand)| tuple(strand.colour.to_array())))
+        }),
+    ];
+    for (value_type, name, values) in attributes {
+        let _ = write!(usda, "        {value_type} {name}");
+        match frames {
+            [frame] => {
+                let _ = write!(usda, " = {}", values(&frame.strands));
+            }
+            _ => {
+                usda.push_str(".timeSamples = {\n");
+                for frame in frames {
+                    let _ = writeln!(
+                        usda,
+                        "            {}: {},",
+                        time_code(frame),
+                        values(&frame.strands)
+                    );
+                }
+                usda.push_str("        }");
+            }
+        }
+        if name == "widths" || name == "primvars:displayColor" {
+            usda.push_str(" (\n            interpolation = \"vertex\"\n        )");
+        }
+        usda.push('\n');
+    }
+    usda.push_str("    }\n}\n");
+    Ok(usda)
+}
+
+/// `{"frames": [{"time", "strands": [{"points", "widths", "colour"}]}]}`, with linear colours.
+pub fn save_json(frames: &[CurveFrame], path: &Path) -> anyhow::Result<()> {
+    let json = json(frames)?;
+    std::fs::write(path, json).with_context(|| format!("Unable to write {}", path.display()))
+}
+
+/// Writes numbers with `{:?}`, which is valid JSON for the finite values it is checked to have.
+fn json(frames: &[CurveFrame]) -> anyhow::Result<String> {
+    ensure_finite(frames)?;
+    let frames: Vec<String> = frames
+        .iter()
+        .map(|frame| {
+            let strands: Vec<String> = frame
+                .strands
+                .iter()
+                .map(|strand| {
+                    let points = strand
+                        .points
+                        .iter()
+                        .map(|point| format!("{:?}", point.position.to_array()));
+                    let widths = strand
+                        .points
+                        .iter()
+                        .map(|point| format!("{:?}", 2.0 * point.radius));
+                    format!(
+                        r#"{{"points":[{}],"widths":[{}],"colour":{:?}}}"#,
+                        points.collect::<Vec<_>>().join(","),
+                        widths.collect::<Vec<_>>().join(","),
+                        strand.colour.to_array()
+                    )
+                })
+                .collect();
+            format!(
+                "{{\"time\":{:?},\"strands\":[\n{}\n]}}",
+                frame.time,
+                strands.join(",\n")
+            )
+        })
+        .collect();
+    Ok(format!("{{\"frames\":[\n{}\n]}}\n", frames.join(",\n")))
+}
+
+/// One row per point, with linear colours.
+pub fn save_csv(frames: &[CurveFrame], path: &Path) -> anyhow::Result<()> {
+    let csv = csv(frames)?;
+    std::fs::write(path, csv).with_context(|| format!("Unable to write {}", path.display()))
+}
+
+fn csv(frames: &[CurveFrame]) -> anyhow::Result<String> {
+    ensure_finite(frames)?;
+    let mut csv = String::from("time,strand,point,x,y,z,width,r,g,b\n");
+    for frame in frames {
+        for (strand_index, strand) in frame.strands.iter().enumerate() {
+            for (point_index, point) in strand.points.iter().enumerate() {
+                let [x, y, z] = point.position.to_array();
+                let [r, g, b] = strand.colour.to_array();
+                let _ = writeln!(
+                    csv,
+                    "{},{strand_index},{point_index},{x},{y},{z},{},{r},{g},{b}",
+                    frame.time,
+                    2.0 * point.radius
+                );
+            }
+        }
+    }
+    Ok(csv)
+}
+
+/// Fails on positions, widths, colours or times that are not finite, which JSON cannot hold and
+/// which would break the curves in other tools.
+fn ensure_finite(frames: &[CurveFrame]) -> anyhow::Result<()> {
+    for frame in frames {
+        ensure!(
+            frame.time.is_finite(),
+            "Frame time {} is not finite",
+            frame.time
+        );
+        for (index, strand) in frame.strands.iter().enumerate() {
+            let finite = strand.colour.is_finite()
+                && strand
+                    .points
+                    .iter()
+                    .all(|point| point.position.is_finite() && point.radius.is_finite());
+            ensure!(
+                finite,
+                "Strand {index} at {} seconds has values that are not finite",
+                frame.time
+            );
+        }
+    }
+    Ok(())
+}
+
+fn points(strands: &[Strand]) -> impl Iterator<Item = (&StrandPoint, &Strand)> {
+    strands
+        .iter()
+        .flat_map(|strand| strand.points.iter().map(move |point| (point, strand)))
+}
+
+fn tuple([x, y, z]: [f32; 3]) -> String {
+    format!("({x}, {y}, {z})")
+}
+
+fn list(values: impl Iterator<Item = String>) -> String {
+    format!("[{}]", values.collect::<Vec<_>>().join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::Vec3;
+
+    fn frame(time: f32, x: f32) -> CurveFrame {
+        let point = |position| StrandPoint {
+            position,
+            normal: Vec3::X,
+            bitangent: Vec3::Y,
+            radius: 0.25,
+        };
+        CurveFrame {
+            time,
+            strands: vec![Strand {
+                points: vec![point(Vec3::ZERO), point(Vec3::new(x, 1.0, 2.0))],
+                colour: Vec3::new(1.0, 0.5, 0.0),
+            }],
+        }
+    }
+
+    #[test]
+    fn usda_is_written() {
+        let single = usda(&[frame(0.0, 1.0)], 24.0).unwrap();
+        assert!(single.contains("int[] curveVertexCounts = [2]"));
+        assert!(single.contains("point3f[] points = [(0, 0, 0), (1, 1, 2)]"));
+        assert!(single.contains("float[] widths = [0.5, 0.5]"));
+        assert!(!single.contains("timeSamples"));
+
+        let animated = usda(&[frame(0.0, 1.0), frame(0.5, 3.0)], 24.0).unwrap();
+        assert!(animated.contains("endTimeCode = 12"));
+        assert!(animated.contains("12: [(0, 0, 0), (3, 1, 2)],"));
+    }
+
+    #[test]
+    fn json_is_written() {
+        let json = json(&[frame(0.5, 1.0)]).unwrap();
+        assert_eq!(
+            json,
+            "{\"frames\":[\n{\"time\":0.5,\"strands\":[\n\
+             {\"points\":[[0.0, 0.0, 0.0],[1.0, 1.0, 2.0]],\"widths\":[0.5,0.5],\
+             \"colour\":[1.0, 0.5, 0.0]}\n]}\n]}\n"
+        );
+    }
+
+    #[test]
+    fn csv_is_written() {
+        let csv = csv(&[frame(0.5, 1.0)]).unwrap();
+        assert_eq!(
+            csv,
+            "time,strand,point,x,y,z,width,r,g,b\n\
+             0.5,0,0,0,0,0,0.5,1,0.5,0\n\
+             0.5,0,1,1,1,2,0.5,1,0.5,0\n"
+        );
+        let polylines = crate::import::Polylines::parse_csv(&csv).unwrap();
+        assert_eq!(polylines.0[0].points[1], Vec3::new(1.0, 1.0, 2.0));
+    }
+
+    #[test]
+    fn non_finite_values_fail() {
+        for value in [f32::NAN, f32::INFINITY] {
+            let frames = [frame(0.0, value)];
+            assert!(usda(&frames, 24.0).is_err());
+            assert!(json(&frames).is_err());
+            assert!(csv(&frames).is_err());
+        }
+        assert!(json(&[frame(f32::NAN, 1.0)]).is_err());
+    }
+}
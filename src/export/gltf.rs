@@ -0,0 +1,92 @@
+use glam::Vec3;
+
+use super::Mesh;
+
+const MAGIC: u32 = 0x4654_6c67;
+const VERSION: u32 = 2;
+const JSON_CHUNK: u32 = 0x4e4f_534a;
+const BIN_CHUNK: u32 = 0x004e_4942;
+const FLOAT: u32 = 5126;
+const UNSIGNED_INT: u32 = 5125;
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+
+/// A single binary glTF 2.0 mesh with one triangle primitive. `COLOR_0` is linear, as the
+/// specification requires.
+pub fn write(mesh: &Mesh) -> Vec<u8> {
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut views = Vec::new();
+    for attribute in [&mesh.positions, &mesh.normals, &mesh.colours] {
+        views.push((buffer.len(), ARRAY_BUFFER));
+        buffer.extend_from_slice(bytemuck::cast_slice(attribute));
+    }
+    views.push((buffer.len(), ELEMENT_ARRAY_BUFFER));
+    buffer.extend_from_slice(bytemuck::cast_slice(&mesh.triangles));
+    let view_ends: Vec<usize> = views
+        .iter()
+        .skip(1)
+        .map(|(offset, _)| *offset)
+        .chain([buffer.len()])
+        .collect();
+
+    let buffer_views: Vec<String> = views
+        .iter()
+        .zip(&view_ends)
+        .map(|((offset, target), end)| {
+            format!(
+                r#"{{"buffer":0,"byteOffset":{offset},"byteLength":{},"target":{target}}}"#,
+                end - offset
+            )
+        })
+        .collect();
+
+    let (min, max) = mesh.positions.iter().fold(
+        (Vec3::INFINITY, Vec3::NEG_INFINITY),
+        |(min, max), position| (min.min(*position), max.max(*position)),
+    );
+    let vertex_count = mesh.positions.len();
+    let accessors = [
+        format!(
+            r#"{{"bufferView":0,"componentType":{FLOAT},"count":{vertex_count},"type":"VEC3","min":[{},{},{}],"max":[{},{},{}]}}"#,
+            min.x, min.y, min.z, max.x, max.y, max.z
+        ),
+        format!(
+            r#"{{"bufferView":1,"componentType":{FLOAT},"count":{vertex_count},"type":"VEC3"}}"#
+        ),
+        format!(
+            r#"{{"bufferView":2,"componentType":{FLOAT},"count":{vertex_count},"type":"VEC3"}}"#
+        ),
+        format!(
+            r#"{{"bufferView":3,"componentType":{UNSIGNED_INT},"count":{},"type":"SCALAR"}}"#,
+            3 * mesh.triangles.len()
+        ),
+    ];
+
+    let json = format!(
+        concat!(
+            r#"{{"asset":{{"version":"2.0","generator":"noodles"}},"#,
+            r#""scene":0,"scenes":[{{"nodes":[0]}}],"nodes":[{{"name":"noodles","mesh":0}}],"#,
+            r#""meshes":[{{"primitives":[{{"attributes":{{"POSITION":0,"NORMAL":1,"COLOR_0":2}},"indices":3,"mode":4}}]}}],"#,
+            r#""accessors":[{}],"bufferViews":[{}],"buffers":[{{"byteLength":{}}}]}}"#
+        ),
+        accessors.join(","),
+        buffer_views.join(","),
+        buffer.len()
+    );
+
+    let mut json = json.into_bytes();
+    json.resize(json.len().next_multiple_of(4), b' ');
+    buffer.resize(buffer.len().next_multiple_of(4), 0);
+
+    let length = 12 + 8 + json.len() + 8 + buffer.len();
+    let mut bytes = Vec::with_capacity(length);
+    for word in [MAGIC, VERSION, length as u32, json.len() as u32, JSON_CHUNK] {
+        bytes.extend_from_slice(&word.to_le_bytes());
+    }
+    bytes.extend_from_slice(&json);
+    for word in [buffer.len() as u32, BIN_CHUNK] {
+        bytes.extend_from_slice(&word.to_le_bytes());
+    }
+    bytes.extend_from_slice(&buffer);
+    bytes
+}
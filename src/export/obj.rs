@@ -0,0 +1,28 @@
+use std::fmt::Write;
+
+use super::{Mesh, linear_to_srgb};
+
+/// Vertex colours follow the position as the widely read `v x y z r g b` extension.
+pub fn write(mesh: &Mesh) -> Vec<u8> {
+    let mut text = String::from("# Generated by noodles\no noodles\n");
+    for (position, colour) in mesh.positions.iter().zip(&mesh.colours) {
+        let _ = writeln!(
+            text,
+            "v {} {} {} {:.4} {:.4} {:.4}",
+            position.x,
+            position.y,
+            position.z,
+            linear_to_srgb(colour.x),
+            linear_to_srgb(colour.y),
+            linear_to_srgb(colour.z)
+        );
+    }
+    for normal in &mesh.normals {
+        let _ = writeln!(text, "vn {} {} {}", normal.x, normal.y, normal.z);
+    }
+    for triangle in &mesh.triangles {
+        let [a, b, c] = triangle.map(|index| index + 1);
+        let _ = writeln!(text, "f {a}//{a} {b}//{b} {c}//{c}");
+    }
+    text.into_bytes()
+}
@@ -0,0 +1,576 @@
+use anyhow::Context;
+use glam::{Mat4, Vec2, Vec3, Vec4, vec2};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use std::path::Path;
+
+use super::Strand;
+
+/// Layout of a pen plotter drawing. Lengths are in millimetres on the paper unless noted.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlotOptions {
+    /// Width and height of the paper; the view is fitted inside the margins, centred.
+    pub paper_size: Vec2,
+    pub margin: f32,
+    /// Furthest simplification may move a line.
+    pub tolerance: f32,
+    /// Visible pieces of a strand with gaps shorter than this are drawn as one line, saving
+    /// pen lifts across small occlusions. Zero keeps them apart.
+    pub merge_distance: f32,
+    pub stroke_width: f32,
+    /// Also writes HPGL for plotters that take it directly.
+    pub hpgl: bool,
+}
+
+/// Visible lines on the paper, from its top left corner, grouped by the strand they trace.
+#[derive(Debug, Clone)]
+pub struct Plot {
+    pub paper_size: Vec2,
+    pub stroke_width: f32,
+    pub strands: Vec<Vec<Vec<Vec2>>>,
+}
+
+impl Default for PlotOptions {
+    fn default() -> Self {
+        Self {
+            // A4 landscape
+            paper_size: vec2(297.0, 210.0),
+            margin: 15.0,
+            tolerance: 0.1,
+            merge_distance: 0.5,
+            stroke_width: 0.3,
+            hpgl: false,
+        }
+    }
+}
+
+/// Hidden line tests are made at least this often along a projected segment, in pixels.
+const SAMPLE_SPACING: f32 = 2.0;
+/// Side of the square screen regions that tubes are sorted into for occlusion tests, in pixels.
+const BIN_SIZE: f32 = 16.0;
+const HPGL_UNITS_PER_MILLIMETRE: f32 = 40.0;
+
+impl Plot {
+    /// Projects strand centrelines through `camera` onto a view of `viewport` pixels, fitted to
+    /// the paper, keeping the parts not hidden behind the tubes around other segments.
+    pub fn project(
+        strands: &[Strand],
+        camera: Mat4,
+        viewport: Vec2,
+        options: &PlotOptions,
+    ) -> Self {
+        let area = options.paper_size - Vec2::splat(2.0 * options.margin);
+        let millimetres_per_pixel = (area / viewport).min_element();
+        let corner = 0.5 * (options.paper_size - viewport * millimetres_per_pixel);
+        let occluders = Occluders::new(strands, camera, viewport);
+
+        let strands = strands
+            .iter()
+            .enumerate()
+            .map(|(strand_index, strand)| {
+                let mut lines: Vec<Vec<Vec2>> = Vec::new();
+                let mut line = Vec::new();
+                let mut visit = |position: Vec3, segment: usize| match occluders
+                    .visible_pixel(position, (strand_index, segment))
+                {
+                    Some(pixel) => line.push(corner + pixel * millimetres_per_pixel),
+                    None if !line.is_empty() => lines.push(std::mem::take(&mut line)),
+                    None => {}
+                };
+                for (segment, pair) in strand.points.windows(2).enumerate() {
+                    // Spaced along the part in front of the eye, whether or not it is in view.
+                    let [a, b] = [pair[0].position, pair[1].position]
+                        .map(|position| camera * position.extend(1.0));
+                    let length = clip_segment(a, b, None).map_or(0.0, |(a, b)| {
+                        occluders
+                            .clip_to_screen(a)
+                            .distance(occluders.clip_to_screen(b))
+                    });
+                    let samples = (length / SAMPLE_SPACING).ceil().clamp(1.0, 64.0) as usize;
+                    for sample in 0..samples {
+                        let t = sample as f32 / samples as f32;
+                        visit(pair[0].position.lerp(pair[1].position, t), segment);
+                    }
+                }
+                if let Some(last) = strand.points.last() {
+                    visit(last.position, strand.points.len() - 2);
+                }
+                if !line.is_empty() {
+                    lines.push(line);
+                }
+
+                merge_lines(lines, options.merge_distance)
+                    .into_iter()
+                    .map(|line| simplify(&line, options.tolerance))
+                    .filter(|line| line.len() >= 2)
+                    .collect::<Vec<_>>()
+            })
+            .filter(|lines| !lines.is_empty())
+            .collect();
+
+        Self {
+            paper_size: options.paper_size,
+            stroke_width: options.stroke_width,
+            strands,
+        }
+    }
+
+    /// One path per strand, sized in millimetres.
+    pub fn save_svg(&self, path: &Path) -> anyhow::Result<()> {
+        std::fs::write(path, self.svg())
+            .with_context(|| format!("Unable to write {}", path.display()))
+    }
+
+    /// HPGL with the origin in the bottom left corner of the paper, drawing the lines in an
+    /// order that keeps pen-up travel short.
+    pub fn save_hpgl(&self, path: &Path) -> anyhow::Result<()> {
+        std::fs::write(path, self.hpgl())
+            .with_context(|| format!("Unable to write {}", path.display()))
+    }
+
+    fn svg(&self) -> String {
+        let Vec2 {
+            x: width,
+            y: height,
+        } = self.paper_size;
+        let mut svg = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}mm\" height=\"{height}mm\" \
+             viewBox=\"0 0 {width} {height}\">\n\
+             <g fill=\"none\" stroke=\"black\" stroke-width=\"{}\" stroke-linecap=\"round\" \
+             stroke-linejoin=\"round\">\n",
+            self.stroke_width
+        );
+        for lines in &self.strands {
+            svg.push_str("<path d=\"");
+            for line in lines {
+                for (i, point) in line.iter().enumerate() {
+                    let command = if i == 0 { 'M' } else { 'L' };
+                    let _ = write!(svg, "{command}{:.2} {:.2} ", point.x, point.y);
+                }
+            }
+            svg.truncate(svg.trim_end().len());
+            svg.push_str("\"/>\n");
+        }
+        svg.push_str("</g>\n</svg>\n");
+        svg
+    }
+
+    fn hpgl(&self) -> String {
+        let to_units = |point: Vec2| {
+            let units = vec2(point.x, self.paper_size.y - point.y) * HPGL_UNITS_PER_MILLIMETRE;
+            units.round().as_ivec2()
+        };
+        let mut hpgl = String::from("IN;SP1;");
+        for line in self.plotting_order() {
+            let start = to_units(line[0]);
+            let _ = write!(hpgl, "\nPU{},{};PD", start.x, start.y);
+            let points: Vec<String> = line[1..]
+                .iter()
+                .map(|point| {
+                    let point = to_units(*point);
+                    format!("{},{}", point.x, point.y)
+                })
+                .collect();
+            let _ = write!(hpgl, "{};", points.join(","));
+        }
+        hpgl.push_str("\nPU;SP0;IN;\n");
+        hpgl
+    }
+
+    /// Greedily picks the nearest undrawn line to the pen each time, drawing it from whichever
+    /// end is closer.
+    fn plotting_order(&self) -> Vec<Vec<Vec2>> {
+        let mut remaining: Vec<&Vec<Vec2>> = self.strands.iter().flatten().collect();
+        let mut ordered = Vec::with_capacity(remaining.len());
+        let mut pen = Vec2::ZERO;
+        while !remaining.is_empty() {
+            let (index, reversed) = remaining
+                .iter()
+                .enumerate()
+                .flat_map(|(i, line)| {
+                    [
+                        (i, false, line[0].distance_squared(pen)),
+                        (i, true, line[line.len() - 1].distance_squared(pen)),
+                    ]
+                })
+                .min_by(|a, b| a.2.total_cmp(&b.2))
+                .map(|(i, reversed, _)| (i, reversed))
+                .unwrap();
+            let mut line = remaining.swap_remove(index).clone();
+            if reversed {
+                line.reverse();
+            }
+            pen = line[line.len() - 1];
+            ordered.push(line);
+        }
+        ordered
+    }
+}
+
+fn merge_lines(lines: Vec<Vec<Vec2>>, merge_distance: f32) -> Vec<Vec<Vec2>> {
+    let mut merged: Vec<Vec<Vec2>> = Vec::with_capacity(lines.len());
+    for line in lines {
+        match merged.last_mut() {
+            Some(last) if last[last.len() - 1].distance(line[0]) < merge_distance => {
+                last.extend(line)
+            }
+            _ => merged.push(line),
+        }
+    }
+    merged
+}
+
+/// Ramer-Douglas-Peucker simplification, keeping every point further than `tolerance` from
+/// the simplified line.
+fn simplify(line: &[Vec2], tolerance: f32) -> Vec<Vec2> {
+    if line.len() < 3 {
+        return line.to_vec();
+    }
+    let mut keep = vec![false; line.len()];
+    keep[0] = true;
+    keep[line.len() - 1] = true;
+    let mut spans = vec![(0, line.len() - 1)];
+    while let Some((first, last)) = spans.pop() {
+        let (a, b) = (line[first], line[last]);
+        let furthest = (first + 1..last)
+            .map(|i| (i, segment_distance(line[i], a, b)))
+            .max_by(|x, y| x.1.total_cmp(&y.1));
+        if let Some((i, distance)) = furthest
+            && distance > tolerance
+        {
+            keep[i] = true;
+            spans.push((first, i));
+            spans.push((i, last));
+        }
+    }
+    line.iter()
+        .zip(keep)
+        .filter_map(|(point, keep)| keep.then_some(*point))
+        .collect()
+}
+
+fn segment_distance(point: Vec2, a: Vec2, b: Vec2) -> f32 {
+    let axis = b - a;
+    let t = ((point - a).dot(axis) / axis.length_squared().max(f32::MIN_POSITIVE)).clamp(0.0, 1.0);
+    point.distance(a + t * axis)
+}
+
+/// The tube around every strand segment, as a capsule, sorted into screen space bins by the
+/// region it covers.
+struct Occluders<'a> {
+    strands: &'a [Strand],
+    camera: Mat4,
+    eye: Vec3,
+    viewport: Vec2,
+    columns: usize,
+    /// Strand and segment indices of the capsules overlapping each bin, row by row.
+    bins: Vec<Vec<(usize, usize)>>,
+}
+
+impl<'a> Occluders<'a> {
+    fn new(strands: &'a [Strand], camera: Mat4, viewport: Vec2) -> Self {
+        // The eye is the point the projection sends to infinity.
+        let eye = camera.inverse() * Vec4::Z;
+        let focal = camera.row(1).truncate().length();
+        let columns = (viewport.x / BIN_SIZE).ceil() as usize;
+        let rows = (viewport.y / BIN_SIZE).ceil() as usize;
+        let mut occluders = Self {
+            strands,
+            camera,
+            eye: eye.truncate() / eye.w,
+            viewport,
+            columns,
+            bins: vec![Vec::new(); columns * rows],
+        };
+
+        for (strand_index, strand) in strands.iter().enumerate() {
+            for (segment, pair) in strand.points.windows(2).enumerate() {
+                // A length at view distance w spans about focal / w in normalised device
+                // coordinates; the radius is doubled to cover views from off axis.
+                let reach = 2.0 * pair[0].radius * focal;
+                let [a, b] = [pair[0].position, pair[1].position]
+                    .map(|position| camera * position.extend(1.0));
+                // Only the part of the capsule that may cover the view is binned.
+                let Some((a, b)) = clip_segment(a, b, Some(reach)) else {
+                    continue;
+                };
+                let (mut lower, mut upper) = (Vec2::INFINITY, Vec2::NEG_INFINITY);
+                for clip in [a, b] {
+                    let pixel = occluders.clip_to_screen(clip);
+                    let reach = reach * 0.5 * viewport.y / clip.w;
+                    lower = lower.min(pixel - reach);
+                    upper = upper.max(pixel + reach);
+                }
+                let first = (lower / BIN_SIZE).floor().max(Vec2::ZERO).as_uvec2();
+                let last = (upper / BIN_SIZE)
+                    .floor()
+                    .min(vec2(columns as f32 - 1.0, rows as f32 - 1.0))
+                    .as_uvec2();
+                for row in first.y..=last.y {
+                    for column in first.x..=last.x {
+                        occluders.bins[row as usize * columns + column as usize]
+                            .push((strand_index, segment));
+                    }
+                }
+            }
+        }
+        occluders
+    }
+
+    /// Where a point in front of the eye projects to, in pixels, whether or not it is in view.
+    fn clip_to_screen(&self, clip: Vec4) -> Vec2 {
+        let ndc = clip.truncate() / clip.w;
+        vec2(0.5 * (ndc.x + 1.0), 0.5 * (1.0 - ndc.y)) * self.viewport
+    }
+
+    fn clip_to_pixel(&self, clip: Vec4) -> Option<Vec2> {
+        if clip.w <= 0.0 {
+            return None;
+        }
+        let ndc = clip.truncate() / clip.w;
+        (ndc.x.abs() <= 1.0 && ndc.y.abs() <= 1.0).then(|| self.clip_to_screen(clip))
+    }
+
+    fn to_pixel(&self, position: Vec3) -> Option<Vec2> {
+        self.clip_to_pixel(self.camera * position.extend(1.0))
+    }
+
+    /// Where `position` on the given segment appears, if it is in view and no other tube
+    /// lies between it and the eye. The segment's own tube and those of its neighbours
+    /// always surround it, so they are skipped.
+    fn visible_pixel(&self, position: Vec3, (strand, segment): (usize, usize)) -> Option<Vec2> {
+        let pixel = self.to_pixel(position)?;
+        let bin = (pixel / BIN_SIZE).floor().as_uvec2();
+        let bin = (bin.y as usize * self.columns + bin.x as usize).min(self.bins.len() - 1);
+        let offset = position - self.eye;
+        let distance = offset.length();
+        let direction = offset / distance;
+        let hidden = self.bins[bin].iter().any(|&(other_strand, other_segment)| {
+            if other_strand == strand && other_segment.abs_diff(segment) <= 1 {
+                return false;
+            }
+            let points = &self.strands[other_strand].points[other_segment..other_segment + 2];
+            ray_capsule(
+                self.eye,
+                direction,
+                points[0].position,
+                points[1].position,
+                points[0].radius,
+            )
+            .is_some_and(|t| t < distance)
+        });
+        (!hidden).then_some(pixel)
+    }
+}
+
+/// The part of the segment `a`-`b`, in clip coordinates, in front of the near plane, which
+/// reversed depth puts at z = w. Given a `margin`, it is also cut where it leaves the view by
+/// more than that, in clip coordinates.
+fn clip_segment(a: Vec4, b: Vec4, margin: Option<f32>) -> Option<(Vec4, Vec4)> {
+    let margin = margin.unwrap_or(f32::INFINITY);
+    let inside = |clip: Vec4| {
+        [
+            clip.w - clip.z,
+            clip.w + clip.x + margin,
+            clip.w - clip.x + margin,
+            clip.w + clip.y + margin,
+            clip.w - clip.y + margin,
+        ]
+    };
+    let (mut first, mut last) = (0.0_f32, 1.0_f32);
+    for (from, to) in inside(a).into_iter().zip(inside(b)) {
+        if from < 0.0 && to < 0.0 {
+            return None;
+        } else if from < 0.0 {
+            first = first.max(from / (from - to));
+        } else if to < 0.0 {
+            last = last.min(from / (from - to));
+        }
+    }
+    (first <= last).then(|| (a.lerp(b, first), a.lerp(b, last)))
+}
+
+/// Distance along a ray with unit `direction` to where it enters the capsule around `a`-`b`.
+fn ray_capsule(origin: Vec3, direction: Vec3, a: Vec3, b: Vec3, radius: f32) -> Option<f32> {
+    let ray_sphere = |centre: Vec3| {
+        let offset = origin - centre;
+        let half_b = offset.dot(direction);
+        let discriminant = half_b * half_b - (offset.length_squared() - radius * radius);
+        (discriminant >= 0.0).then(|| -half_b - discriminant.sqrt())
+    };
+    // The side of the cylinder, where the hit lies between the end caps.
+    let axis = b - a;
+    let offset = origin - a;
+    let axis_length_squared = axis.length_squared();
+    let along_ray = axis.dot(direction);
+    let along_offset = axis.dot(offset);
+    let quadratic_a = axis_length_squared - along_ray * along_ray;
+    let quadratic_b = axis_length_squared * offset.dot(direction) - along_offset * along_ray;
+    let quadratic_c = axis_length_squared * offset.length_squared()
+        - along_offset * along_offset
+        - radius * radius * axis_length_squared;
+    let discriminant = quadratic_b * quadratic_b - quadratic_a * quadratic_c;
+    let side = (quadratic_a > f32::EPSILON && discriminant >= 0.0)
+        .then(|| (-quadratic_b - discriminant.sqrt()) / quadratic_a)
+        .filter(|t| (0.0..axis_length_squared).contains(&(along_offset + t * along_ray)));
+    [side, ray_sphere(a), ray_sphere(b)]
+        .into_iter()
+        .flatten()
+        .filter(|t| *t > 0.0)
+        .min_by(f32::total_cmp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::StrandPoint;
+    use glam::vec3;
+
+    fn strand(points: &[Vec3], radius: f32) -> Strand {
+        Strand {
+            points: points
+                .iter()
+                .map(|&position| StrandPoint {
+                    position,
+                    normal: Vec3::X,
+                    bitangent: Vec3::Y,
+                    radius,
+                })
+                .collect(),
+            colour: Vec3::ONE,
+        }
+    }
+
+    /// Looks down -z from the origin with a square 90 degree view.
+    fn camera() -> Mat4 {
+        Mat4::perspective_infinite_reverse_rh(90_f32.to_radians(), 1.0, 0.1)
+            * Mat4::look_at_rh(Vec3::ZERO, -Vec3::Z, Vec3::Y)
+    }
+
+    const VIEWPORT: Vec2 = vec2(400.0, 400.0);
+
+    #[test]
+    fn simplify_keeps_ends_and_corners() {
+        let line = [
+            vec2(0.0, 0.0),
+            vec2(1.0, 0.0),
+            vec2(2.0, 0.0),
+            vec2(3.0, 0.05),
+            vec2(4.0, 0.0),
+            vec2(4.0, 2.0),
+        ];
+        assert_eq!(
+            simplify(&line, 0.1),
+            [vec2(0.0, 0.0), vec2(4.0, 0.0), vec2(4.0, 2.0)]
+        );
+        assert_eq!(simplify(&line[..2], 0.1), &line[..2]);
+    }
+
+    #[test]
+    fn close_lines_are_merged() {
+        let lines = vec![
+            vec![vec2(0.0, 0.0), vec2(1.0, 0.0)],
+            vec![vec2(1.3, 0.0), vec2(2.0, 0.0)],
+            vec![vec2(4.0, 0.0), vec2(5.0, 0.0)],
+        ];
+        let merged = merge_lines(lines.clone(), 0.5);
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0], [lines[0].clone(), lines[1].clone()].concat());
+        assert_eq!(merged[1], lines[2]);
+        assert_eq!(merge_lines(lines, 0.0).len(), 3);
+    }
+
+    #[test]
+    fn rays_enter_capsules() {
+        let (a, b) = (vec3(-1.0, 0.0, -5.0), vec3(1.0, 0.0, -5.0));
+        let side = ray_capsule(Vec3::ZERO, -Vec3::Z, a, b, 0.5).unwrap();
+        assert!((side - 4.5).abs() < 1e-5);
+        let cap = ray_capsule(vec3(1.2, 0.0, 0.0), -Vec3::Z, a, b, 0.5).unwrap();
+        assert!((cap - (5.0 - 0.21_f32.sqrt())).abs() < 1e-5);
+        assert!(ray_capsule(vec3(2.0, 0.0, 0.0), -Vec3::Z, a, b, 0.5).is_none());
+        // Capsules behind the ray are not hit.
+        assert!(ray_capsule(Vec3::ZERO, Vec3::Z, a, b, 0.5).is_none());
+    }
+
+    #[test]
+    fn segments_behind_tubes_are_hidden() {
+        let strands = [
+            strand(&[vec3(-1.0, 0.0, -5.0), vec3(1.0, 0.0, -5.0)], 0.2),
+            strand(&[vec3(0.0, -2.0, -10.0), vec3(0.0, 2.0, -10.0)], 0.01),
+            strand(&[vec3(0.5, -1.0, -2.0), vec3(0.5, 1.0, -2.0)], 0.01),
+        ];
+        let occluders = Occluders::new(&strands, camera(), VIEWPORT);
+        let visible = |x, y, z, strand| occluders.visible_pixel(vec3(x, y, z), (strand, 0));
+        assert_eq!(visible(0.0, 0.0, -10.0, 1), None);
+        assert!(visible(0.0, 1.5, -10.0, 1).is_some());
+        let pixel = visible(0.5, 0.0, -2.0, 2).unwrap();
+        assert!(pixel.distance(vec2(250.0, 200.0)) < 1e-3);
+    }
+
+    #[test]
+    fn tubes_leaving_the_view_still_hide() {
+        // One tube reaches far off the left of the view, and one passes behind the eye. Both
+        // cross the line of sight to the point, away from either end.
+        let strands = [
+            strand(&[vec3(-100.0, 0.0, -5.0), vec3(-1.0, 0.0, -5.0)], 0.2),
+            strand(&[vec3(-1.5, -1.0, -5.0), vec3(-1.5, -1.0, 5.0)], 0.2),
+            strand(&[vec3(-3.0, -1.0, -10.0), vec3(-3.0, 1.0, -10.0)], 0.01),
+        ];
+        let occluders = Occluders::new(&strands, camera(), VIEWPORT);
+        let visible = |y| occluders.visible_pixel(vec3(-3.0, y, -10.0), (2, 0));
+        assert_eq!(visible(0.0), None);
+        assert_eq!(visible(-2.0), None);
+        assert!(visible(1.0).is_some());
+    }
+
+    fn plot() -> Plot {
+        let strands = [
+            strand(&[vec3(-1.0, 0.5, -5.0), vec3(1.0, 0.5, -5.0)], 0.01),
+            strand(&[vec3(-1.0, -0.5, -5.0), vec3(1.0, -0.5, -5.0)], 0.01),
+        ];
+        let options = PlotOptions {
+            paper_size: vec2(200.0, 100.0),
+            margin: 10.0,
+            ..Default::default()
+        };
+        Plot::project(&strands, camera(), VIEWPORT, &options)
+    }
+
+    #[test]
+    fn svg_has_a_path_per_strand() {
+        let svg = plot().svg();
+        assert!(svg.contains("width=\"200mm\" height=\"100mm\" viewBox=\"0 0 200 100\""));
+        // The 80 millimetre square view sits in the middle of the paper, 8 millimetres to a
+        // unit at this distance.
+        let paths: Vec<&str> = svg
+            .lines()
+            .filter(|line| line.starts_with("<path"))
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                "<path d=\"M92.00 46.00 L108.00 46.00\"/>",
+                "<path d=\"M92.00 54.00 L108.00 54.00\"/>",
+            ]
+        );
+    }
+
+    #[test]
+    fn hpgl_is_in_plotter_units() {
+        let hpgl = plot().hpgl();
+        assert!(hpgl.starts_with("IN;SP1;\n"));
+        assert!(hpgl.ends_with("\nPU;SP0;IN;\n"));
+        // Forty units to the millimetre, from the bottom of the paper, nearest line first.
+        let lines: Vec<&str> = hpgl.lines().filter(|line| line.starts_with("PU")).collect();
+        assert_eq!(
+            lines,
+            [
+                "PU3680,2160;PD4320,2160;",
+                "PU4320,1840;PD3680,1840;",
+                "PU;SP0;IN;"
+            ]
+        );
+    }
+}
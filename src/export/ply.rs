@@ -0,0 +1,41 @@
+use super::{Mesh, linear_to_srgb};
+
+pub fn write(mesh: &Mesh) -> Vec<u8> {
+    let header = format!(
+        "ply\n\
+         format binary_little_endian 1.0\n\
+         comment Generated by noodles\n\
+         element vertex {}\n\
+         property float x\n\
+         property float y\n\
+         property float z\n\
+         property float nx\n\
+         property float ny\n\
+         property float nz\n\
+         property uchar red\n\
+         property uchar green\n\
+         property uchar blue\n\
+         element face {}\n\
+         property list uchar uint vertex_indices\n\
+         end_header\n",
+        mesh.positions.len(),
+        mesh.triangles.len()
+    );
+    let mut bytes = header.into_bytes();
+    for ((position, normal), colour) in mesh.positions.iter().zip(&mesh.normals).zip(&mesh.colours)
+    {
+        for value in position.to_array().into_iter().chain(normal.to_array()) {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        for value in colour.to_array() {
+            bytes.push((linear_to_srgb(value) * 255.0).round() as u8);
+        }
+    }
+    for triangle in &mesh.triangles {
+        bytes.push(3);
+        for index in triangle {
+            bytes.extend_from_slice(&index.to_le_bytes());
+        }
+    }
+    bytes
+}
@@ -0,0 +1,453 @@
+use anyhow::{Context, ensure};
+use glam::{UVec3, Vec3, uvec3};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+use super::Strand;
+
+/// How the strands are turned into a solid for 3D printing. Lengths are in millimetres, which
+/// is also the unit of the STL coordinates.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PrintOptions {
+    /// Length of the longest side of the clipping box once printed.
+    pub size: f32,
+    /// Thinner strands are thickened to this radius.
+    pub min_radius: f32,
+    /// Box in scene units that strands are clipped to, by default the bounds of all of them.
+    pub min: Option<Vec3>,
+    pub max: Option<Vec3>,
+    /// Number of distance samples along the longest side of the clipping box, up to
+    /// [`Solid::MAX_RESOLUTION`].
+    pub resolution: u32,
+    pub base_plate: Option<BasePlate>,
+}
+
+/// A slab under the clipping box that strands reaching its floor are merged into.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BasePlate {
+    pub thickness: f32,
+    /// How far the plate extends beyond the clipping box in X and Y.
+    pub margin: f32,
+}
+
+/// A closed, consistently wound triangle mesh.
+#[derive(Debug, Default, Clone)]
+pub struct Solid {
+    pub positions: Vec<Vec3>,
+    pub triangles: Vec<[u32; 3]>,
+}
+
+/// Edges that keep a mesh from being a closed 2-manifold, by how they are used.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ManifoldReport {
+    pub edges: usize,
+    /// Edges on only one triangle, i.e. holes.
+    pub boundary: usize,
+    /// Edges shared by more than two triangles.
+    pub overshared: usize,
+    /// Edges whose two triangles traverse them in the same direction.
+    pub misoriented: usize,
+}
+
+impl Default for PrintOptions {
+    fn default() -> Self {
+        Self {
+            size: 100.0,
+            min_radius: 0.5,
+            min: None,
+            max: None,
+            resolution: 256,
+            base_plate: None,
+        }
+    }
+}
+
+impl Default for BasePlate {
+    fn default() -> Self {
+        Self {
+            thickness: 2.0,
+            margin: 5.0,
+        }
+    }
+}
+
+/// Samples outside the box around the surface are left padded this many cells wide.
+const PADDING: u32 = 2;
+
+/// Limit on the distance samples of a print, of four bytes each, which a wide base plate can
+/// reach below the maximum resolution.
+const MAX_SAMPLES: usize = 1 << 26;
+
+/// Cubes are split into six tetrahedra around their main diagonal, which tiles space
+/// consistently across neighbouring cubes. Corners are numbered with x in bit 0, y in bit 1
+/// and z in bit 2.
+const TETRAHEDRA: [[usize; 4]; 6] = [
+    [0, 1, 3, 7],
+    [0, 1, 5, 7],
+    [0, 2, 3, 7],
+    [0, 2, 6, 7],
+    [0, 4, 5, 7],
+    [0, 4, 6, 7],
+];
+
+impl Solid {
+    /// Highest resolution on offer, whose cube of samples stays under `MAX_SAMPLES`.
+    pub const MAX_RESOLUTION: u32 = 384;
+
+    /// Meshes the union of capsules around the strand segments, intersected with the clipping
+    /// box and unioned with the base plate, as a level set of a sampled signed distance field.
+    /// Marching tetrahedra over the samples gives a closed manifold by construction, and every
+    /// strand end is capped with a hemisphere.
+    pub fn from_strands(strands: &[Strand], options: &PrintOptions) -> anyhow::Result<Self> {
+        ensure!(
+            (16..=Self::MAX_RESOLUTION).contains(&options.resolution),
+            "Print resolution must be between 16 and {}",
+            Self::MAX_RESOLUTION
+        );
+        let (strands_min, strands_max) = strands
+            .iter()
+            .flat_map(|strand| &strand.points)
+            .fold((Vec3::INFINITY, Vec3::NEG_INFINITY), |(min, max), point| {
+                (min.min(point.position), max.max(point.position))
+            });
+        let clip_min = options.min.unwrap_or(strands_min);
+        let clip_max = options.max.unwrap_or(strands_max);
+        ensure!(
+            clip_max.cmpgt(clip_min).all(),
+            "There are no strands inside the print box"
+        );
+        let millimetres_per_unit = options.size / (clip_max - clip_min).max_element();
+        let min_radius = options.min_radius / millimetres_per_unit;
+        let cell = (clip_max - clip_min).max_element() / options.resolution as f32;
+
+        let plate = options.base_plate.as_ref().map(|plate| {
+            let margin = Vec3::new(plate.margin, plate.margin, 0.0) / millimetres_per_unit;
+            let top = clip_min.z + plate.thickness / millimetres_per_unit;
+            (clip_min - margin, clip_max.with_z(top) + margin)
+        });
+        let (domain_min, domain_max) = match plate {
+            Some((plate_min, plate_max)) => (clip_min.min(plate_min), clip_max.max(plate_max)),
+            None => (clip_min, clip_max),
+        };
+
+        let mut grid = Grid::new(
+            domain_min - cell * PADDING as f32,
+            domain_max + cell * PADDING as f32,
+            cell,
+        )?;
+        for strand in strands {
+            for pair in strand.points.windows(2) {
+                let radius = pair[0].radius.max(min_radius);
+                grid.add_capsule(pair[0].position, pair[1].position, radius);
+            }
+        }
+        grid.combine(|position, strands| {
+            let clipped = strands.max(box_distance(position, clip_min, clip_max));
+            match plate {
+                Some((plate_min, plate_max)) => {
+                    clipped.min(box_distance(position, plate_min, plate_max))
+                }
+                None => clipped,
+            }
+        });
+
+        let mut solid = grid.march_tetrahedra();
+        ensure!(
+            !solid.triangles.is_empty(),
+            "There are no strands inside the print box"
+        );
+        for position in &mut solid.positions {
+            *position = (*position - domain_min) * millimetres_per_unit;
+        }
+        Ok(solid)
+    }
+
+    pub fn manifold_report(&self) -> ManifoldReport {
+        // Uses of each undirected edge in its forward and backward directions.
+        let mut uses: HashMap<(u32, u32), (u32, u32)> = HashMap::new();
+        for triangle in &self.triangles {
+            for i in 0..3 {
+                let (a, b) = (triangle[i], triangle[(i + 1) % 3]);
+                let entry = uses.entry((a.min(b), a.max(b))).or_default();
+                if a < b {
+                    entry.0 += 1;
+                } else {
+                    entry.1 += 1;
+                }
+            }
+        }
+        let mut report = ManifoldReport {
+            edges: uses.len(),
+            ..Default::default()
+        };
+        for (forward, backward) in uses.into_values() {
+            match forward + backward {
+                1 => report.boundary += 1,
+                2 if forward != backward => report.misoriented += 1,
+                2 => {}
+                _ => report.overshared += 1,
+            }
+        }
+        report
+    }
+
+    /// Binary STL, with facet normals from the winding.
+    pub fn save_stl(&self, path: &Path) -> anyhow::Result<()> {
+        let mut bytes = Vec::with_capacity(84 + 50 * self.triangles.len());
+        let mut header = [0u8; 80];
+        header[..18].copy_from_slice(b"noodles STL export");
+        bytes.extend_from_slice(&header);
+        bytes.extend_from_slice(&(self.triangles.len() as u32).to_le_bytes());
+        for triangle in &self.triangles {
+            let [a, b, c] = triangle.map(|index| self.positions[index as usize]);
+            let normal = (b - a).cross(c - a).normalize_or_zero();
+            for vector in [normal, a, b, c] {
+                for value in vector.to_array() {
+                    bytes.extend_from_slice(&value.to_le_bytes());
+                }
+            }
+            bytes.extend_from_slice(&0u16.to_le_bytes());
+        }
+        std::fs::write(path, bytes).with_context(|| format!("Unable to write {}", path.display()))
+    }
+}
+
+impl ManifoldReport {
+    pub fn is_manifold(&self) -> bool {
+        self.boundary == 0 && self.overshared == 0 && self.misoriented == 0
+    }
+}
+
+impl std::fmt::Display for ManifoldReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} edges, {} boundary, {} shared by more than two triangles, {} misoriented",
+            self.edges, self.boundary, self.overshared, self.misoriented
+        )
+    }
+}
+
+/// Signed distances sampled on the corners of cubic cells, with `x` varying fastest. Only
+/// samples near a surface hold true distances; the rest are clamped to `band`.
+struct Grid {
+    origin: Vec3,
+    cell: f32,
+    dimensions: UVec3,
+    band: f32,
+    values: Vec<f32>,
+}
+
+impl Grid {
+    fn new(min: Vec3, max: Vec3, cell: f32) -> anyhow::Result<Self> {
+        let samples = ((max - min) / cell).ceil() + Vec3::ONE;
+        ensure!(
+            samples.element_product() <= MAX_SAMPLES as f32,
+            "The print needs {} distance samples, more than the limit of {MAX_SAMPLES}; lower \
+             its resolution or base plate margin",
+            samples.element_product()
+        );
+        let dimensions = samples.as_uvec3();
+        let band = 2.0 * cell;
+        Ok(Self {
+            origin: min,
+            cell,
+            dimensions,
+            band,
+            values: vec![band; dimensions.element_product() as usize],
+        })
+    }
+
+    fn index(&self, point: UVec3) -> usize {
+        ((point.z * self.dimensions.y + point.y) * self.dimensions.x + point.x) as usize
+    }
+
+    fn position(&self, point: UVec3) -> Vec3 {
+        self.origin + point.as_vec3() * self.cell
+    }
+
+    fn add_capsule(&mut self, a: Vec3, b: Vec3, radius: f32) {
+        let reach = Vec3::splat(radius + self.band);
+        let lower = ((a.min(b) - reach - self.origin) / self.cell)
+            .floor()
+            .max(Vec3::ZERO)
+            .as_uvec3();
+        let upper = ((a.max(b) + reach - self.origin) / self.cell)
+            .ceil()
+            .as_uvec3()
+            .min(self.dimensions - UVec3::ONE);
+        for z in lower.z..=upper.z {
+            for y in lower.y..=upper.y {
+                for x in lower.x..=upper.x {
+                    let point = uvec3(x, y, z);
+                    let distance = capsule_distance(self.position(point), a, b) - radius;
+                    let index = self.index(point);
+                    self.values[index] = self.values[index].min(distance);
+                }
+            }
+        }
+    }
+
+    /// Replaces every sample with `f(position, sample)`, clamped to the band and nudged off
+    /// zero so that no surface vertex lands exactly on a sample.
+    fn combine(&mut self, f: impl Fn(Vec3, f32) -> f32) {
+        let epsilon = 1e-4 * self.cell;
+        for z in 0..self.dimensions.z {
+            for y in 0..self.dimensions.y {
+                for x in 0..self.dimensions.x {
+                    let point = uvec3(x, y, z);
+                    let index = self.index(point);
+                    let value = f(self.position(point), self.values[index]).min(self.band);
+                    self.values[index] = if value.abs() < epsilon {
+                        value.signum() * epsilon
+                    } else {
+                        value
+                    };
+                }
+            }
+        }
+    }
+
+    fn march_tetrahedra(&self) -> Solid {
+        let mut solid = Solid::default();
+        // Surface vertices by the pair of samples whose edge they lie on, so that neighbouring
+        // tetrahedra share them.
+        let mut vertices: HashMap<(usize, usize), u32> = HashMap::new();
+        let mut vertex = |solid: &mut Solid, a: usize, b: usize, pa: Vec3, pb: Vec3| {
+            *vertices.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                let (va, vb) = (self.values[a], self.values[b]);
+                solid.positions.push(pa.lerp(pb, va / (va - vb)));
+                solid.positions.len() as u32 - 1
+            })
+        };
+
+        for z in 0..self.dimensions.z - 1 {
+            for y in 0..self.dimensions.y - 1 {
+                for x in 0..self.dimensions.x - 1 {
+                    let corners: [UVec3; 8] = std::array::from_fn(|i| {
+                        uvec3(x, y, z) + uvec3(i as u32 & 1, (i as u32 >> 1) & 1, i as u32 >> 2)
+                    });
+                    let indices = corners.map(|corner| self.index(corner));
+                    let inside = indices.map(|index| self.values[index] < 0.0);
+                    if inside.iter().all(|&i| i) || inside.iter().all(|&i| !i) {
+                        continue;
+                    }
+                    let positions = corners.map(|corner| self.position(corner));
+
+                    for tetrahedron in TETRAHEDRA {
+                        let (ins, outs): (Vec<usize>, Vec<usize>) =
+                            tetrahedron.iter().partition(|&&corner| inside[corner]);
+                        let mut edge = |i: usize, o: usize| {
+                            vertex(
+                                &mut solid,
+                                indices[i],
+                                indices[o],
+                                positions[i],
+                                positions[o],
+                            )
+                        };
+                        let polygon = match (ins.len(), outs.len()) {
+                            (1, 3) => vec![
+                                edge(ins[0], outs[0]),
+                                edge(ins[0], outs[1]),
+                                edge(ins[0], outs[2]),
+                            ],
+                            (3, 1) => vec![
+                                edge(ins[0], outs[0]),
+                                edge(ins[1], outs[0]),
+                                edge(ins[2], outs[0]),
+                            ],
+                            (2, 2) => vec![
+                                edge(ins[0], outs[0]),
+                                edge(ins[0], outs[1]),
+                                edge(ins[1], outs[1]),
+                                edge(ins[1], outs[0]),
+                            ],
+                            _ => continue,
+                        };
+                        // Wind each polygon to face away from the inside of the tetrahedron.
+                        let centroid = |corners: &[usize]| {
+                            corners.iter().map(|&i| positions[i]).sum::<Vec3>()
+                                / corners.len() as f32
+                        };
+                        let outward = centroid(&outs) - centroid(&ins);
+                        let [a, b, c] = [0, 1, 2].map(|i| solid.positions[polygon[i] as usize]);
+                        let normal = (b - a).cross(c - a);
+                        let polygon = if normal.dot(outward) < 0.0 {
+                            polygon.into_iter().rev().collect()
+                        } else {
+                            polygon
+                        };
+                        for i in 1..polygon.len() - 1 {
+                            solid
+                                .triangles
+                                .push([polygon[0], polygon[i], polygon[i + 1]]);
+                        }
+                    }
+                }
+            }
+        }
+        solid
+    }
+}
+
+fn capsule_distance(position: Vec3, a: Vec3, b: Vec3) -> f32 {
+    let axis = b - a;
+    let t =
+        ((position - a).dot(axis) / axis.length_squared().max(f32::MIN_POSITIVE)).clamp(0.0, 1.0);
+    position.distance(a + t * axis)
+}
+
+fn box_distance(position: Vec3, min: Vec3, max: Vec3) -> f32 {
+    let centre = 0.5 * (min + max);
+    let q = (position - centre).abs() - 0.5 * (max - min);
+    q.max(Vec3::ZERO).length() + q.max_element().min(0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::StrandPoint;
+
+    fn strand(points: &[Vec3]) -> Strand {
+        Strand {
+            points: points
+                .iter()
+                .map(|&position| StrandPoint {
+                    position,
+                    normal: Vec3::X,
+                    bitangent: Vec3::Y,
+                    radius: 0.05,
+                })
+                .collect(),
+            colour: Vec3::ONE,
+        }
+    }
+
+    #[test]
+    fn strands_make_a_closed_solid() {
+        let strands = [
+            strand(&[Vec3::ZERO, Vec3::new(0.5, 0.2, 0.5), Vec3::ONE]),
+            strand(&[Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 1.0)]),
+        ];
+        for base_plate in [None, Some(BasePlate::default())] {
+            let options = PrintOptions {
+                resolution: 32,
+                base_plate,
+                ..Default::default()
+            };
+            let solid = Solid::from_strands(&strands, &options).unwrap();
+            let report = solid.manifold_report();
+            assert!(report.is_manifold(), "{report}");
+        }
+
+        let options = PrintOptions {
+            resolution: Solid::MAX_RESOLUTION + 1,
+            ..Default::default()
+        };
+        assert!(Solid::from_strands(&strands, &options).is_err());
+    }
+}
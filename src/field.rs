@@ -0,0 +1,39 @@
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// The vector field that strands are traced through.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "source", rename_all = "snake_case")]
+pub enum Field {
+    #[default]
+    CurlNoise,
+    /// Vectors sampled trilinearly from a raw or legacy VTK volume file. Outside its bounds the
+    /// vectors on the nearest face are used.
+    Volume {
+        path: PathBuf,
+        /// Override the bounds stored in the volume file.
+        min: Option<Vec3>,
+        max: Option<Vec3>,
+        /// Multiplies the stored vectors, e.g. by -1 to trace upstream.
+        #[serde(default = "unit_scale")]
+        scale: f32,
+        #[serde(default)]
+        precision: Precision,
+    },
+}
+
+/// Texel format the vector field is uploaded in.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Precision {
+    /// `Rgba32Float`
+    #[default]
+    Full,
+    /// `Rgba16Float`, half the memory.
+    Half,
+}
+
+fn unit_scale() -> f32 {
+    1.0
+}
@@ -0,0 +1,82 @@
+/// The depth buffer, and the multisampled colour buffer if there is one, for drawing the
+/// strands into a texture of a given size.
+pub struct Framebuffer {
+    depth: wgpu::TextureView,
+    /// Resolved into the target each frame, when multisampling.
+    multisampled: Option<wgpu::TextureView>,
+}
+
+impl Framebuffer {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+        sample_count: u32,
+    ) -> Self {
+        let texture = |label, format| {
+            device
+                .create_texture(&wgpu::TextureDescriptor {
+                    label: Some(label),
+                    size: wgpu::Extent3d {
+                        width,
+                        height,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count,
+                    dimension: wgpu::TextureDimension::D2,
+                    format,
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                    view_formats: &[],
+                })
+                .create_view(&Default::default())
+        };
+        Self {
+            depth: texture("Depth buffer", wgpu::TextureFormat::Depth32Float),
+            multisampled: (sample_count > 1).then(|| texture("Multisampled colour buffer", format)),
+        }
+    }
+
+    /// Starts a pass that clears `target` to `clear_colour` and draws into it, by way of the
+    /// multisampled buffer if there is one.
+    pub fn begin_render_pass<'encoder>(
+        &self,
+        encoder: &'encoder mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        clear_colour: wgpu::Color,
+        timestamp_writes: Option<wgpu::RenderPassTimestampWrites>,
+    ) -> wgpu::RenderPass<'encoder> {
+        let (view, resolve_target) = match &self.multisampled {
+            Some(multisampled) => (multisampled, Some(target)),
+            None => (target, None),
+        };
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(clear_colour),
+                    // Only the resolved colours are needed after the pass.
+                    store: if resolve_target.is_some() {
+                        wgpu::StoreOp::Discard
+                    } else {
+                        wgpu::StoreOp::Store
+                    },
+                },
+                depth_slice: None,
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.depth,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(0.0),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes,
+        })
+    }
+}
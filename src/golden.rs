@@ -0,0 +1,426 @@
+use anyhow::Context;
+use std::path::{Path, PathBuf};
+
+use crate::State;
+use crate::pipelines::{GrowthMode, Integrator, Pipelines};
+use crate::profiler::Profiler;
+use crate::scene::Scene;
+use crate::sources::{self, Fixed, SourceFrame, StrandSource};
+use crate::view::Camera;
+
+/// Renders are small, as the software adapters they are made on are slow.
+const SIZE: u32 = 256;
+/// Renders pass if their mean similarity to the reference is at least this.
+const MIN_SSIM: f32 = 0.98;
+/// Windows less similar than this are outlined in the diff images.
+const MARKED_SSIM: f32 = 0.9;
+/// Setting this environment variable writes the references afresh instead of checking them.
+const UPDATE: &str = "UPDATE_GOLDEN";
+
+/// Setting this environment variable skips the tests that need a software adapter on machines
+/// without one. Otherwise they fail there, so that a CI machine losing its adapter is noticed.
+const NO_ADAPTER: &str = "NOODLES_NO_ADAPTER";
+
+/// A device on a software adapter, or `None` if there is no such adapter here and the tests
+/// needing one are to be skipped.
+pub fn fallback_device() -> Option<(wgpu::Device, wgpu::Queue)> {
+    fallback_device_with(wgpu::Limits::default(), wgpu::Features::empty())
+}
+
+/// A device on a software adapter held to `limits`, such as those of WebGL2.
+fn fallback_device_with_limits(limits: wgpu::Limits) -> Option<(wgpu::Device, wgpu::Queue)> {
+    fallback_device_with(limits, wgpu::Features::empty())
+}
+
+/// A device on a software adapter with whichever of `features` the adapter has.
+pub fn fallback_device_with_features(
+    features: wgpu::Features,
+) -> Option<(wgpu::Device, wgpu::Queue)> {
+    fallback_device_with(wgpu::Limits::default(), features)
+}
+
+fn fallback_device_with(
+    limits: wgpu::Limits,
+    features: wgpu::Features,
+) -> Option<(wgpu::Device, wgpu::Queue)> {
+    let device = request_fallback_device(limits, features);
+    if device.is_none() {
+        assert!(
+            std::env::var_os(NO_ADAPTER).is_some(),
+            "There is no software adapter; set {NO_ADAPTER}=1 to skip the tests needing one"
+        );
+        eprintln!("Skipping, as there is no fallback adapter");
+    }
+    device
+}
+
+fn request_fallback_device(
+    limits: wgpu::Limits,
+    features: wgpu::Features,
+) -> Option<(wgpu::Device, wgpu::Queue)> {
+    let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+        backends: wgpu::Backends::all(),
+        ..Default::default()
+    });
+    let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+        force_fallback_adapter: true,
+        ..Default::default()
+    }))
+    .ok()
+    .or_else(|| {
+        instance
+            .enumerate_adapters(wgpu::Backends::all())
+            .into_iter()
+            .find(|adapter| adapter.get_info().device_type == wgpu::DeviceType::Cpu)
+    })?;
+    pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
+        required_features: features & adapter.features(),
+        required_limits: limits,
+        ..Default::default()
+    }))
+    .ok()
+}
+
+/// What to render and when. Trail mode is left out, as the software adapters take minutes to
+/// compile its shader.
+struct Case {
+    name: &'static str,
+    scene: Scene,
+    source: fn(&Pipelines) -> Box<dyn StrandSource>,
+    /// Seconds since the demo started.
+    seconds: f32,
+    growth_progress: f32,
+}
+
+fn cases() -> anyhow::Result<Vec<Case>> {
+    let mut rk4 = Scene::default();
+    rk4.simulation.integrator = Integrator::Rk4;
+    let mut grow = Scene::default();
+    grow.simulation.growth = GrowthMode::Grow { duration: 10.0 };
+    let obstacles =
+        Scene::load(&Path::new(env!("CARGO_MANIFEST_DIR")).join("scenes/obstacles.toml"))?;
+    let curl_noise: fn(&Pipelines) -> Box<dyn StrandSource> = sources::curl_noise_in_full;
+    Ok(vec![
+        Case {
+            name: "curl_noise",
+            scene: Scene::default(),
+            source: curl_noise,
+            seconds: 0.0,
+            growth_progress: 1.0,
+        },
+        Case {
+            name: "curl_noise_rk4",
+            scene: rk4,
+            source: curl_noise,
+            seconds: 30.0,
+            growth_progress: 1.0,
+        },
+        Case {
+            name: "half_grown",
+            scene: grow,
+            source: curl_noise,
+            seconds: 5.0,
+            growth_progress: 0.5,
+        },
+        Case {
+            name: "obstacles",
+            scene: obstacles,
+            source: curl_noise,
+            seconds: 4.0,
+            growth_progress: 1.0,
+        },
+        Case {
+            name: "sinusoid",
+            scene: Scene::default(),
+            source: |_| {
+                Box::new(Fixed::sinusoid(
+                    Camera::default().centre,
+                    State::IMPORT_SIZE,
+                ))
+            },
+            seconds: 0.0,
+            growth_progress: 1.0,
+        },
+    ])
+}
+
+/// Renders `case` as the demo would show it, returning sRGB encoded RGBA pixels.
+fn render(device: &wgpu::Device, queue: &wgpu::Queue, case: &Case) -> anyhow::Result<Vec<u8>> {
+    let format = wgpu::TextureFormat::Rgba8UnormSrgb;
+    let mut pipelines = Pipelines::new(device, queue, format, 1);
+    let scene = &case.scene;
+    pipelines.update_seeds(
+        queue,
+        &scene
+            .seeding
+            .generate(Pipelines::NUM_STRANDS, scene.random_seed)?,
+    )?;
+    pipelines.update_obstacles(queue, &scene.obstacles, case.seconds);
+    pipelines.update_simulation(queue, &scene.simulation, case.growth_progress, case.seconds);
+    State::update_camera(&mut pipelines, queue, scene, 1.0, case.seconds);
+    let mut source = (case.source)(&pipelines);
+    source.start(device, queue, &mut pipelines)?;
+
+    let extent = wgpu::Extent3d {
+        width: SIZE,
+        height: SIZE,
+        depth_or_array_layers: 1,
+    };
+    let texture = |label, format, usage| {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: extent,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage,
+            view_formats: &[],
+        })
+    };
+    let colour = texture(
+        "Golden colour",
+        format,
+        wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+    );
+    let depth = texture(
+        "Golden depth",
+        wgpu::TextureFormat::Depth32Float,
+        wgpu::TextureUsages::RENDER_ATTACHMENT,
+    );
+
+    let mut encoder = device.create_command_encoder(&Default::default());
+    source.update(SourceFrame {
+        device,
+        queue,
+        encoder: &mut encoder,
+        pipelines: &mut pipelines,
+        profiler: &mut Profiler::new(device, queue),
+        simulation: scene.simulation,
+        growth_progress: case.growth_progress,
+        time: case.seconds,
+    })?;
+    {
+        let colour_view = colour.create_view(&Default::default());
+        let depth_view = depth.create_view(&Default::default());
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Golden render pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &colour_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(scene.lighting.clear_colour()),
+                    store: wgpu::StoreOp::Store,
+                },
+                depth_slice: None,
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(0.0),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        pipelines.render(&mut render_pass);
+    }
+    queue.submit([encoder.finish()]);
+
+    // Rows of 256 pixels already meet the copy alignment.
+    let bytes_per_row = 4 * SIZE;
+    crate::pipelines::read_back(
+        device,
+        queue,
+        (bytes_per_row * SIZE) as u64,
+        |encoder, buffer| {
+            encoder.copy_texture_to_buffer(
+                colour.as_image_copy(),
+                wgpu::TexelCopyBufferInfo {
+                    buffer,
+                    layout: wgpu::TexelCopyBufferLayout {
+                        offset: 0,
+                        bytes_per_row: Some(bytes_per_row),
+                        rows_per_image: Some(SIZE),
+                    },
+                },
+                extent,
+            );
+        },
+    )
+}
+
+fn load_png(path: &Path) -> anyhow::Result<Vec<u8>> {
+    let bytes =
+        std::fs::read(path).with_context(|| format!("Unable to read {}", path.display()))?;
+    let mut decoder = png::Decoder::new(std::io::Cursor::new(bytes));
+    decoder.set_transformations(png::Transformations::ALPHA);
+    let mut reader = decoder.read_info()?;
+    let mut pixels = vec![0; reader.output_buffer_size().context("PNG is too large")?];
+    let info = reader.next_frame(&mut pixels)?;
+    anyhow::ensure!(
+        info.width == SIZE && info.height == SIZE && info.color_type == png::ColorType::Rgba,
+        "{} is not a {SIZE}×{SIZE} RGBA image",
+        path.display()
+    );
+    pixels.truncate(info.buffer_size());
+    Ok(pixels)
+}
+
+fn save_png(path: &Path, pixels: &[u8]) -> anyhow::Result<()> {
+    if let Some(directory) = path.parent() {
+        std::fs::create_dir_all(directory)?;
+    }
+    let file = std::fs::File::create(path)
+        .with_context(|| format!("Unable to write {}", path.display()))?;
+    let mut encoder = png::Encoder::new(std::io::BufWriter::new(file), SIZE, SIZE);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(pixels)?;
+    Ok(())
+}
+
+/// Structural similarity of the luma of two images, per 8×8 window stepped by 4 pixels. Windows
+/// that are flat in both images, such as empty background, are left out.
+fn ssim(a: &[u8], b: &[u8]) -> Vec<(u32, u32, f32)> {
+    const WINDOW: u32 = 8;
+    const STEP: u32 = 4;
+    const C1: f32 = 0.01 * 0.01;
+    const C2: f32 = 0.03 * 0.03;
+    let luma = |pixels: &[u8], x: u32, y: u32| {
+        let i = 4 * (y * SIZE + x) as usize;
+        (0.299 * pixels[i] as f32 + 0.587 * pixels[i + 1] as f32 + 0.114 * pixels[i + 2] as f32)
+            / 255.0
+    };
+    let mut windows = Vec::new();
+    for y in (0..=SIZE - WINDOW).step_by(STEP as usize) {
+        for x in (0..=SIZE - WINDOW).step_by(STEP as usize) {
+            let samples: Vec<(f32, f32)> = (0..WINDOW * WINDOW)
+                .map(|i| {
+                    let (x, y) = (x + i % WINDOW, y + i / WINDOW);
+                    (luma(a, x, y), luma(b, x, y))
+                })
+                .collect();
+            let n = samples.len() as f32;
+            let mean_a = samples.iter().map(|(a, _)| a).sum::<f32>() / n;
+            let mean_b = samples.iter().map(|(_, b)| b).sum::<f32>() / n;
+            let (mut var_a, mut var_b, mut covariance) = (0.0, 0.0, 0.0);
+            for (a, b) in &samples {
+                var_a += (a - mean_a) * (a - mean_a) / n;
+                var_b += (b - mean_b) * (b - mean_b) / n;
+                covariance += (a - mean_a) * (b - mean_b) / n;
+            }
+            if var_a + var_b < 1e-6 && (mean_a - mean_b).abs() < 1e-3 {
+                continue;
+            }
+            let similarity = ((2.0 * mean_a * mean_b + C1) * (2.0 * covariance + C2))
+                / ((mean_a * mean_a + mean_b * mean_b + C1) * (var_a + var_b + C2));
+            windows.push((x, y, similarity));
+        }
+    }
+    windows
+}
+
+/// The render dimmed, with the windows that fail outlined in red.
+fn diff_image(actual: &[u8], windows: &[(u32, u32, f32)]) -> Vec<u8> {
+    let mut pixels: Vec<u8> = actual
+        .chunks_exact(4)
+        .flat_map(|pixel| [pixel[0] / 3, pixel[1] / 3, pixel[2] / 3, 255])
+        .collect();
+    for &(x, y, similarity) in windows {
+        if similarity >= MARKED_SSIM {
+            continue;
+        }
+        for i in 0..8 {
+            for (px, py) in [(x + i, y), (x + i, y + 7), (x, y + i), (x + 7, y + i)] {
+                let index = 4 * (py * SIZE + px) as usize;
+                pixels[index..index + 3].copy_from_slice(&[255, 0, 0]);
+            }
+        }
+    }
+    pixels
+}
+
+fn manifest_directory() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+}
+
+/// Compares a render of the case called `name` with its reference, describing the failure if
+/// it is not similar enough. Renders that fail are written to `target/golden` as `output`, each
+/// beside a diff image.
+fn compare(name: &str, output: &str, actual: &[u8]) -> Option<String> {
+    let reference_path = manifest_directory().join(format!("tests/golden/{name}.png"));
+    let reference = load_png(&reference_path)
+        .with_context(|| format!("Run with {UPDATE}=1 to make the reference"))
+        .unwrap();
+    let windows = ssim(&reference, actual);
+    let mean = if windows.is_empty() {
+        1.0
+    } else {
+        windows
+            .iter()
+            .map(|&(_, _, similarity)| similarity)
+            .sum::<f32>()
+            / windows.len() as f32
+    };
+    if mean >= MIN_SSIM {
+        return None;
+    }
+    let failures_directory = manifest_directory().join("target/golden");
+    let actual_path = failures_directory.join(format!("{output}.png"));
+    let diff_path = failures_directory.join(format!("{output}-diff.png"));
+    save_png(&actual_path, actual).unwrap();
+    save_png(&diff_path, &diff_image(actual, &windows)).unwrap();
+    Some(format!(
+        "{output}: mean SSIM {mean:.3}, see {} and {}",
+        actual_path.display(),
+        diff_path.display()
+    ))
+}
+
+/// Renders that fail are written to `target/golden`, each beside a diff image. After a change
+/// to the look that is meant, run with `UPDATE_GOLDEN=1` and commit the new references.
+#[test]
+fn renders_match_references() {
+    let Some((device, queue)) = fallback_device() else {
+        return;
+    };
+    let mut failures = Vec::new();
+    for case in cases().unwrap() {
+        let actual = render(&device, &queue, &case).unwrap();
+        if std::env::var_os(UPDATE).is_some() {
+            let reference_path =
+                manifest_directory().join(format!("tests/golden/{}.png", case.name));
+            save_png(&reference_path, &actual).unwrap();
+            continue;
+        }
+        failures.extend(compare(case.name, case.name, &actual));
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+/// Held to the limits of WebGL2, the demo traces strands on the CPU and draws them from a vertex
+/// buffer, which should look the same as the compute shaders and storage buffers do. The CPU
+/// tracer leaves out obstacles, so that case is skipped.
+#[test]
+fn webgl2_renders_match_references() {
+    let limits = wgpu::Limits::downlevel_webgl2_defaults();
+    let Some((device, queue)) = fallback_device_with_limits(limits) else {
+        return;
+    };
+    let mut failures = Vec::new();
+    for case in cases().unwrap() {
+        if case.name == "obstacles" {
+            continue;
+        }
+        let actual = render(&device, &queue, &case).unwrap();
+        failures.extend(compare(
+            case.name,
+            &format!("webgl2_{}", case.name),
+            &actual,
+        ));
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
@@ -0,0 +1,643 @@
+use anyhow::{Context, bail};
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use winit::dpi::PhysicalSize;
+
+use crate::State;
+use crate::adapter::{self, GraphicsOptions};
+use crate::cli::{Cli, Command};
+use crate::config::Config;
+use crate::export::{self, Strand, curves::CurveFrame};
+use crate::framebuffer::Framebuffer;
+use crate::pipelines::{GrowthMode, Pipelines};
+use crate::profiler::{Pass, Profiler, Timings};
+use crate::scene::Scene;
+use crate::sources::{self, SourceFrame, StrandSource};
+
+/// The demo drawn into a texture instead of a window, for the `render` and `export`
+/// subcommands. Strands start growing at time zero, however late the first frame is.
+struct Headless {
+    adapter_info: wgpu::AdapterInfo,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipelines: Pipelines,
+    source: Box<dyn StrandSource>,
+    scene: Scene,
+    size: PhysicalSize<u32>,
+    framebuffer: Framebuffer,
+    colour: wgpu::Texture,
+    profiler: Profiler,
+    reset_trails: bool,
+}
+
+impl Headless {
+    const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+    const DEFAULT_SIZE: PhysicalSize<u32> = PhysicalSize::new(1920, 1080);
+
+    async fn new(
+        options: &GraphicsOptions,
+        scene: Scene,
+        size: PhysicalSize<u32>,
+    ) -> anyhow::Result<Self> {
+        let instance = adapter::instance(options).await;
+        let adapter = adapter::request_adapter(&instance, options, None).await?;
+        let (device, queue) = adapter::request_device(&adapter).await?;
+        let sample_count = adapter::sample_count(&adapter, Self::FORMAT, options.msaa);
+        Self::with_device(adapter.get_info(), device, queue, sample_count, scene, size)
+    }
+
+    /// Draws on `device`, from the adapter `adapter_info` describes.
+    fn with_device(
+        adapter_info: wgpu::AdapterInfo,
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        sample_count: u32,
+        scene: Scene,
+        size: PhysicalSize<u32>,
+    ) -> anyhow::Result<Self> {
+        let mut pipelines = Pipelines::new(&device, &queue, Self::FORMAT, sample_count);
+        State::load_sdf_volume(&mut pipelines, &device, &queue, &scene.obstacles)?;
+        State::load_vector_field(&mut pipelines, &device, &queue, &scene.field)?;
+        pipelines.update_seeds(
+            &queue,
+            &scene
+                .seeding
+                .generate(Pipelines::NUM_STRANDS, scene.random_seed)?,
+        )?;
+        let mut source = sources::curl_noise_in_full(&pipelines);
+        source.start(&device, &queue, &mut pipelines)?;
+
+        let framebuffer =
+            Framebuffer::new(&device, Self::FORMAT, size.width, size.height, sample_count);
+        let colour = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Headless colour"),
+            size: wgpu::Extent3d {
+                width: size.width,
+                height: size.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: Self::FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+
+        Ok(Self {
+            adapter_info,
+            profiler: Profiler::new(&device, &queue),
+            device,
+            queue,
+            pipelines,
+            source,
+            scene,
+            size,
+            framebuffer,
+            colour,
+            reset_trails: true,
+        })
+    }
+
+    /// As [`State::simulate`] does for the window.
+    fn simulate(&mut self, encoder: &mut wgpu::CommandEncoder, seconds: f32) {
+        State::update_camera(
+            &mut self.pipelines,
+            &self.queue,
+            &self.scene,
+            self.size.width as f32 / self.size.height as f32,
+            seconds,
+        );
+        self.pipelines
+            .update_obstacles(&self.queue, &self.scene.obstacles, seconds);
+
+        let growth_progress = match self.scene.simulation.growth {
+            GrowthMode::Grow { duration } => seconds / duration,
+            _ => 1.0,
+        };
+        if self.reset_trails {
+            self.pipelines.reset_trails(encoder);
+            self.reset_trails = false;
+        }
+        self.pipelines.update_simulation(
+            &self.queue,
+            &self.scene.simulation,
+            growth_progress,
+            seconds,
+        );
+
+        let frame = SourceFrame {
+            device: &self.device,
+            queue: &self.queue,
+            encoder,
+            pipelines: &mut self.pipelines,
+            profiler: &mut self.profiler,
+            simulation: self.scene.simulation,
+            growth_progress,
+            time: seconds,
+        };
+        if let Err(e) = self.source.update(frame) {
+            log::error!("Unable to update {}, {:#}", self.source.name(), e);
+        }
+    }
+
+    /// Records and submits the frame at `seconds`, without waiting for it to be drawn.
+    fn draw(&mut self, seconds: f32) {
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Headless render encoder"),
+            });
+        self.profiler.begin_frame(&self.device);
+        self.simulate(&mut encoder, seconds);
+        {
+            let view = self.colour.create_view(&Default::default());
+            let mut render_pass = self.framebuffer.begin_render_pass(
+                &mut encoder,
+                &view,
+                self.scene.lighting.clear_colour(),
+                self.profiler.render_timestamps(Pass::Render),
+            );
+            self.pipelines.render(&mut render_pass);
+        }
+        self.profiler.resolve(&self.device, &mut encoder);
+        self.queue.submit([encoder.finish()]);
+        self.profiler.end_frame();
+    }
+
+    /// Draws the frame at `seconds`, returning sRGB encoded RGBA pixels row by row.
+    fn render(&mut self, seconds: f32) -> anyhow::Result<Vec<u8>> {
+        self.draw(seconds);
+
+        let PhysicalSize { width, height } = self.size;
+        let row = 4 * width;
+        let padded_row = row.next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let bytes = crate::pipelines::read_back(
+            &self.device,
+            &self.queue,
+            (padded_row * height) as u64,
+            |encoder, buffer| {
+                encoder.copy_texture_to_buffer(
+                    self.colour.as_image_copy(),
+                    wgpu::TexelCopyBufferInfo {
+                        buffer,
+                        layout: wgpu::TexelCopyBufferLayout {
+                            offset: 0,
+                            bytes_per_row: Some(padded_row),
+                            rows_per_image: Some(height),
+                        },
+                    },
+                    self.colour.size(),
+                );
+            },
+        )?;
+        Ok(bytes
+            .chunks_exact(padded_row as usize)
+            .flat_map(|padded| &padded[..row as usize])
+            .copied()
+            .collect())
+    }
+
+    /// Simulates the strands at `seconds` and reads them back.
+    fn strands(&mut self, seconds: f32) -> anyhow::Result<Vec<Strand>> {
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Headless export encoder"),
+            });
+        self.simulate(&mut encoder, seconds);
+        self.queue.submit([encoder.finish()]);
+        export::read_strands(&self.device, &self.queue, &self.pipelines)
+    }
+
+    fn save_png(&self, path: &Path, pixels: &[u8]) -> anyhow::Result<()> {
+        let file = std::fs::File::create(path)
+            .with_context(|| format!("Unable to write {}", path.display()))?;
+        let mut encoder = png::Encoder::new(
+            std::io::BufWriter::new(file),
+            self.size.width,
+            self.size.height,
+        );
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header()?.write_image_data(pixels)?;
+        Ok(())
+    }
+}
+
+/// Runs a subcommand to the end, with the scene, quality and graphics options given to `cli`.
+/// Choices saved from the launcher are left out, so runs can be repeated.
+pub fn run(cli: &Cli, command: &Command) -> anyhow::Result<()> {
+    let size = cli.resolution.unwrap_or(Headless::DEFAULT_SIZE);
+    let mut scene = cli.scene()?;
+    if let Some(quality) = cli.quality {
+        quality.apply(&mut scene.simulation);
+    }
+    let graphics = cli.graphics(&Config::default());
+    let mut headless = pollster::block_on(Headless::new(&graphics, scene, size))?;
+    if let Some(quality) = cli.quality {
+        headless
+            .pipelines
+            .set_sides(&headless.device, quality.sides());
+    }
+    match command {
+        Command::Render {
+            output,
+            frames,
+            fps,
+        } => {
+            std::fs::create_dir_all(output)
+                .with_context(|| format!("Unable to create {}", output.display()))?;
+            for frame in 0..*frames {
+                let pixels = headless.render(cli.start + frame as f32 / fps)?;
+                let path = output.join(format!("frame-{frame:05}.png"));
+                headless.save_png(&path, &pixels)?;
+                log::info!("Rendered {}", path.display());
+            }
+            Ok(())
+        }
+        Command::Export { outputs } => export(&mut headless, outputs, cli.start),
+        Command::Bench {
+            strands,
+            segments,
+            sides,
+            frames,
+            warmup,
+            outputs,
+        } => {
+            let matrix = strands.iter().flat_map(|&strands| {
+                segments.iter().flat_map(move |&segments| {
+                    sides
+                        .iter()
+                        .map(move |&sides| (strands, segments, sides as usize))
+                })
+            });
+            let mut results = Vec::new();
+            for (strands, segments, sides) in matrix {
+                let timings = bench(
+                    &mut headless,
+                    (strands, segments, sides),
+                    (*warmup, *frames),
+                    cli.start,
+                )?;
+                results.push(BenchResult {
+                    strands,
+                    segments,
+                    sides,
+                    timings,
+                });
+            }
+            let report = BenchReport {
+                adapter: &headless.adapter_info,
+                size: headless.size,
+                frames: *frames,
+                timestamps: headless.profiler.has_timestamps(),
+                results,
+            };
+            if outputs.is_empty() {
+                print!("{}", report.csv());
+            }
+            for path in outputs {
+                let extension = path
+                    .extension()
+                    .and_then(|extension| extension.to_str())
+                    .map(str::to_ascii_lowercase);
+                let text = match extension.as_deref() {
+                    Some("csv") => report.csv(),
+                    Some("json") => report.json(),
+                    _ => bail!("Unknown benchmark format {}", path.display()),
+                };
+                std::fs::write(path, text)
+                    .with_context(|| format!("Unable to write {}", path.display()))?;
+                log::info!("Wrote {}", path.display());
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Draws `frames` frames, after `warmup` untimed ones, with `strands` strands of `segments`
+/// segments drawn as tubes of `sides` sides. Each frame is waited for before the next, so
+/// frame times include the GPU's work.
+fn bench(
+    headless: &mut Headless,
+    (strands, segments, sides): (u32, u32, usize),
+    (warmup, frames): (u32, u32),
+    start: f32,
+) -> anyhow::Result<Timings> {
+    let simulation = &mut headless.scene.simulation;
+    simulation.strands = strands;
+    simulation.segments = segments;
+    // Grown strands and trails would draw fewer segments, or as many as a trail is long.
+    simulation.growth = GrowthMode::Full;
+    headless.pipelines.set_sides(&headless.device, sides);
+    headless.profiler.set_interval(None);
+
+    for frame in 0..warmup + frames {
+        if frame == warmup {
+            headless.profiler.finish(&headless.device)?;
+        }
+        headless.draw(start + frame as f32 / 30.0);
+        headless.device.poll(wgpu::PollType::wait_indefinitely())?;
+    }
+    let timings = headless.profiler.finish(&headless.device)?;
+    log::info!(
+        "{strands} strands, {segments} segments, {sides} sides: {:.2} ms per frame",
+        timings.frame
+    );
+    Ok(timings)
+}
+
+/// The average times for one combination in a benchmark.
+struct BenchResult {
+    strands: u32,
+    segments: u32,
+    sides: usize,
+    timings: Timings,
+}
+
+/// What a benchmark ran on and how long each combination took, in milliseconds per frame.
+struct BenchReport<'a> {
+    adapter: &'a wgpu::AdapterInfo,
+    size: PhysicalSize<u32>,
+    frames: u32,
+    /// Whether passes were timed on the GPU, without which only frame times are known.
+    timestamps: bool,
+    results: Vec<BenchResult>,
+}
+
+impl BenchReport<'_> {
+    /// The passes drawn without a window.
+    const PASSES: [Pass; 2] = [Pass::Compute, Pass::Render];
+
+    /// One row per combination, with pass times left blank if they were not timed.
+    fn csv(&self) -> String {
+        let mut csv = String::from("strands,segments,sides,frame_ms,cpu_ms");
+        for pass in Self::PASSES {
+            let _ = write!(csv, ",{}_ms", pass.name());
+        }
+        csv.push('\n');
+        for result in &self.results {
+            let timings = &result.timings;
+            let _ = write!(
+                csv,
+                "{},{},{},{:.3},{:.3}",
+                result.strands, result.segments, result.sides, timings.frame, timings.cpu
+            );
+            for pass in Self::PASSES {
+                match timings.passes[pass as usize] {
+                    Some(time) => write!(csv, ",{time:.3}"),
+                    None => write!(csv, ","),
+                }
+                .unwrap_or_default();
+            }
+            csv.push('\n');
+        }
+        csv
+    }
+
+    /// The adapter and settings, with a result per combination. Pass times are null if they
+    /// were not timed.
+    fn json(&self) -> String {
+        let results: Vec<String> = self
+            .results
+            .iter()
+            .map(|result| {
+                let timings = &result.timings;
+                let passes: String = Self::PASSES
+                    .iter()
+                    .map(|&pass| {
+                        let time = timings.passes[pass as usize]
+                            .map_or("null".to_string(), |time| format!("{time:.3}"));
+                        format!(r#","{}_ms":{time}"#, pass.name())
+                    })
+                    .collect();
+                format!(
+                    r#"{{"strands":{},"segments":{},"sides":{},"frame_ms":{:.3},"cpu_ms":{:.3}{passes}}}"#,
+                    result.strands, result.segments, result.sides, timings.frame, timings.cpu
+                )
+            })
+            .collect();
+        format!(
+            "{{\"adapter\":{},\"backend\":\"{:?}\",\"resolution\":[{},{}],\"frames\":{},\"timestamps\":{},\"results\":[\n{}\n]}}\n",
+            json_string(&self.adapter.name),
+            self.adapter.backend,
+            self.size.width,
+            self.size.height,
+            self.frames,
+            self.timestamps,
+            results.join(",\n")
+        )
+    }
+}
+
+/// `text` as a quoted JSON string.
+fn json_string(text: &str) -> String {
+    let mut json = String::with_capacity(text.len() + 2);
+    json.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(json, "\\u{:04x}", c as u32);
+            }
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+/// Writes the strands at `seconds` to each of `outputs`, in the format of its extension. Curve
+/// exports cover the scene's animation instead, if it has one.
+fn export(headless: &mut Headless, outputs: &[PathBuf], seconds: f32) -> anyhow::Result<()> {
+    let strands = headless.strands(seconds)?;
+    let mut curve_frames = None;
+    for path in outputs {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("obj" | "ply" | "glb") => {
+                export::Mesh::tubes(&strands, headless.pipelines.sides()).save(path)?
+            }
+            Some("stl") => {
+                let solid = export::stl::Solid::from_strands(&strands, &headless.scene.print)?;
+                let report = solid.manifold_report();
+                if !report.is_manifold() {
+                    log::warn!("Print mesh is not manifold, {}", report);
+                }
+                solid.save_stl(path)?;
+            }
+            Some(extension @ ("svg" | "hpgl")) => {
+                let size = headless.size;
+                let plot = export::plot::Plot::project(
+                    &strands,
+                    headless.pipelines.camera(),
+                    glam::vec2(size.width as f32, size.height as f32),
+                    &headless.scene.plot,
+                );
+                if extension == "svg" {
+                    plot.save_svg(path)?;
+                } else {
+                    plot.save_hpgl(path)?;
+                }
+            }
+            Some(extension @ ("usda" | "json" | "csv")) => {
+                let animation = headless.scene.curves.animation.clone();
+                if curve_frames.is_none() {
+                    curve_frames = Some(match &animation {
+                        None => vec![CurveFrame {
+                            time: seconds,
+                            strands: strands.clone(),
+                        }],
+                        Some(animation) => {
+                            // Trails grow afresh over the animation, as they do in the demo.
+                            headless.reset_trails = true;
+                            animation
+                                .times()
+                                .map(|time| {
+                                    Ok(CurveFrame {
+                                        time,
+                                        strands: headless.strands(time)?,
+                                    })
+                                })
+                                .collect::<anyhow::Result<_>>()?
+                        }
+                    });
+                }
+                let frames = curve_frames.as_deref().unwrap_or_default();
+                match extension {
+                    "usda" => {
+                        let frames_per_second =
+                            animation.map_or(24.0, |animation| animation.frames_per_second);
+                        export::curves::save_usda(frames, frames_per_second, path)?
+                    }
+                    "json" => export::curves::save_json(frames, path)?,
+                    _ => export::curves::save_csv(frames, path)?,
+                }
+            }
+            _ => bail!("Unknown export format {}", path.display()),
+        }
+        log::info!("Exported {}", path.display());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn adapter() -> wgpu::AdapterInfo {
+        wgpu::AdapterInfo {
+            name: "llvmpipe".to_string(),
+            vendor: 0,
+            device: 0,
+            device_type: wgpu::DeviceType::Cpu,
+            driver: String::new(),
+            driver_info: String::new(),
+            backend: wgpu::Backend::Gl,
+        }
+    }
+
+    fn report(timestamps: bool) -> (wgpu::AdapterInfo, Vec<BenchResult>) {
+        let adapter = adapter();
+        let passes = if timestamps {
+            [Some(0.5), Some(1.25), None]
+        } else {
+            [None; 3]
+        };
+        let results = vec![BenchResult {
+            strands: 256,
+            segments: 32,
+            sides: 8,
+            timings: Timings {
+                frame: 4.0,
+                cpu: 0.75,
+                passes,
+            },
+        }];
+        (adapter, results)
+    }
+
+    #[test]
+    fn bench_report_csv() {
+        for (timestamps, row) in [
+            (true, "256,32,8,4.000,0.750,0.500,1.250\n"),
+            (false, "256,32,8,4.000,0.750,,\n"),
+        ] {
+            let (adapter, results) = report(timestamps);
+            let report = BenchReport {
+                adapter: &adapter,
+                size: PhysicalSize::new(640, 360),
+                frames: 20,
+                timestamps,
+                results,
+            };
+            assert_eq!(
+                report.csv(),
+                format!("strands,segments,sides,frame_ms,cpu_ms,compute_ms,render_ms\n{row}")
+            );
+        }
+    }
+
+    #[test]
+    fn bench_report_json() {
+        for (timestamps, passes) in [
+            (true, r#""compute_ms":0.500,"render_ms":1.250"#),
+            (false, r#""compute_ms":null,"render_ms":null"#),
+        ] {
+            let (adapter, results) = report(timestamps);
+            let report = BenchReport {
+                adapter: &adapter,
+                size: PhysicalSize::new(640, 360),
+                frames: 20,
+                timestamps,
+                results,
+            };
+            assert_eq!(
+                report.json(),
+                format!(
+                    "{{\"adapter\":\"llvmpipe\",\"backend\":\"Gl\",\"resolution\":[640,360],\
+                     \"frames\":20,\"timestamps\":{timestamps},\"results\":[\n\
+                     {{\"strands\":256,\"segments\":32,\"sides\":8,\"frame_ms\":4.000,\
+                     \"cpu_ms\":0.750,{passes}}}\n]}}\n"
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn adapter_names_are_escaped() {
+        assert_eq!(json_string("llvmpipe"), r#""llvmpipe""#);
+        assert_eq!(
+            json_string("GPU \"Max\" \\ 2\n\u{1}é"),
+            r#""GPU \"Max\" \\ 2\n\u0001é""#
+        );
+    }
+
+    #[test]
+    fn bench_times_frames() {
+        let Some((device, queue)) = crate::golden::fallback_device() else {
+            return;
+        };
+        let mut headless = Headless::with_device(
+            adapter(),
+            device,
+            queue,
+            1,
+            Scene::default(),
+            PhysicalSize::new(64, 64),
+        )
+        .unwrap();
+        let timings = bench(&mut headless, (64, 16, 4), (1, 2), 0.0).unwrap();
+        assert!(timings.frame > 0.0 && timings.frame.is_finite());
+        assert!(timings.cpu > 0.0 && timings.cpu.is_finite());
+        assert!(timings.passes.iter().flatten().all(|time| time.is_finite()));
+    }
+}
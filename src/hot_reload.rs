@@ -0,0 +1,124 @@
+use anyhow::{Context, anyhow};
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+
+/// WGSL compiled from the shader sources on disk.
+pub struct ShaderSources {
+    pub tube: String,
+    pub instances: String,
+}
+
+/// Recompiles the shaders in the source tree whenever one of them is saved.
+pub struct ShaderWatcher {
+    directory: PathBuf,
+    modified: Option<SystemTime>,
+    last_check: Instant,
+}
+
+impl ShaderWatcher {
+    /// How often the shader files are checked for changes.
+    const INTERVAL: Duration = Duration::from_millis(500);
+
+    /// Watches the shaders the binary was built from. The first change after this is the first
+    /// to be compiled, as the shaders built in match those on disk.
+    pub fn new() -> Self {
+        let directory = PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/src/shaders"));
+        let modified = last_modified(&directory).ok();
+        Self {
+            directory,
+            modified,
+            last_check: Instant::now(),
+        }
+    }
+
+    /// The newly compiled shaders if any of them has changed since the last call, or the reason
+    /// they failed to compile.
+    pub fn poll(&mut self) -> Option<anyhow::Result<ShaderSources>> {
+        if self.last_check.elapsed() < Self::INTERVAL {
+            return None;
+        }
+        self.last_check = Instant::now();
+        let modified = match last_modified(&self.directory) {
+            Ok(modified) => modified,
+            Err(e) => return Some(Err(e)),
+        };
+        if self.modified == Some(modified) {
+            return None;
+        }
+        self.modified = Some(modified);
+        Some(self.compile())
+    }
+
+    fn compile(&self) -> anyhow::Result<ShaderSources> {
+        let wesl = wesl::Wesl::new(&self.directory);
+        let compile = |root: &str| -> anyhow::Result<String> {
+            let root = root.parse().map_err(|e| anyhow!("{e}"))?;
+            let compiled = wesl.compile(&root).map_err(|e| anyhow!("{e}"))?;
+            Ok(compiled.to_string())
+        };
+        Ok(ShaderSources {
+            tube: compile("package::tube").context("Unable to compile tube.wesl")?,
+            instances: compile("package::instances").context("Unable to compile instances.wesl")?,
+        })
+    }
+}
+
+/// The latest modification time of the files in `directory`.
+fn last_modified(directory: &std::path::Path) -> anyhow::Result<SystemTime> {
+    let mut latest = SystemTime::UNIX_EPOCH;
+    for entry in std::fs::read_dir(directory)
+        .with_context(|| format!("Unable to watch {}", directory.display()))?
+    {
+        latest = latest.max(entry?.metadata()?.modified()?);
+    }
+    Ok(latest)
+}
+
+/// The most specific line of a shader error, short enough for a window title. Errors put their
+/// cause last, followed by any source snippet, which is left out along with terminal colours.
+pub fn error_summary(error: &anyhow::Error) -> String {
+    let mut message = format!("{error:#}");
+    while let Some(start) = message.find('\x1b') {
+        let end = message[start..]
+            .find('m')
+            .map_or(message.len(), |end| start + end + 1);
+        message.replace_range(start..end, "");
+    }
+    let is_snippet = |line: &str| {
+        line.starts_with(['│', '┌', '|', '^', '='])
+            || line.starts_with("-->")
+            || line.starts_with(|c: char| c.is_ascii_digit())
+    };
+    message
+        .lines()
+        .map(str::trim)
+        .rev()
+        .find(|line| !line.is_empty() && !is_snippet(line))
+        .unwrap_or_default()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_summary_keeps_the_cause() {
+        let error = anyhow!(
+            "\x1b[1;31merror\x1b[0m: expected expression, found \";\"\n   \
+             ┌─ wgsl:3:13\n   \
+             │\n \
+             3 │     let x = ;\n   \
+             │             ^ expected expression\n   \
+             = note: in tube.wesl"
+        )
+        .context("Unable to compile tube.wesl");
+        assert_eq!(
+            error_summary(&error),
+            "Unable to compile tube.wesl: error: expected expression, found \";\""
+        );
+
+        let error = anyhow!("first\n\x1b[33mthe cause\x1b[0m\n\n  --> tube.wesl:1:1\n");
+        assert_eq!(error_summary(&error), "the cause");
+    }
+}
@@ -0,0 +1,306 @@
+mod hair;
+
+use anyhow::{Context, bail, ensure};
+use glam::{Vec3, vec3};
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::pipelines::TubeInstance;
+
+/// One strand from another tool, with a radius and linear colour at every point.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Polyline {
+    pub points: Vec<Vec3>,
+    pub radii: Vec<f32>,
+    pub colours: Vec<Vec3>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Polylines(pub Vec<Polyline>);
+
+/// Radius given to points of a CSV file without a `width` or `radius` column.
+const DEFAULT_RADIUS: f32 = 0.01;
+
+impl Polylines {
+    /// Reads Cem Yuksel's `.hair` files by extension, and anything else as polyline CSV.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let bytes = std::fs::read(path)
+            .with_context(|| format!("Unable to read strands {}", path.display()))?;
+        let is_hair = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("hair"));
+        if is_hair {
+            hair::parse(&bytes)
+        } else {
+            Self::parse_csv(std::str::from_utf8(&bytes)?)
+        }
+        .with_context(|| format!("Unable to parse strands {}", path.display()))
+    }
+
+    /// Whether `path` is a CSV file whose header names a `strand` column, as opposed to a list
+    /// of seed positions.
+    pub fn is_polyline_csv(path: &Path) -> bool {
+        let is_csv = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("csv"));
+        is_csv
+            && std::fs::read_to_string(path).is_ok_and(|text| {
+                text.lines()
+                    .next()
+                    .is_some_and(|header| header.split(',').any(|name| name.trim() == "strand"))
+            })
+    }
+
+    /// Rows of points under a header naming at least `strand`, `x`, `y` and `z` columns, with
+    /// optional `radius` or `width` and `r`, `g`, `b` columns. Rows with the same strand, in
+    /// file order, make up a polyline. If there is a `time` column, only the rows at the first
+    /// time are read, so the curve export's CSV can be read back in.
+    pub fn parse_csv(text: &str) -> anyhow::Result<Self> {
+        let mut lines = text.lines().enumerate();
+        let (_, header) = lines.next().context("CSV file is empty")?;
+        let columns: HashMap<&str, usize> = header
+            .split(',')
+            .enumerate()
+            .map(|(i, name)| (name.trim(), i))
+            .collect();
+        let column = |name: &str| columns.get(name).copied();
+        let (Some(strand), Some(x), Some(y), Some(z)) =
+            (column("strand"), column("x"), column("y"), column("z"))
+        else {
+            bail!("CSV header must name strand, x, y and z columns");
+        };
+
+        let mut polylines: Vec<Polyline> = Vec::new();
+        let mut indices: HashMap<String, usize> = HashMap::new();
+        let mut first_time = None;
+        for (line_number, line) in lines {
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+            if fields.iter().all(|field| field.is_empty()) {
+                continue;
+            }
+            let number = |i: usize| -> anyhow::Result<f32> {
+                let field = fields.get(i).context("Missing value")?;
+                Ok(field.parse()?)
+            };
+            let row = || -> anyhow::Result<_> {
+                let time = column("time").map(number).transpose()?;
+                let position = vec3(number(x)?, number(y)?, number(z)?);
+                let radius = match (column("radius"), column("width")) {
+                    (Some(radius), _) => number(radius)?,
+                    (None, Some(width)) => 0.5 * number(width)?,
+                    (None, None) => DEFAULT_RADIUS,
+                };
+                let colour = match (column("r"), column("g"), column("b")) {
+                    (Some(r), Some(g), Some(b)) => vec3(number(r)?, number(g)?, number(b)?),
+                    _ => Vec3::ONE,
+                };
+                Ok((time, position, radius, colour))
+            };
+            let (time, position, radius, colour) =
+                row().with_context(|| format!("Bad point on line {}", line_number + 1))?;
+            if time.is_some() && *first_time.get_or_insert(time) != time {
+                continue;
+            }
+
+            let name = fields.get(strand).copied().unwrap_or_default();
+            let index = *indices.entry(name.to_string()).or_insert_with(|| {
+                polylines.push(Polyline {
+                    points: Vec::new(),
+                    radii: Vec::new(),
+                    colours: Vec::new(),
+                });
+                polylines.len() - 1
+            });
+            let polyline = &mut polylines[index];
+            polyline.points.push(position);
+            polyline.radii.push(radius);
+            polyline.colours.push(colour);
+        }
+        ensure!(!polylines.is_empty(), "CSV file has no points");
+        Ok(Self(polylines))
+    }
+
+    /// Scales and moves the strands, radii included, so that their bounds fit centred in the
+    /// cube of side `size` around `centre`.
+    pub fn fit(&mut self, centre: Vec3, size: f32) {
+        let points = self.0.iter().flat_map(|polyline| &polyline.points).copied();
+        let Some(fit) = Fit::new(points, centre, size) else {
+            return;
+        };
+        for polyline in &mut self.0 {
+            for point in &mut polyline.points {
+                *point = fit.apply(*point);
+            }
+            for radius in &mut polyline.radii {
+                *radius *= fit.scale;
+            }
+        }
+    }
+
+    /// One instance per segment, strand after strand, with rotation minimising frames. Each
+    /// segment takes the radius and colour of its first point.
+    pub fn to_instances(&self) -> Vec<TubeInstance> {
+        let mut instances = Vec::new();
+        for polyline in &self.0 {
+            // Repeated points would leave a segment without a direction.
+            let mut kept: Vec<usize> = Vec::with_capacity(polyline.points.len());
+            for (i, point) in polyline.points.iter().enumerate() {
+                if kept
+                    .last()
+                    .is_none_or(|&last| polyline.points[last] != *point)
+                {
+                    kept.push(i);
+                }
+            }
+            if kept.len() < 2 {
+                continue;
+            }
+            let points: Vec<Vec3> = kept.iter().map(|&i| polyline.points[i]).collect();
+            let frames = rotation_minimising_frames(&points);
+            for (pair, &i) in frames.windows(2).zip(&kept) {
+                let (start, end) = (pair[0], pair[1]);
+                instances.push(TubeInstance {
+                    start_position: start.position,
+                    start_normal: start.normal,
+                    start_bitangent: start.bitangent,
+                    end_position: end.position,
+                    end_normal: end.normal,
+                    end_bitangent: end.bitangent,
+                    colour: polyline.colours[i],
+                    radius: polyline.radii[i],
+                    ..Default::default()
+                });
+            }
+        }
+        instances
+    }
+}
+
+/// The uniform scale and move that fits the bounds of some points centred in a cube.
+#[derive(Debug, Clone, Copy)]
+pub struct Fit {
+    pub scale: f32,
+    middle: Vec3,
+    centre: Vec3,
+}
+
+impl Fit {
+    /// Fits `points` in the cube of side `size` around `centre`, or `None` if they have no
+    /// finite extent.
+    pub fn new(points: impl IntoIterator<Item = Vec3>, centre: Vec3, size: f32) -> Option<Self> {
+        let (min, max) = points
+            .into_iter()
+            .fold((Vec3::INFINITY, Vec3::NEG_INFINITY), |(min, max), point| {
+                (min.min(point), max.max(point))
+            });
+        let extent = (max - min).max_element();
+        (extent.is_finite() && extent > 0.0).then(|| Self {
+            scale: size / extent,
+            middle: 0.5 * (min + max),
+            centre,
+        })
+    }
+
+    pub fn apply(&self, point: Vec3) -> Vec3 {
+        self.centre + (point - self.middle) * self.scale
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Frame {
+    position: Vec3,
+    normal: Vec3,
+    bitangent: Vec3,
+}
+
+/// Carries a normal along the polyline by the double reflection method of Wang et al., so the
+/// tube twists as little as possible. Tangents are central differences, and the bitangent is
+/// `normal × tangent`, as in the shaders.
+fn rotation_minimising_frames(points: &[Vec3]) -> Vec<Frame> {
+    let last = points.len() - 1;
+    let tangents: Vec<Vec3> = (0..=last)
+        .map(|i| (points[(i + 1).min(last)] - points[i.saturating_sub(1)]).normalize_or_zero())
+        .collect();
+
+    let first_normal = {
+        let normal = tangents[0].cross(Vec3::Y);
+        if normal.length_squared() > 1e-6 {
+            normal.normalize()
+        } else {
+            tangents[0].any_orthonormal_vector()
+        }
+    };
+    let mut normals = vec![first_normal];
+    for i in 0..last {
+        let reflect = |vector: Vec3, axis: Vec3| {
+            let length_squared = axis.length_squared();
+            if length_squared < 1e-12 {
+                vector
+            } else {
+                vector - (2.0 / length_squared) * axis.dot(vector) * axis
+            }
+        };
+        let step = points[i + 1] - points[i];
+        let normal = reflect(normals[i], step);
+        let tangent = reflect(tangents[i], step);
+        normals.push(reflect(normal, tangents[i + 1] - tangent).normalize_or_zero());
+    }
+
+    points
+        .iter()
+        .zip(tangents)
+        .zip(normals)
+        .map(|((position, tangent), normal)| Frame {
+            position: *position,
+            normal,
+            bitangent: normal.cross(tangent),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn polyline_csv_parses() {
+        let text = "strand,x,y,z,width,r,g,b\n\
+                    a,0,0,0,0.2,1,0,0\n\
+                    b,5,5,5,0.2,0,1,0\n\
+                    \n\
+                    a,1,0,0,0.4,1,0,0\n";
+        let polylines = Polylines::parse_csv(text).unwrap();
+        assert_eq!(polylines.0.len(), 2);
+        assert_eq!(polylines.0[0].points, [Vec3::ZERO, Vec3::X]);
+        assert_eq!(polylines.0[0].radii, [0.1, 0.2]);
+        assert_eq!(polylines.0[1].colours, [Vec3::Y]);
+
+        // Only the first time of an exported animation is read.
+        let text = "time,strand,x,y,z\n0,0,0,0,0\n0,0,1,0,0\n1,0,2,0,0\n";
+        let polylines = Polylines::parse_csv(text).unwrap();
+        assert_eq!(polylines.0[0].points, [Vec3::ZERO, Vec3::X]);
+        assert_eq!(polylines.0[0].radii, [DEFAULT_RADIUS; 2]);
+    }
+
+    #[test]
+    fn polylines_fit_a_cube() {
+        let mut polylines =
+            Polylines::parse_csv("strand,x,y,z,radius\n0,0,0,0,1\n0,4,2,0,1\n").unwrap();
+        polylines.fit(Vec3::ONE, 2.0);
+        assert_eq!(
+            polylines.0[0].points,
+            [vec3(0.0, 0.5, 1.0), vec3(2.0, 1.5, 1.0)]
+        );
+        assert_eq!(polylines.0[0].radii, [0.5, 0.5]);
+        assert!(Fit::new([Vec3::ONE, Vec3::ONE], Vec3::ZERO, 1.0).is_none());
+    }
+
+    #[test]
+    fn broken_polyline_csv_fails() {
+        assert!(Polylines::parse_csv("").is_err());
+        assert!(Polylines::parse_csv("strand,x,y\n0,0,0\n").is_err());
+        assert!(Polylines::parse_csv("strand,x,y,z\n").is_err());
+        assert!(Polylines::parse_csv("strand,x,y,z\n0,0,zero,0\n").is_err());
+        assert!(Polylines::parse_csv("strand,x,y,z\n0,0,0\n").is_err());
+    }
+}
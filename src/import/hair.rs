@@ -0,0 +1,163 @@
+use anyhow::{bail, ensure};
+use glam::Vec3;
+
+use super::{Polyline, Polylines};
+
+const MAGIC: &[u8; 4] = b"HAIR";
+const HEADER_LEN: usize = 128;
+const HAS_SEGMENTS: u32 = 1 << 0;
+const HAS_POINTS: u32 = 1 << 1;
+const HAS_THICKNESS: u32 = 1 << 2;
+const HAS_TRANSPARENCY: u32 = 1 << 3;
+const HAS_COLOUR: u32 = 1 << 4;
+
+/// Cem Yuksel's binary hair format: a 128 byte little-endian header of `"HAIR"`, strand count,
+/// point count, a bit field of the arrays present, and the defaults for the missing ones,
+/// followed by the arrays of segment counts (`u16` per strand), points, thicknesses,
+/// transparencies and colours (`f32`s per point). Transparency is ignored.
+pub fn parse(bytes: &[u8]) -> anyhow::Result<Polylines> {
+    ensure!(
+        bytes.len() >= HEADER_LEN && &bytes[0..4] == MAGIC,
+        "Not a hair file"
+    );
+    let u32_at = |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+    let f32_at = |offset: usize| f32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+    let strand_count = u32_at(4) as usize;
+    let point_count = u32_at(8) as usize;
+    let flags = u32_at(12);
+    let default_segments = u32_at(16) as usize;
+    let default_thickness = f32_at(20);
+    let default_colour = Vec3::new(f32_at(28), f32_at(32), f32_at(36));
+    if flags & HAS_POINTS == 0 {
+        bail!("Hair file has no points");
+    }
+    // Check the counts against the file before allocating for them.
+    ensure!(
+        point_count <= (bytes.len() - HEADER_LEN) / 12,
+        "Hair file is truncated"
+    );
+    ensure!(
+        strand_count <= point_count,
+        "Hair file has more strands than points"
+    );
+
+    let mut offset = HEADER_LEN;
+    let mut take = |length: usize| -> anyhow::Result<&[u8]> {
+        ensure!(bytes.len() >= offset + length, "Hair file is truncated");
+        offset += length;
+        Ok(&bytes[offset - length..offset])
+    };
+    let segments: Vec<usize> = if flags & HAS_SEGMENTS != 0 {
+        take(2 * strand_count)?
+            .chunks_exact(2)
+            .map(|count| u16::from_le_bytes([count[0], count[1]]) as usize)
+            .collect()
+    } else {
+        vec![default_segments; strand_count]
+    };
+    let floats = |bytes: &[u8]| -> Vec<f32> {
+        bytes
+            .chunks_exact(4)
+            .map(|value| f32::from_le_bytes(value.try_into().unwrap()))
+            .collect()
+    };
+    let points = floats(take(12 * point_count)?);
+    let thicknesses = if flags & HAS_THICKNESS != 0 {
+        floats(take(4 * point_count)?)
+    } else {
+        vec![default_thickness; point_count]
+    };
+    if flags & HAS_TRANSPARENCY != 0 {
+        take(4 * point_count)?;
+    }
+    let colours = if flags & HAS_COLOUR != 0 {
+        floats(take(12 * point_count)?)
+    } else {
+        default_colour.to_array().repeat(point_count)
+    };
+
+    let mut polylines = Vec::with_capacity(strand_count);
+    let mut first = 0;
+    for segments in segments {
+        let end = first + segments + 1;
+        ensure!(
+            end <= point_count,
+            "Hair strands use more points than the file has"
+        );
+        polylines.push(Polyline {
+            points: (first..end)
+                .map(|i| Vec3::from_slice(&points[3 * i..]))
+                .collect(),
+            radii: thicknesses[first..end].iter().map(|t| 0.5 * t).collect(),
+            colours: (first..end)
+                .map(|i| Vec3::from_slice(&colours[3 * i..]))
+                .collect(),
+        });
+        first = end;
+    }
+    Ok(Polylines(polylines))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A hair file of `strands` whose points all lie on the x axis.
+    fn hair(flags: u32, default_segments: u32, strands: &[u16]) -> Vec<u8> {
+        let point_count: u32 = strands
+            .iter()
+            .map(|&segments| u32::from(segments) + 1)
+            .sum();
+        let mut bytes = MAGIC.to_vec();
+        for word in [strands.len() as u32, point_count, flags, default_segments] {
+            bytes.extend(word.to_le_bytes());
+        }
+        bytes.extend(0.5f32.to_le_bytes());
+        bytes.resize(HEADER_LEN, 0);
+        if flags & HAS_SEGMENTS != 0 {
+            for segments in strands {
+                bytes.extend(segments.to_le_bytes());
+            }
+        }
+        for i in 0..point_count {
+            for value in [i as f32, 0.0, 0.0] {
+                bytes.extend(value.to_le_bytes());
+            }
+        }
+        bytes
+    }
+
+    #[test]
+    fn hair_parses() {
+        let polylines = parse(&hair(HAS_SEGMENTS | HAS_POINTS, 0, &[1, 2])).unwrap();
+        assert_eq!(polylines.0.len(), 2);
+        assert_eq!(polylines.0[1].points.len(), 3);
+        assert_eq!(polylines.0[1].points[0], Vec3::new(2.0, 0.0, 0.0));
+        assert_eq!(polylines.0[1].radii, [0.25; 3]);
+
+        let polylines = parse(&hair(HAS_POINTS, 2, &[2, 2])).unwrap();
+        assert_eq!(polylines.0.len(), 2);
+        assert_eq!(polylines.0[1].points.len(), 3);
+    }
+
+    #[test]
+    fn broken_hair_fails() {
+        let valid = hair(HAS_SEGMENTS | HAS_POINTS, 0, &[1, 2]);
+        assert!(parse(&valid[..valid.len() - 4]).is_err());
+        assert!(parse(&valid[..HEADER_LEN - 1]).is_err());
+        assert!(parse(&hair(HAS_SEGMENTS, 0, &[1])).is_err());
+
+        // Huge counts in the header must fail rather than be allocated.
+        let mut huge_strands = hair(HAS_POINTS, 1, &[1]);
+        huge_strands[4..8].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(parse(&huge_strands).is_err());
+        let mut huge_points = hair(HAS_POINTS, 1, &[1]);
+        huge_points[8..12].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(parse(&huge_points).is_err());
+
+        // Strands using more points than there are.
+        let mut overrun = hair(HAS_SEGMENTS | HAS_POINTS, 0, &[1, 2]);
+        overrun[HEADER_LEN..HEADER_LEN + 2].copy_from_slice(&9u16.to_le_bytes());
+        assert!(parse(&overrun).is_err());
+    }
+}
@@ -0,0 +1,280 @@
+use egui::{ComboBox, Ui};
+use std::sync::Arc;
+use winit::dpi::PhysicalSize;
+use winit::window::Window;
+
+use crate::adapter::{self, GraphicsOptions};
+use crate::config::Config;
+use crate::quality::Quality;
+
+/// What to do once the launcher closes.
+pub enum Choice {
+    Start,
+    Quit,
+}
+
+/// The setup dialog drawn with egui in the demo's window before the demo starts, which hands
+/// the window over to the demo once a choice is made.
+pub struct Launcher {
+    window: Arc<Window>,
+    surface: wgpu::Surface<'static>,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    surface_config: wgpu::SurfaceConfiguration,
+    context: egui::Context,
+    input: egui_winit::State,
+    renderer: egui_wgpu::Renderer,
+    /// Sizes of the screen modes on offer, largest first.
+    resolutions: Vec<PhysicalSize<u32>>,
+    config: Config,
+}
+
+impl Launcher {
+    pub const SIZE: winit::dpi::LogicalSize<f64> = winit::dpi::LogicalSize::new(420.0, 320.0);
+    const MSAA: [u32; 4] = [1, 2, 4, 8];
+
+    /// Draws with the adapter in `options`, so a broken one shows up before the demo starts.
+    pub async fn new(
+        window: Arc<Window>,
+        options: &GraphicsOptions,
+        config: Config,
+        resolutions: Vec<PhysicalSize<u32>>,
+    ) -> anyhow::Result<Self> {
+        let instance = adapter::instance(options).await;
+        let surface = instance
+            .create_surface(window.clone())
+            .map_err(crate::error::GraphicsError::Surface)?;
+        let adapter = adapter::request_adapter(&instance, options, Some(&surface)).await?;
+        let (device, queue) = adapter::request_device(&adapter).await?;
+
+        let capabilities = surface.get_capabilities(&adapter);
+        let format = capabilities
+            .formats
+            .iter()
+            .find(|f| f.is_srgb())
+            .copied()
+            .unwrap_or(capabilities.formats[0]);
+        let size = window.inner_size();
+        let surface_config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format,
+            width: size.width.max(1),
+            height: size.height.max(1),
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: capabilities.alpha_modes[0],
+            view_formats: vec![format.add_srgb_suffix()],
+            desired_maximum_frame_latency: 2,
+        };
+        surface.configure(&device, &surface_config);
+
+        let context = egui::Context::default();
+        let input = egui_winit::State::new(
+            context.clone(),
+            egui::ViewportId::ROOT,
+            &window,
+            Some(window.scale_factor() as f32),
+            None,
+            Some(device.limits().max_texture_dimension_2d as usize),
+        );
+        let renderer =
+            egui_wgpu::Renderer::new(&device, format.add_srgb_suffix(), Default::default());
+        window.request_redraw();
+
+        Ok(Self {
+            window,
+            surface,
+            device,
+            queue,
+            surface_config,
+            context,
+            input,
+            renderer,
+            resolutions,
+            config,
+        })
+    }
+
+    pub fn handle_event(&mut self, event: &winit::event::WindowEvent) {
+        let response = self.input.on_window_event(&self.window, event);
+        if response.repaint {
+            self.window.request_redraw();
+        }
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if width > 0 && height > 0 {
+            self.surface_config.width = width;
+            self.surface_config.height = height;
+            self.surface.configure(&self.device, &self.surface_config);
+        }
+    }
+
+    /// Draws the dialog, returning the choice once one has been made.
+    pub fn draw(&mut self) -> anyhow::Result<Option<Choice>> {
+        let raw_input = self.input.take_egui_input(&self.window);
+        let context = self.context.clone();
+        let mut choice = None;
+        let output = context.run(raw_input, |context| {
+            egui::CentralPanel::default().show(context, |ui| {
+                choice = self.ui(ui);
+            });
+            if context.input(|input| input.key_pressed(egui::Key::Enter)) {
+                choice = Some(Choice::Start);
+            }
+            if context.input(|input| input.key_pressed(egui::Key::Escape)) {
+                choice = Some(Choice::Quit);
+            }
+        });
+        self.input
+            .handle_platform_output(&self.window, output.platform_output);
+        if choice.is_some() {
+            return Ok(choice);
+        }
+
+        let frame = match self.surface.get_current_texture() {
+            Ok(frame) => frame,
+            Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                self.surface.configure(&self.device, &self.surface_config);
+                return Ok(None);
+            }
+            Err(e) => anyhow::bail!("Unable to draw the launcher, {e}"),
+        };
+        let view = frame.texture.create_view(&wgpu::TextureViewDescriptor {
+            format: Some(self.surface_config.format.add_srgb_suffix()),
+            ..Default::default()
+        });
+        let screen = egui_wgpu::ScreenDescriptor {
+            size_in_pixels: [self.surface_config.width, self.surface_config.height],
+            pixels_per_point: output.pixels_per_point,
+        };
+        let primitives = context.tessellate(output.shapes, output.pixels_per_point);
+        for (id, delta) in &output.textures_delta.set {
+            self.renderer
+                .update_texture(&self.device, &self.queue, *id, delta);
+        }
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Launcher encoder"),
+            });
+        let commands = self.renderer.update_buffers(
+            &self.device,
+            &self.queue,
+            &mut encoder,
+            &primitives,
+            &screen,
+        );
+        {
+            let mut render_pass = encoder
+                .begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("Launcher Pass"),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: &view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                            store: wgpu::StoreOp::Store,
+                        },
+                        depth_slice: None,
+                    })],
+                    depth_stencil_attachment: None,
+                    occlusion_query_set: None,
+                    timestamp_writes: None,
+                })
+                .forget_lifetime();
+            self.renderer.render(&mut render_pass, &primitives, &screen);
+        }
+        self.queue
+            .submit(commands.into_iter().chain([encoder.finish()]));
+        frame.present();
+        for id in &output.textures_delta.free {
+            self.renderer.free_texture(id);
+        }
+        Ok(None)
+    }
+
+    /// Gives the window back, with nothing left drawing to it, and the choices made.
+    pub fn finish(self) -> (Arc<Window>, Config) {
+        (self.window, self.config)
+    }
+
+    fn ui(&mut self, ui: &mut Ui) -> Option<Choice> {
+        let config = &mut self.config;
+        ui.heading("Noodles");
+        ui.add_space(8.0);
+        egui::Grid::new("Setup")
+            .num_columns(2)
+            .spacing([16.0, 8.0])
+            .show(ui, |ui| {
+                ui.label("Resolution");
+                let name = |resolution: Option<[u32; 2]>| match resolution {
+                    Some([width, height]) => format!("{width}×{height}"),
+                    None => "Desktop".to_string(),
+                };
+                ComboBox::from_id_salt("Resolution")
+                    .selected_text(name(config.resolution))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut config.resolution, None, name(None));
+                        for size in &self.resolutions {
+                            let resolution = Some([size.width, size.height]);
+                            ui.selectable_value(
+                                &mut config.resolution,
+                                resolution,
+                                name(resolution),
+                            );
+                        }
+                    });
+                ui.end_row();
+
+                ui.label("Full screen");
+                ui.checkbox(&mut config.fullscreen, "");
+                ui.end_row();
+
+                ui.label("Vsync");
+                ui.checkbox(&mut config.vsync, "");
+                ui.end_row();
+
+                ui.label("Antialiasing");
+                let name = |msaa: u32| match msaa {
+                    1 => "Off".to_string(),
+                    samples => format!("{samples}× MSAA"),
+                };
+                ComboBox::from_id_salt("Antialiasing")
+                    .selected_text(name(config.msaa))
+                    .show_ui(ui, |ui| {
+                        for msaa in Self::MSAA {
+                            ui.selectable_value(&mut config.msaa, msaa, name(msaa));
+                        }
+                    });
+                ui.end_row();
+
+                ui.label("Quality");
+                ui.horizontal(|ui| {
+                    for quality in Quality::ALL {
+                        ui.radio_value(&mut config.quality, quality, quality.name());
+                    }
+                });
+                ui.end_row();
+            });
+        let quality = config.quality;
+        ui.label(format!(
+            "{} strands of {} segments, as {}-sided tubes, traced with {:?}",
+            quality.strands(),
+            quality.segments(),
+            quality.sides(),
+            quality.integrator()
+        ));
+
+        ui.add_space(12.0);
+        ui.horizontal(|ui| {
+            if ui.button("Start").clicked() {
+                Some(Choice::Start)
+            } else if ui.button("Quit").clicked() {
+                Some(Choice::Quit)
+            } else {
+                None
+            }
+        })
+        .inner
+    }
+}
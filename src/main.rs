@@ -1,67 +1,139 @@
+mod adapter;
+#[cfg(not(target_arch = "wasm32"))]
+mod cli;
+mod clock;
+#[cfg(not(target_arch = "wasm32"))]
+mod config;
+mod error;
+#[cfg(not(target_arch = "wasm32"))]
+mod export;
+mod field;
+mod framebuffer;
+#[cfg(test)]
+mod golden;
+#[cfg(not(target_arch = "wasm32"))]
+mod headless;
+#[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+mod hot_reload;
+mod import;
+#[cfg(all(feature = "launcher", not(target_arch = "wasm32")))]
+mod launcher;
+mod obstacles;
+#[cfg(feature = "overlay")]
+mod overlay;
 mod pipelines;
-
-use crate::pipelines::Pipelines;
-
-use glam::vec3;
+mod profiler;
+mod quality;
+#[cfg(any(target_arch = "wasm32", test))]
+mod query;
+mod reference;
+mod scene;
+mod seeding;
+mod sources;
+mod view;
+mod volume;
 #[cfg(target_arch = "wasm32")]
-use wasm_bindgen::prelude::*;
+mod web;
+
+use crate::adapter::GraphicsOptions;
+use crate::clock::Clock;
+use crate::error::GraphicsError;
+use crate::field::Field;
+use crate::framebuffer::Framebuffer;
+use crate::import::Polylines;
+use crate::obstacles::Obstacles;
+use crate::pipelines::{GrowthMode, Pipelines, VectorField};
+use crate::profiler::{Pass, Profiler};
+use crate::quality::Quality;
+use crate::scene::Scene;
+use crate::seeding::SeedStrategy;
+use crate::sources::{Fixed, SourceFrame, StrandSource};
+use crate::volume::Volume;
 
 use winit::{
     application::ApplicationHandler,
-    dpi::LogicalSize,
     event::{ElementState, KeyEvent, WindowEvent},
-    event_loop::{ActiveEventLoop, ControlFlow, EventLoop},
+    event_loop::{ActiveEventLoop, EventLoop},
     keyboard::{Key, NamedKey},
     window::{Fullscreen, Window, WindowId},
 };
 
-use std::sync::Arc;
-use web_time::Instant;
+use std::sync::{Arc, Mutex};
 
 struct State {
     window: Arc<Window>,
     surface: wgpu::Surface<'static>,
-    depth_buffer: wgpu::Texture,
+    framebuffer: Framebuffer,
+    /// What the state was made with, to make it again if the device is lost.
+    options: GraphicsOptions,
+    /// Samples per pixel, which is 1 if the adapter cannot multisample as many as asked for.
+    sample_count: u32,
     device: wgpu::Device,
     queue: wgpu::Queue,
     surface_config: wgpu::SurfaceConfiguration,
     is_surface_configured: bool,
     pipelines: Pipelines,
-    start_time: Instant,
+    /// Whatever makes the segments that the pipelines draw.
+    source: Box<dyn StrandSource>,
+    /// Index of the last built in source cycled to.
+    source_preset: usize,
+    scene: Scene,
+    clock: Clock,
+    profiler: Profiler,
+    /// Demo time at which strands last started growing.
+    growth_start: f32,
+    reset_trails: bool,
+    #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+    shader_watcher: hot_reload::ShaderWatcher,
+    /// The window title from before any shader error was shown in it.
+    #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+    title: String,
+    #[cfg(feature = "overlay")]
+    overlay: overlay::Overlay,
+    #[cfg(target_arch = "wasm32")]
+    adapter_info: wgpu::AdapterInfo,
+    /// The quality preset asked for by the link the page was opened with, for links made
+    /// from it to carry on.
+    #[cfg(target_arch = "wasm32")]
+    quality: Option<Quality>,
+    /// Why the device was lost, once it has been.
+    device_lost: Arc<Mutex<Option<String>>>,
 }
 
 impl State {
-    pub async fn new(window: Arc<Window>) -> anyhow::Result<Self> {
+    /// Imported strands are scaled to fit a cube this wide around the point the camera circles.
+    const IMPORT_SIZE: f32 = 3.2;
+
+    pub async fn new(window: Arc<Window>, options: GraphicsOptions) -> anyhow::Result<Self> {
         let size = window.inner_size().max(winit::dpi::PhysicalSize {
             width: 1,
             height: 1,
         });
 
-        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::PRIMARY,
-            ..Default::default()
-        });
+        let instance = adapter::instance(&options).await;
+
+        let surface = instance
+            .create_surface(window.clone())
+            .map_err(GraphicsError::Surface)?;
 
-        let surface = instance.create_surface(window.clone())?;
-
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::HighPerformance,
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
-            })
-            .await?;
-
-        let (device, queue) = adapter
-            .request_device(&wgpu::DeviceDescriptor {
-                label: None,
-                required_features: wgpu::Features::empty(),
-                experimental_features: wgpu::ExperimentalFeatures::disabled(),
-                required_limits: wgpu::Limits::default(),
-                memory_hints: Default::default(),
-                trace: wgpu::Trace::Off,
-            })
-            .await?;
+        let adapter = adapter::request_adapter(&instance, &options, Some(&surface)).await?;
+        let (device, queue) = adapter::request_device(&adapter).await?;
+
+        let device_lost = Arc::new(Mutex::new(None));
+        {
+            let device_lost = device_lost.clone();
+            device.set_device_lost_callback(move |reason, message| {
+                // Only a device destroyed on purpose is lost for good.
+                if reason != wgpu::DeviceLostReason::Destroyed {
+                    *device_lost.lock().unwrap() = Some(message);
+                }
+            });
+        }
+
+        // An uncaptured error would otherwise stop the demo on the web, where the host page is
+        // told about it instead.
+        #[cfg(target_arch = "wasm32")]
+        device.on_uncaptured_error(Arc::new(|e| log::error!("{}", e)));
 
         let surface_capabilities = surface.get_capabilities(&adapter);
 
@@ -74,31 +146,85 @@ impl State {
 
         dbg!(surface_format);
 
+        let present_mode = match options.present_mode {
+            wgpu::PresentMode::AutoVsync | wgpu::PresentMode::AutoNoVsync => options.present_mode,
+            mode if surface_capabilities.present_modes.contains(&mode) => mode,
+            mode => {
+                log::warn!("The window cannot present with {:?}, so Fifo is used", mode);
+                wgpu::PresentMode::Fifo
+            }
+        };
+        let sample_count =
+            adapter::sample_count(&adapter, surface_format.add_srgb_suffix(), options.msaa);
+
         let surface_config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
             format: surface_format,
             width: size.width,
             height: size.height,
-            present_mode: wgpu::PresentMode::Fifo,
+            present_mode,
             alpha_mode: surface_capabilities.alpha_modes[0],
             view_formats: vec![surface_format.add_srgb_suffix()],
             desired_maximum_frame_latency: 2,
         };
 
-        let depth_buffer = Self::create_depth_buffer(&device, size.width, size.height);
+        let framebuffer = Framebuffer::new(
+            &device,
+            surface_format.add_srgb_suffix(),
+            size.width,
+            size.height,
+            sample_count,
+        );
+
+        let mut pipelines = Pipelines::new(
+            &device,
+            &queue,
+            surface_format.add_srgb_suffix(),
+            sample_count,
+        );
+        let scene = Scene::default();
+        pipelines.update_seeds(
+            &queue,
+            &scene
+                .seeding
+                .generate(Pipelines::NUM_STRANDS, scene.random_seed)?,
+        )?;
+
+        let source = sources::curl_noise(&pipelines);
+        let profiler = Profiler::new(&device, &queue);
 
-        let pipelines = Pipelines::new(&device, surface_format.add_srgb_suffix());
+        #[cfg(feature = "overlay")]
+        let overlay = overlay::Overlay::new(&window, &device, surface_format.add_srgb_suffix());
 
         Ok(Self {
-            window,
             surface,
             device,
             queue,
             surface_config,
             is_surface_configured: false,
             pipelines,
-            depth_buffer,
-            start_time: Instant::now(),
+            source,
+            source_preset: 0,
+            scene,
+            framebuffer,
+            options,
+            sample_count,
+            clock: Clock::new(),
+            profiler,
+            growth_start: 0.0,
+            reset_trails: true,
+            #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+            shader_watcher: hot_reload::ShaderWatcher::new(),
+            #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+            title: window.title(),
+            #[cfg(feature = "overlay")]
+            overlay,
+            #[cfg(target_arch = "wasm32")]
+            adapter_info: adapter.get_info(),
+            #[cfg(target_arch = "wasm32")]
+            quality: None,
+            device_lost,
+            window,
         })
     }
 
@@ -107,26 +233,452 @@ impl State {
             self.surface_config.width = width;
             self.surface_config.height = height;
             self.surface.configure(&self.device, &self.surface_config);
-            self.depth_buffer = Self::create_depth_buffer(&self.device, width, height);
+            self.framebuffer = Framebuffer::new(
+                &self.device,
+                self.surface_config.format.add_srgb_suffix(),
+                width,
+                height,
+                self.sample_count,
+            );
             self.is_surface_configured = true;
         }
     }
 
-    fn create_depth_buffer(device: &wgpu::Device, width: u32, height: u32) -> wgpu::Texture {
-        device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("Depth buffer"),
-            size: wgpu::Extent3d {
-                width,
-                height,
-                depth_or_array_layers: 1,
-            },
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Depth32Float,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            view_formats: &[],
-        })
+    /// Fails once the device has been lost, after which nothing more can be drawn with it.
+    pub fn check_device(&self) -> Result<(), GraphicsError> {
+        match self.device_lost.lock().unwrap().take() {
+            Some(message) => Err(GraphicsError::DeviceLost(message)),
+            None => Ok(()),
+        }
+    }
+
+    /// Starts again on a new device after the old one was lost, carrying on from the same time
+    /// with the same scene and strands. Trails start again from their seeds.
+    pub async fn recover(self) -> anyhow::Result<Self> {
+        let Self {
+            window,
+            surface,
+            options,
+            pipelines,
+            scene,
+            clock,
+            growth_start,
+            source,
+            source_preset,
+            #[cfg(target_arch = "wasm32")]
+            quality,
+            ..
+        } = self;
+        // Some platforms refuse a second surface on a window that still has one.
+        drop(surface);
+
+        let sides = pipelines.sides();
+        drop(pipelines);
+
+        let mut state = Self::new(window, options).await?;
+        state.pipelines.set_sides(&state.device, sides);
+        state.clock = clock;
+        state.growth_start = growth_start;
+        #[cfg(target_arch = "wasm32")]
+        {
+            state.quality = quality;
+        }
+        state.restore(scene, source, source_preset);
+        let size = state.window.inner_size();
+        state.resize(size.width, size.height);
+        state.window.request_redraw();
+        Ok(state)
+    }
+
+    /// Loads `scene` and starts `source` afresh on this state's device. Whatever fails to load
+    /// is left as the new state had it.
+    fn restore(&mut self, scene: Scene, mut source: Box<dyn StrandSource>, source_preset: usize) {
+        if let Err(e) = Self::load_sdf_volume(
+            &mut self.pipelines,
+            &self.device,
+            &self.queue,
+            &scene.obstacles,
+        ) {
+            log::error!("Unable to load SDF volume, {:#}", e);
+        }
+        if let Err(e) =
+            Self::load_vector_field(&mut self.pipelines, &self.device, &self.queue, &scene.field)
+        {
+            log::error!("Unable to load vector field, {:#}", e);
+        }
+        let seeded = scene
+            .seeding
+            .generate(Pipelines::NUM_STRANDS, scene.random_seed)
+            .and_then(|seeds| self.pipelines.update_seeds(&self.queue, &seeds));
+        if let Err(e) = seeded {
+            log::error!("Unable to seed strands, {:#}", e);
+        }
+        self.scene = scene;
+        match source.start(&self.device, &self.queue, &mut self.pipelines) {
+            Ok(()) => {
+                self.source = source;
+                self.source_preset = source_preset;
+            }
+            Err(e) => log::error!("Unable to restart {}, {:#}", source.name(), e),
+        }
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.clock.paused = !self.clock.paused;
+        log::info!("Paused: {}", self.clock.paused);
+    }
+
+    pub fn cycle_integrator(&mut self) {
+        self.scene.simulation.integrator = self.scene.simulation.integrator.next();
+        log::info!("Integrator: {:?}", self.scene.simulation.integrator);
+    }
+
+    pub fn cycle_growth(&mut self) {
+        self.scene.simulation.growth = self.scene.simulation.growth.next();
+        log::info!("Growth: {:?}", self.scene.simulation.growth);
+        self.restart_growth();
+    }
+
+    fn restart_growth(&mut self) {
+        self.growth_start = self.clock.seconds();
+        self.reset_trails = true;
+    }
+
+    pub fn cycle_seeding(&mut self) {
+        let presets = SeedStrategy::presets();
+        let next = presets
+            .iter()
+            .position(|preset| *preset == self.scene.seeding)
+            .map_or(0, |i| (i + 1) % presets.len());
+        self.set_seeding(presets[next].clone());
+    }
+
+    /// Keeps the current seeds if the new strategy fails, e.g. because its file is missing.
+    pub fn set_seeding(&mut self, seeding: SeedStrategy) {
+        let seeded = seeding
+            .generate(Pipelines::NUM_STRANDS, self.scene.random_seed)
+            .and_then(|seeds| self.pipelines.update_seeds(&self.queue, &seeds));
+        match seeded {
+            Ok(()) => {
+                log::info!("Seeding: {:?}", seeding);
+                if let Err(e) = self.set_source(sources::curl_noise(&self.pipelines)) {
+                    log::error!("Unable to return to computed strands, {:#}", e);
+                }
+                self.scene.seeding = seeding;
+                self.restart_growth();
+            }
+            Err(e) => log::error!("Unable to seed strands, {:#}", e),
+        }
+    }
+
+    /// Keeps the current source if the new one fails to start.
+    pub fn set_source(&mut self, mut source: Box<dyn StrandSource>) -> anyhow::Result<()> {
+        source.start(&self.device, &self.queue, &mut self.pipelines)?;
+        log::info!("Strands: {}", source.name());
+        self.source = source;
+        self.source_preset = 0;
+        Ok(())
+    }
+
+    /// Switches between the computed strands and the built in CPU generated ones.
+    pub fn cycle_source(&mut self) {
+        let presets =
+            sources::presets(self.scene.camera.centre, Self::IMPORT_SIZE, &self.pipelines);
+        let next = (self.source_preset + 1) % presets.len();
+        let source = presets
+            .into_iter()
+            .nth(next)
+            .expect("preset index is in range");
+        match self.set_source(source) {
+            Ok(()) => self.source_preset = next,
+            Err(e) => log::error!("Unable to switch strands, {:#}", e),
+        }
+    }
+
+    /// Draws strands from a `.hair` or polyline CSV file in place of the simulated ones, until
+    /// the seeding is next changed.
+    pub fn import_strands(&mut self, path: &std::path::Path) -> anyhow::Result<()> {
+        let source = Fixed::load(path, self.scene.camera.centre, Self::IMPORT_SIZE)?;
+        self.set_source(Box::new(source))
+    }
+
+    /// Parts of the scene that fail to load, such as a missing seed file, are left as they were.
+    pub fn set_scene(&mut self, scene: Scene) {
+        if let Err(e) = Self::load_sdf_volume(
+            &mut self.pipelines,
+            &self.device,
+            &self.queue,
+            &scene.obstacles,
+        ) {
+            log::error!("Unable to load SDF volume, {:#}", e);
+        }
+        if let Err(e) =
+            Self::load_vector_field(&mut self.pipelines, &self.device, &self.queue, &scene.field)
+        {
+            log::error!("Unable to load vector field, {:#}", e);
+        }
+        let seeding = scene.seeding.clone();
+        self.scene = Scene {
+            seeding: self.scene.seeding.clone(),
+            ..scene
+        };
+        self.set_seeding(seeding);
+        self.restart_growth();
+    }
+
+    /// Strands, segments and integrator are set in the scene, so a scene dropped later brings
+    /// its own.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_quality(&mut self, quality: Quality) {
+        quality.apply(&mut self.scene.simulation);
+        self.pipelines.set_sides(&self.device, quality.sides());
+        self.restart_growth();
+    }
+
+    fn load_sdf_volume(
+        pipelines: &mut Pipelines,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        obstacles: &Obstacles,
+    ) -> anyhow::Result<()> {
+        let Some(source) = &obstacles.volume else {
+            return pipelines.set_sdf_volume(device, queue, None);
+        };
+        let volume = Volume::load(&source.path)?;
+        let min = source.min.unwrap_or(volume.min);
+        let max = source.max.unwrap_or(volume.max);
+        pipelines.set_sdf_volume(device, queue, Some((&volume, min, max)))
+    }
+
+    fn load_vector_field(
+        pipelines: &mut Pipelines,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        field: &Field,
+    ) -> anyhow::Result<()> {
+        let Field::Volume {
+            path,
+            min,
+            max,
+            scale,
+            precision,
+        } = field
+        else {
+            return pipelines.set_vector_field(device, queue, None);
+        };
+        let volume = Volume::load(path)?;
+        let field = VectorField {
+            volume: &volume,
+            min: min.unwrap_or(volume.min),
+            max: max.unwrap_or(volume.max),
+            scale: *scale,
+            precision: *precision,
+        };
+        pipelines.set_vector_field(device, queue, Some(field))
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn read_strands(&self) -> anyhow::Result<Vec<export::Strand>> {
+        export::read_strands(&self.device, &self.queue, &self.pipelines)
+    }
+
+    /// Writes the tubes of the last frame to the working directory as OBJ, PLY and glTF.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn export_meshes(&self) -> anyhow::Result<()> {
+        let strands = self.read_strands()?;
+        let mesh = export::Mesh::tubes(&strands, self.pipelines.sides());
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_secs();
+        for extension in ["obj", "ply", "glb"] {
+            let path = std::path::PathBuf::from(format!("noodles-{timestamp}.{extension}"));
+            mesh.save(&path)?;
+            log::info!("Exported {}", path.display());
+        }
+        Ok(())
+    }
+
+    /// Writes the tubes of the last frame to the working directory as a watertight STL for
+    /// printing, warning if the mesh turns out not to be manifold.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn export_print(&self) -> anyhow::Result<()> {
+        let strands = self.read_strands()?;
+        let solid = export::stl::Solid::from_strands(&strands, &self.scene.print)?;
+        let report = solid.manifold_report();
+        if report.is_manifold() {
+            log::info!("Print mesh is manifold, {}", report);
+        } else {
+            log::warn!("Print mesh is not manifold, {}", report);
+        }
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_secs();
+        let path = std::path::PathBuf::from(format!("noodles-{timestamp}.stl"));
+        solid.save_stl(&path)?;
+        log::info!("Exported {}", path.display());
+        Ok(())
+    }
+
+    /// Writes the visible parts of the strand centrelines in the last frame to the working
+    /// directory as an SVG drawing, and HPGL if the scene asks for it.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn export_plot(&self) -> anyhow::Result<()> {
+        let strands = self.read_strands()?;
+        let viewport = glam::vec2(
+            self.surface_config.width as f32,
+            self.surface_config.height as f32,
+        );
+        let plot = export::plot::Plot::project(
+            &strands,
+            self.pipelines.camera(),
+            viewport,
+            &self.scene.plot,
+        );
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_secs();
+        let path = std::path::PathBuf::from(format!("noodles-{timestamp}.svg"));
+        plot.save_svg(&path)?;
+        log::info!("Exported {}", path.display());
+        if self.scene.plot.hpgl {
+            let path = path.with_extension("hpgl");
+            plot.save_hpgl(&path)?;
+            log::info!("Exported {}", path.display());
+        }
+        Ok(())
+    }
+
+    /// Writes the strand centrelines to the working directory as USD curves and as JSON and
+    /// CSV polylines, either from the last frame or resimulated over the scene's time range.
+    /// Resimulated trails start from their seeds, and the live ones start again afterwards.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn export_curves(&mut self) -> anyhow::Result<()> {
+        use export::curves::{self, CurveFrame};
+
+        let animation = self.scene.curves.animation.clone();
+        let frames = match &animation {
+            None => vec![CurveFrame {
+                time: self.clock.seconds(),
+                strands: self.read_strands()?,
+            }],
+            Some(animation) => {
+                self.reset_trails = true;
+                let frames = animation
+                    .times()
+                    .map(|time| {
+                        let mut encoder =
+                            self.device
+                                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                                    label: Some("Curve export encoder"),
+                                });
+                        self.simulate(&mut encoder, time);
+                        self.queue.submit([encoder.finish()]);
+                        Ok(CurveFrame {
+                            time,
+                            strands: self.read_strands()?,
+                        })
+                    })
+                    .collect::<anyhow::Result<_>>();
+                self.reset_trails = true;
+                frames?
+            }
+        };
+        let frames_per_second = animation.map_or(24.0, |animation| animation.frames_per_second);
+
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_secs();
+        let path = std::path::PathBuf::from(format!("noodles-{timestamp}.usda"));
+        curves::save_usda(&frames, frames_per_second, &path)?;
+        curves::save_json(&frames, &path.with_extension("json"))?;
+        curves::save_csv(&frames, &path.with_extension("csv"))?;
+        log::info!("Exported {} frames to {}", frames.len(), path.display());
+        Ok(())
+    }
+
+    /// Moves the camera and the noise to where they are `seconds` after the start.
+    fn update_camera(
+        pipelines: &mut Pipelines,
+        queue: &wgpu::Queue,
+        scene: &Scene,
+        aspect_ratio: f32,
+        seconds: f32,
+    ) {
+        pipelines.update_uniforms(
+            queue,
+            scene.camera.view_projection(seconds, aspect_ratio),
+            &scene.lighting,
+            scene.simulation.drift * seconds,
+        );
+    }
+
+    /// Moves the camera, obstacles and growth to `seconds` after the start and has the strand
+    /// source regenerate the strands.
+    fn simulate(&mut self, encoder: &mut wgpu::CommandEncoder, seconds: f32) {
+        Self::update_camera(
+            &mut self.pipelines,
+            &self.queue,
+            &self.scene,
+            self.surface_config.width as f32 / self.surface_config.height as f32,
+            seconds,
+        );
+
+        self.pipelines
+            .update_obstacles(&self.queue, &self.scene.obstacles, seconds);
+
+        let growth_progress = match self.scene.simulation.growth {
+            GrowthMode::Grow { duration } => (seconds - self.growth_start) / duration,
+            _ => 1.0,
+        };
+        if self.reset_trails {
+            self.pipelines.reset_trails(encoder);
+            self.reset_trails = false;
+        }
+        self.pipelines.update_simulation(
+            &self.queue,
+            &self.scene.simulation,
+            growth_progress,
+            seconds,
+        );
+
+        let frame = SourceFrame {
+            device: &self.device,
+            queue: &self.queue,
+            encoder,
+            pipelines: &mut self.pipelines,
+            profiler: &mut self.profiler,
+            simulation: self.scene.simulation,
+            growth_progress,
+            time: seconds,
+        };
+        if let Err(e) = self.source.update(frame) {
+            log::error!("Unable to update {}, {:#}", self.source.name(), e);
+        }
+    }
+
+    /// Rebuilds the pipelines if a shader has been saved. Errors are shown in the window title
+    /// until the shaders compile again, and the old pipelines are kept meanwhile.
+    #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+    fn reload_shaders(&mut self) {
+        let Some(sources) = self.shader_watcher.poll() else {
+            return;
+        };
+        let result = sources.and_then(|sources| {
+            self.pipelines
+                .reload_shaders(&self.device, &sources.tube, &sources.instances)
+        });
+        match result {
+            Ok(()) => {
+                log::info!("Reloaded shaders");
+                self.window.set_title(&self.title);
+            }
+            Err(e) => {
+                log::error!("Unable to reload shaders, {:#}", e);
+                let summary = hot_reload::error_summary(&e);
+                self.window
+                    .set_title(&format!("{} - shader error: {}", self.title, summary));
+            }
+        }
     }
 
     pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
@@ -134,79 +686,66 @@ impl State {
             return Ok(());
         }
 
-        let elapsed_time = (Instant::now() - self.start_time).as_secs_f32() * 0.1;
+        #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+        self.reload_shaders();
+
+        let seconds = self.clock.tick();
 
         let output = self.surface.get_current_texture()?;
+        self.profiler.begin_frame(&self.device);
 
         let view = output.texture.create_view(&wgpu::TextureViewDescriptor {
             format: Some(self.surface_config.format.add_srgb_suffix()),
             ..Default::default()
         });
 
-        let depth_view = self.depth_buffer.create_view(&Default::default());
-
         let mut encoder = self
             .device
             .create_command_encoder(&wgpu::CommandEncoderDescriptor {
                 label: Some("Render Encoder"),
             });
 
-        let centre = vec3(0.8, 0.0, 1.6);
-
-        self.pipelines.update_uniforms(
-            &self.queue,
-            centre
-                + vec3(
-                    5.0 * elapsed_time.cos(),
-                    4.0 * (0.9 * elapsed_time).sin(),
-                    3.0 * (0.3 * elapsed_time + 1.2).sin(),
-                ),
-            centre,
-            self.surface_config.width as f32 / self.surface_config.height as f32,
-            0.5 * elapsed_time,
-        );
+        self.simulate(&mut encoder, seconds);
 
         {
-            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-                label: Some("Compute Pass"),
-                timestamp_writes: None,
-            });
-            self.pipelines.compute_instances(&mut compute_pass);
+            let mut render_pass = self.framebuffer.begin_render_pass(
+                &mut encoder,
+                &view,
+                self.scene.lighting.clear_colour(),
+                self.profiler.render_timestamps(Pass::Render),
+            );
+
+            self.pipelines.render(&mut render_pass);
         }
 
+        #[cfg(feature = "overlay")]
         {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.01,
-                            g: 0.01,
-                            b: 0.014,
-                            a: 1.0,
-                        }),
-                        store: wgpu::StoreOp::Store,
-                    },
-                    depth_slice: None,
-                })],
-                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: &depth_view,
-                    depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(0.0),
-                        store: wgpu::StoreOp::Store,
-                    }),
-                    stencil_ops: None,
-                }),
-                occlusion_query_set: None,
-                timestamp_writes: None,
-            });
-
-            self.pipelines.render(&mut render_pass);
+            let size = [self.surface_config.width, self.surface_config.height];
+            let response = self.overlay.draw(
+                &self.window,
+                &self.device,
+                &self.queue,
+                &mut encoder,
+                &view,
+                size,
+                &mut self.scene,
+                &mut self.profiler,
+            );
+            if response.restart_growth {
+                self.restart_growth();
+            }
+            if let Some(scene) = response.load {
+                self.set_scene(scene);
+            }
+            #[cfg(target_arch = "wasm32")]
+            if response.copy_link {
+                web::copy_link(self);
+            }
         }
 
+        self.profiler.resolve(&self.device, &mut encoder);
         self.queue.submit(std::iter::once(encoder.finish()));
+        self.profiler.end_frame();
         output.present();
 
         self.window.request_redraw();
@@ -215,84 +754,360 @@ impl State {
     }
 }
 
+/// Events sent to the event loop from outside it, which only happens on the web.
+enum UserEvent {
+    /// The graphics state, once it has been created asynchronously.
+    #[cfg(target_arch = "wasm32")]
+    Ready(Box<State>),
+    #[cfg(target_arch = "wasm32")]
+    Command(web::Command),
+}
+
 #[derive(Default)]
 struct Demo {
     #[cfg(target_arch = "wasm32")]
-    proxy: Option<winit::event_loop::EventLoopProxy<State>>,
+    proxy: Option<winit::event_loop::EventLoopProxy<UserEvent>>,
+    #[cfg(target_arch = "wasm32")]
+    canvas: Option<web_sys::HtmlCanvasElement>,
+    /// Commands that arrived before the state was ready.
+    #[cfg(target_arch = "wasm32")]
+    pending: Vec<web::Command>,
     state: Option<State>,
+    /// Why the demo stopped, if it failed, for `run` to return.
+    #[cfg(not(target_arch = "wasm32"))]
+    error: Option<anyhow::Error>,
+    graphics: GraphicsOptions,
+    /// The window and quality options, with those from the command line in place.
+    #[cfg(not(target_arch = "wasm32"))]
+    config: config::Config,
+    /// The present mode from the command line, which wins over the vsync option.
+    #[cfg(all(feature = "launcher", not(target_arch = "wasm32")))]
+    present_mode: Option<wgpu::PresentMode>,
+    /// Whether to show the launcher before starting.
+    #[cfg(not(target_arch = "wasm32"))]
+    show_launcher: bool,
+    /// The launcher, while it is open.
+    #[cfg(all(feature = "launcher", not(target_arch = "wasm32")))]
+    launcher: Option<launcher::Launcher>,
+    /// The scene to start with, until the state has been made.
+    #[cfg(not(target_arch = "wasm32"))]
+    scene: Option<Scene>,
+    /// Seconds of demo time to start from.
+    #[cfg(not(target_arch = "wasm32"))]
+    start: f32,
 }
 
 impl Demo {
-    fn new(#[cfg(target_arch = "wasm32")] event_loop: &EventLoop<State>) -> Self {
-        #[cfg(target_arch = "wasm32")]
-        let proxy = Some(event_loop.create_proxy());
+    #[cfg(not(target_arch = "wasm32"))]
+    fn new(cli: &cli::Cli, config: config::Config, scene: Option<Scene>) -> Self {
         Self {
-            state: None,
-            #[cfg(target_arch = "wasm32")]
-            proxy,
+            graphics: cli.graphics(&config),
+            config,
+            #[cfg(feature = "launcher")]
+            present_mode: cli.present_mode.map(Into::into),
+            show_launcher: !cli.skip_launcher,
+            scene,
+            start: cli.start,
+            ..Default::default()
+        }
+    }
+
+    /// Borderless full screen, or the screen mode closest to the resolution if one was given.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn fullscreen(&self, event_loop: &ActiveEventLoop) -> Fullscreen {
+        let Some(resolution) = self.config.resolution() else {
+            return Fullscreen::Borderless(None);
+        };
+        let mode = event_loop
+            .primary_monitor()
+            .or_else(|| event_loop.available_monitors().next())
+            .and_then(|monitor| {
+                monitor
+                    .video_modes()
+                    .filter(|mode| mode.size() == resolution)
+                    .max_by_key(|mode| (mode.bit_depth(), mode.refresh_rate_millihertz()))
+            });
+        match mode {
+            Some(mode) => Fullscreen::Exclusive(mode),
+            None => {
+                log::warn!(
+                    "The screen has no {}x{} mode, so the desktop's is kept",
+                    resolution.width,
+                    resolution.height
+                );
+                Fullscreen::Borderless(None)
+            }
+        }
+    }
+
+    /// Sizes the window and makes it fill the screen as the config says.
+    #[cfg(all(feature = "launcher", not(target_arch = "wasm32")))]
+    fn place_window(&self, event_loop: &ActiveEventLoop, window: &Window) {
+        if self.config.fullscreen {
+            window.set_fullscreen(Some(self.fullscreen(event_loop)));
+        } else {
+            let _ = match self.config.resolution() {
+                Some(resolution) => window.request_inner_size(resolution),
+                None => window.request_inner_size(winit::dpi::LogicalSize::new(1920.0, 1080.0)),
+            };
+        }
+    }
+
+    /// Makes the state for `window` and starts it with the scene, quality and time asked for.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn start(&mut self, event_loop: &ActiveEventLoop, window: Arc<Window>) {
+        //block on creating the graphics state on native
+        match pollster::block_on(State::new(window, self.graphics.clone())) {
+            Ok(mut state) => {
+                if let Some(scene) = self.scene.take() {
+                    state.set_scene(scene);
+                }
+                state.set_quality(self.config.quality);
+                state.clock = Clock::starting_at(self.start);
+                let size = state.window.inner_size();
+                state.resize(size.width, size.height);
+                self.state = Some(state);
+            }
+            Err(e) => self.fail(event_loop, e),
+        }
+    }
+
+    /// Opens the launcher in `window`, or starts the demo in it straight away if the launcher
+    /// fails.
+    #[cfg(all(feature = "launcher", not(target_arch = "wasm32")))]
+    fn open_launcher(&mut self, event_loop: &ActiveEventLoop, window: Arc<Window>) {
+        let mut resolutions: Vec<_> = event_loop
+            .primary_monitor()
+            .or_else(|| event_loop.available_monitors().next())
+            .into_iter()
+            .flat_map(|monitor| monitor.video_modes())
+            .map(|mode| mode.size())
+            .collect();
+        resolutions.sort_by_key(|size| std::cmp::Reverse((size.width, size.height)));
+        resolutions.dedup();
+        if let Some(resolution) = self.config.resolution()
+            && !resolutions.contains(&resolution)
+        {
+            resolutions.insert(0, resolution);
+        }
+
+        let launcher = launcher::Launcher::new(
+            window.clone(),
+            &self.graphics,
+            self.config.clone(),
+            resolutions,
+        );
+        match pollster::block_on(launcher) {
+            Ok(launcher) => self.launcher = Some(launcher),
+            Err(e) => {
+                log::error!("Unable to open the launcher, {:#}", e);
+                self.place_window(event_loop, &window);
+                self.start(event_loop, window);
+            }
+        }
+    }
+
+    /// Handles an event for the launcher, closing it and starting the demo or quitting once a
+    /// choice is made. Returns false if the launcher is not open.
+    #[cfg(all(feature = "launcher", not(target_arch = "wasm32")))]
+    fn launcher_event(&mut self, event_loop: &ActiveEventLoop, event: &WindowEvent) -> bool {
+        let Some(launcher) = &mut self.launcher else {
+            return false;
+        };
+        let choice = match event {
+            WindowEvent::CloseRequested => Some(launcher::Choice::Quit),
+            WindowEvent::Resized(size) => {
+                launcher.resize(size.width, size.height);
+                None
+            }
+            WindowEvent::RedrawRequested => match launcher.draw() {
+                Ok(choice) => choice,
+                Err(e) => {
+                    log::error!("{:#}", e);
+                    Some(launcher::Choice::Start)
+                }
+            },
+            event => {
+                launcher.handle_event(event);
+                None
+            }
+        };
+        match choice {
+            None => {}
+            Some(launcher::Choice::Quit) => event_loop.exit(),
+            Some(launcher::Choice::Start) => {
+                if let Some(launcher) = self.launcher.take() {
+                    let (window, config) = launcher.finish();
+                    if let Err(e) = config.save() {
+                        log::error!("Unable to save the launcher settings, {:#}", e);
+                    }
+                    self.graphics.msaa = config.msaa;
+                    self.graphics.present_mode =
+                        self.present_mode.unwrap_or_else(|| config.present_mode());
+                    self.config = config;
+                    window.set_resizable(true);
+                    self.place_window(event_loop, &window);
+                    self.start(event_loop, window);
+                }
+            }
+        }
+        true
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn new(event_loop: &EventLoop<UserEvent>, canvas: web_sys::HtmlCanvasElement) -> Self {
+        Self {
+            proxy: Some(event_loop.create_proxy()),
+            canvas: Some(canvas),
+            ..Default::default()
+        }
+    }
+
+    /// Rebuilds the state on a new device, as the old one was lost. On the web this finishes
+    /// asynchronously, with commands queued up meanwhile.
+    fn recover(&mut self, event_loop: &ActiveEventLoop) {
+        let Some(state) = self.state.take() else {
+            return;
+        };
+
+        #[cfg(not(target_arch = "wasm32"))]
+        match pollster::block_on(state.recover()) {
+            Ok(state) => {
+                log::info!("Started again on a new device");
+                self.state = Some(state);
+            }
+            Err(e) => self.fail(event_loop, e),
+        }
+
+        #[cfg(target_arch = "wasm32")]
+        {
+            let _ = event_loop;
+            if let Some(proxy) = self.proxy.clone() {
+                wasm_bindgen_futures::spawn_local(async move {
+                    match state.recover().await {
+                        Ok(state) => {
+                            log::info!("Started again on a new device");
+                            web::dispatch_ready(&state.adapter_info);
+                            let _ = proxy.send_event(UserEvent::Ready(Box::new(state)));
+                        }
+                        Err(e) => {
+                            log::error!("{:#}", e);
+                            web::show_error(&e);
+                        }
+                    }
+                });
+            }
+        }
+    }
+
+    /// Stops the demo for good. On the web the reason is shown over the canvas, which would
+    /// otherwise be left blank, and natively the event loop exits with it.
+    fn fail(&mut self, event_loop: &ActiveEventLoop, error: anyhow::Error) {
+        self.state = None;
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            self.error = Some(error);
+            event_loop.exit();
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            let _ = event_loop;
+            log::error!("{:#}", error);
+            web::show_error(&error);
         }
     }
 }
 
-impl ApplicationHandler<State> for Demo {
+impl ApplicationHandler<UserEvent> for Demo {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
         let mut window_attributes = Window::default_attributes();
 
-        #[cfg(not(target_arch = "wasm32"))]
-        {
+        #[cfg(all(feature = "launcher", not(target_arch = "wasm32")))]
+        if self.show_launcher {
             window_attributes = window_attributes
-                .with_fullscreen(Some(Fullscreen::Borderless(None)))
-                .with_inner_size(LogicalSize::new(1920.0, 1080.0))
+                .with_title("Noodles")
+                .with_inner_size(launcher::Launcher::SIZE)
+                .with_resizable(false);
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if !cfg!(feature = "launcher") || !self.show_launcher {
+            window_attributes = match self.config.resolution() {
+                Some(resolution) => window_attributes.with_inner_size(resolution),
+                None => {
+                    window_attributes.with_inner_size(winit::dpi::LogicalSize::new(1920.0, 1080.0))
+                }
+            };
+            if self.config.fullscreen {
+                window_attributes =
+                    window_attributes.with_fullscreen(Some(self.fullscreen(event_loop)));
+            }
         }
 
         #[cfg(target_arch = "wasm32")]
         {
-            use wasm_bindgen::JsCast;
             use winit::platform::web::WindowAttributesExtWebSys;
 
-            const CANVAS_ID: &str = "canvas";
-            let window = wgpu::web_sys::window().unwrap_throw();
-            let document = window.document().unwrap_throw();
-            let canvas = document.get_element_by_id(CANVAS_ID).unwrap_throw();
-            let html_canvas_element = canvas.unchecked_into();
-            window_attributes = window_attributes.with_canvas(Some(html_canvas_element));
+            window_attributes = window_attributes.with_canvas(self.canvas.clone());
         }
 
-        let window = Arc::new(event_loop.create_window(window_attributes).unwrap());
+        let window = match event_loop.create_window(window_attributes) {
+            Ok(window) => Arc::new(window),
+            Err(e) => return self.fail(event_loop, GraphicsError::Window(e).into()),
+        };
 
-        #[cfg(not(target_arch = "wasm32"))]
-        {
-            //block on creating the graphics state on native
-            self.state = Some(pollster::block_on(State::new(window)).unwrap());
+        #[cfg(all(feature = "launcher", not(target_arch = "wasm32")))]
+        if self.show_launcher {
+            return self.open_launcher(event_loop, window);
         }
 
+        #[cfg(not(target_arch = "wasm32"))]
+        self.start(event_loop, window);
+
         #[cfg(target_arch = "wasm32")]
         {
             // Run the future asynchronously and use the
             // proxy to send the results to the event loop
-            if let Some(proxy) = self.proxy.take() {
+            if let Some(proxy) = self.proxy.clone()
+                && self.state.is_none()
+            {
+                let graphics = self.graphics.clone();
                 wasm_bindgen_futures::spawn_local(async move {
-                    assert!(
-                        proxy
-                            .send_event(State::new(window).await.expect("Unable to create canvas!"))
-                            .is_ok()
-                    )
+                    match State::new(window, graphics).await {
+                        Ok(state) => {
+                            web::dispatch_ready(&state.adapter_info);
+                            assert!(proxy.send_event(UserEvent::Ready(Box::new(state))).is_ok())
+                        }
+                        Err(e) => {
+                            log::error!("{:#}", e);
+                            web::show_error(&e);
+                        }
+                    }
                 });
             }
         }
     }
 
-    #[allow(unused_mut)]
-    fn user_event(&mut self, _event_loop: &ActiveEventLoop, mut event: State) {
-        #[cfg(target_arch = "wasm32")]
-        {
-            event.window.request_redraw();
-            event.resize(
-                event.window.inner_size().width,
-                event.window.inner_size().height,
-            );
+    fn user_event(&mut self, _event_loop: &ActiveEventLoop, event: UserEvent) {
+        match event {
+            #[cfg(target_arch = "wasm32")]
+            UserEvent::Ready(mut state) => {
+                state.window.request_redraw();
+                state.resize(
+                    state.window.inner_size().width,
+                    state.window.inner_size().height,
+                );
+                for command in self.pending.drain(..) {
+                    command.apply(&mut state);
+                }
+                self.state = Some(*state);
+            }
+            #[cfg(target_arch = "wasm32")]
+            UserEvent::Command(command) => match &mut self.state {
+                Some(state) => command.apply(state),
+                None => self.pending.push(command),
+            },
         }
-        self.state = Some(event);
     }
 
     fn window_event(
@@ -301,6 +1116,18 @@ impl ApplicationHandler<State> for Demo {
         _window_id: WindowId,
         event: WindowEvent,
     ) {
+        #[cfg(all(feature = "launcher", not(target_arch = "wasm32")))]
+        if self.launcher_event(event_loop, &event) {
+            return;
+        }
+
+        #[cfg(feature = "overlay")]
+        if let Some(state) = &mut self.state
+            && state.overlay.handle_event(&state.window, &event)
+        {
+            return;
+        }
+
         match event {
             WindowEvent::CloseRequested => {
                 event_loop.exit();
@@ -315,6 +1142,11 @@ impl ApplicationHandler<State> for Demo {
                 ..
             } => match key {
                 NamedKey::Escape => event_loop.exit(),
+                NamedKey::Space => {
+                    if let Some(state) = &mut self.state {
+                        state.toggle_pause();
+                    }
+                }
                 NamedKey::F11 => {
                     if let Some(State { window, .. }) = &mut self.state {
                         window.set_fullscreen(match window.fullscreen() {
@@ -327,78 +1159,166 @@ impl ApplicationHandler<State> for Demo {
                 }
                 _ => {}
             },
-            WindowEvent::Resized(size) => {
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        logical_key: Key::Character(key),
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } => {
                 if let Some(state) = &mut self.state {
-                    state.resize(size.width, size.height)
+                    match key.to_lowercase().as_str() {
+                        "i" => state.cycle_integrator(),
+                        "s" => state.cycle_seeding(),
+                        "g" => state.cycle_growth(),
+                        "n" => state.cycle_source(),
+                        #[cfg(feature = "overlay")]
+                        "o" => state.overlay.toggle(),
+                        #[cfg(target_arch = "wasm32")]
+                        "l" => web::copy_link(state),
+                        #[cfg(not(target_arch = "wasm32"))]
+                        "e" => {
+                            if let Err(e) = state.export_meshes() {
+                                log::error!("Unable to export meshes, {:#}", e);
+                            }
+                        }
+                        #[cfg(not(target_arch = "wasm32"))]
+                        "p" => {
+                            if let Err(e) = state.export_print() {
+                                log::error!("Unable to export print, {:#}", e);
+                            }
+                        }
+                        #[cfg(not(target_arch = "wasm32"))]
+                        "c" => {
+                            if let Err(e) = state.export_curves() {
+                                log::error!("Unable to export curves, {:#}", e);
+                            }
+                        }
+                        #[cfg(not(target_arch = "wasm32"))]
+                        "v" => {
+                            if let Err(e) = state.export_plot() {
+                                log::error!("Unable to export plot, {:#}", e);
+                            }
+                        }
+                        _ => {}
+                    }
                 }
             }
-            WindowEvent::RedrawRequested => {
+            WindowEvent::DroppedFile(path) => {
                 if let Some(state) = &mut self.state {
-                    match state.render() {
-                        Ok(_) => {}
-                        Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
-                            let size = state.window.inner_size();
-                            state.resize(size.width, size.height);
+                    let extension = path
+                        .extension()
+                        .and_then(|extension| extension.to_str())
+                        .map(str::to_ascii_lowercase);
+                    if extension.as_deref() == Some("toml") {
+                        match Scene::load(&path) {
+                            Ok(scene) => state.set_scene(scene),
+                            Err(e) => log::error!("{:#}", e),
                         }
-                        Err(e) => {
-                            log::error!("Unable to render, {}", e);
+                    } else if extension.as_deref() == Some("hair")
+                        || Polylines::is_polyline_csv(&path)
+                    {
+                        if let Err(e) = state.import_strands(&path) {
+                            log::error!("Unable to import strands, {:#}", e);
+                        }
+                    } else {
+                        match state.scene.seeding.for_file(path) {
+                            Some(seeding) => state.set_seeding(seeding),
+                            None => {
+                                log::warn!(
+                                    "Dropped file is not a scene, strand file, OBJ mesh or CSV seed list"
+                                )
+                            }
                         }
                     }
                 }
             }
+            WindowEvent::Resized(size) => {
+                if let Some(state) = &mut self.state {
+                    state.resize(size.width, size.height)
+                }
+            }
+            WindowEvent::RedrawRequested => {
+                let Some(state) = &mut self.state else {
+                    return;
+                };
+                if let Err(e) = state.check_device() {
+                    log::warn!("{:#}, starting again on a new device", e);
+                    return self.recover(event_loop);
+                }
+                match state.render() {
+                    Ok(_) => {}
+                    Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                        let size = state.window.inner_size();
+                        state.resize(size.width, size.height);
+                    }
+                    Err(wgpu::SurfaceError::OutOfMemory) => {
+                        log::warn!(
+                            "Out of memory for the next frame, starting again on a new device"
+                        );
+                        self.recover(event_loop);
+                    }
+                    Err(e) => {
+                        log::error!("Unable to render, {}", e);
+                    }
+                }
+            }
             _ => {}
         }
     }
 }
 
+#[cfg(not(target_arch = "wasm32"))]
 pub fn run() -> anyhow::Result<()> {
-    #[cfg(not(target_arch = "wasm32"))]
-    {
-        env_logger::init();
-    }
-    #[cfg(target_arch = "wasm32")]
-    {
-        console_log::init_with_level(log::Level::Info).unwrap_throw();
-    }
+    use anyhow::Context;
+    use clap::Parser;
 
-    let event_loop = EventLoop::with_user_event().build()?;
+    env_logger::init();
 
-    event_loop.set_control_flow(ControlFlow::Poll);
-
-    #[cfg(not(target_arch = "wasm32"))]
-    {
-        let mut demo = Demo::new();
-        event_loop.run_app(&mut demo)?;
+    let cli = cli::Cli::parse();
+    if cli.list_adapters {
+        cli::list_adapters(cli.backend.into());
+        return Ok(());
     }
-
-    #[cfg(target_arch = "wasm32")]
-    {
-        use winit::platform::web::EventLoopExtWebSys;
-
-        let demo = Demo::new(&event_loop);
-        let _ = event_loop.spawn_app(demo);
+    if let Some(command) = &cli.command {
+        return headless::run(&cli, command);
     }
+    let scene = cli.scene.as_deref().map(Scene::load).transpose()?;
+    let mut config = config::Config::load();
+    cli.override_config(&mut config);
 
-    #[cfg(target_arch = "wasm32")]
-    web_sys::console::log_1(&wasm_bindgen::JsValue::from_str("Started demo."));
+    let event_loop = EventLoop::with_user_event()
+        .build()
+        .context("Unable to start the event loop")?;
 
-    Ok(())
-}
+    event_loop.set_control_flow(winit::event_loop::ControlFlow::Poll);
 
-#[cfg(target_arch = "wasm32")]
-pub fn run_web() -> Result<(), wasm_bindgen::JsValue> {
-    console_error_panic_hook::set_once();
-    run().unwrap_throw();
+    let mut demo = Demo::new(&cli, config, scene);
+    event_loop.run_app(&mut demo)?;
 
-    Ok(())
+    match demo.error {
+        Some(error) => Err(error),
+        None => Ok(()),
+    }
 }
 
 #[cfg(not(target_arch = "wasm32"))]
-fn main() {
-    run().expect("Could not run!");
+fn main() -> std::process::ExitCode {
+    match run() {
+        Ok(()) => std::process::ExitCode::SUCCESS,
+        Err(e) => {
+            log::error!("{:#}", e);
+            let code = e
+                .downcast_ref::<GraphicsError>()
+                .map_or(1, GraphicsError::exit_code);
+            std::process::ExitCode::from(code)
+        }
+    }
 }
 
 #[cfg(target_arch = "wasm32")]
 fn main() {
-    run_web().expect("Could not run!");
+    web::start_on_default_canvas();
 }
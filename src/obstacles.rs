@@ -0,0 +1,137 @@
+use glam::{EulerRot, Mat4, Quat, Vec3};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Solid shapes that strands flow around rather than through.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Obstacles {
+    /// Distance from a surface over which the flow is bent to run along it.
+    pub influence: f32,
+    pub shapes: Vec<Obstacle>,
+    /// A signed distance field baked into a raw volume file, combined with `shapes`.
+    pub volume: Option<SdfVolume>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Obstacle {
+    pub shape: Shape,
+    #[serde(default)]
+    pub placement: Placement,
+    /// Placements at given times in seconds, in time order, linearly interpolated. The first
+    /// and last are held before and after the animation unless it repeats.
+    #[serde(default)]
+    pub keyframes: Vec<Keyframe>,
+    #[serde(default)]
+    pub repeat: bool,
+}
+
+/// Analytic signed distance primitives, centred on the origin and with their axis along Z.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Shape {
+    Sphere {
+        radius: f32,
+    },
+    Box {
+        half_extents: Vec3,
+    },
+    Torus {
+        major_radius: f32,
+        minor_radius: f32,
+    },
+    Capsule {
+        half_length: f32,
+        radius: f32,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Placement {
+    pub position: Vec3,
+    /// Rotation about X, then Y, then Z, in degrees.
+    pub rotation: Vec3,
+    pub scale: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Keyframe {
+    pub time: f32,
+    #[serde(flatten)]
+    pub placement: Placement,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SdfVolume {
+    pub path: PathBuf,
+    /// Overrides the bounds stored in the volume file.
+    pub min: Option<Vec3>,
+    pub max: Option<Vec3>,
+}
+
+impl Default for Placement {
+    fn default() -> Self {
+        Self {
+            position: Vec3::ZERO,
+            rotation: Vec3::ZERO,
+            scale: 1.0,
+        }
+    }
+}
+
+impl Placement {
+    fn rotation(&self) -> Quat {
+        let radians = self.rotation * std::f32::consts::PI / 180.0;
+        Quat::from_euler(EulerRot::XYZ, radians.x, radians.y, radians.z)
+    }
+
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        Self {
+            position: self.position.lerp(other.position, t),
+            rotation: self.rotation.lerp(other.rotation, t),
+            scale: self.scale + (other.scale - self.scale) * t,
+        }
+    }
+
+    pub fn local_to_world(&self) -> Mat4 {
+        Mat4::from_scale_rotation_translation(
+            Vec3::splat(self.scale),
+            self.rotation(),
+            self.position,
+        )
+    }
+}
+
+impl Obstacle {
+    pub fn placement_at(&self, time: f32) -> Placement {
+        let (Some(first), Some(last)) = (self.keyframes.first(), self.keyframes.last()) else {
+            return self.placement;
+        };
+        let span = last.time - first.time;
+        let time = if self.repeat && span > 0.0 {
+            first.time + (time - first.time).rem_euclid(span)
+        } else {
+            time
+        };
+        match self.keyframes.iter().position(|key| key.time > time) {
+            Some(0) => first.placement,
+            None => last.placement,
+            Some(i) => {
+                let (before, after) = (&self.keyframes[i - 1], &self.keyframes[i]);
+                let t = (time - before.time) / (after.time - before.time);
+                before.placement.lerp(&after.placement, t)
+            }
+        }
+    }
+}
+
+impl Default for Obstacles {
+    fn default() -> Self {
+        Self {
+            influence: 0.3,
+            shapes: Vec::new(),
+            volume: None,
+        }
+    }
+}
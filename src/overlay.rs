@@ -0,0 +1,485 @@
+use egui::{Color32, ComboBox, DragValue, Slider, Stroke, Ui};
+use glam::Vec3;
+use std::collections::VecDeque;
+use web_time::Instant;
+use winit::window::Window;
+
+use crate::pipelines::{GrowthMode, Integrator, Pipelines};
+use crate::profiler::{Pass, Profiler, Timings};
+use crate::scene::Scene;
+use crate::view::CameraMode;
+
+/// What the demo has to do about changes made in the overlay. Everything else it reads from
+/// the scene each frame.
+#[derive(Default)]
+pub struct Response {
+    /// Strands should start growing again, as the growth mode changed.
+    pub restart_growth: bool,
+    /// A saved scene to switch to.
+    pub load: Option<Scene>,
+    /// A link to the current settings should be copied.
+    #[cfg(target_arch = "wasm32")]
+    pub copy_link: bool,
+}
+
+/// Sliders for the scene parameters, drawn over the strands with egui.
+pub struct Overlay {
+    context: egui::Context,
+    input: egui_winit::State,
+    renderer: egui_wgpu::Renderer,
+    visible: bool,
+    panel: Panel,
+}
+
+/// The contents of the overlay window.
+struct Panel {
+    /// Seconds taken by recent frames, oldest first.
+    frame_times: VecDeque<f32>,
+    last_frame: Instant,
+    preset_name: String,
+    presets: Vec<String>,
+    /// The outcome of the last save or load, shown under the presets.
+    status: String,
+}
+
+impl Overlay {
+    pub fn new(window: &Window, device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        let context = egui::Context::default();
+        let input = egui_winit::State::new(
+            context.clone(),
+            egui::ViewportId::ROOT,
+            window,
+            Some(window.scale_factor() as f32),
+            None,
+            Some(device.limits().max_texture_dimension_2d as usize),
+        );
+        let renderer = egui_wgpu::Renderer::new(device, format, Default::default());
+        Self {
+            context,
+            input,
+            renderer,
+            visible: false,
+            panel: Panel::new(),
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+        if self.visible {
+            self.panel.presets = presets::names();
+        }
+    }
+
+    /// Passes a window event to the overlay, returning whether it was used up there rather than
+    /// being meant for the demo, such as typing into a text box.
+    pub fn handle_event(&mut self, window: &Window, event: &winit::event::WindowEvent) -> bool {
+        self.visible && self.input.on_window_event(window, event).consumed
+    }
+
+    /// Records the frame time and, if the overlay is showing, lets it edit `scene` and draws it
+    /// over `view`, with the timings from `profiler`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw(
+        &mut self,
+        window: &Window,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        size: [u32; 2],
+        scene: &mut Scene,
+        profiler: &mut Profiler,
+    ) -> Response {
+        self.panel.record_frame();
+        let mut response = Response::default();
+        if !self.visible {
+            return response;
+        }
+
+        let raw_input = self.input.take_egui_input(window);
+        let context = self.context.clone();
+        let timings = profiler.timings();
+        let timed_passes = profiler.has_timestamps();
+        let output = context.run(raw_input, |context| {
+            egui::Window::new("Noodles")
+                .default_width(280.0)
+                .show(context, |ui| {
+                    egui::ScrollArea::vertical().show(ui, |ui| {
+                        self.panel
+                            .ui(ui, scene, &timings, timed_passes, &mut response)
+                    });
+                });
+        });
+        self.input
+            .handle_platform_output(window, output.platform_output);
+
+        let screen = egui_wgpu::ScreenDescriptor {
+            size_in_pixels: size,
+            pixels_per_point: output.pixels_per_point,
+        };
+        let primitives = context.tessellate(output.shapes, output.pixels_per_point);
+        for (id, delta) in &output.textures_delta.set {
+            self.renderer.update_texture(device, queue, *id, delta);
+        }
+        let commands = self
+            .renderer
+            .update_buffers(device, queue, encoder, &primitives, &screen);
+        queue.submit(commands);
+        {
+            let mut render_pass = encoder
+                .begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("Overlay Pass"),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Load,
+                            store: wgpu::StoreOp::Store,
+                        },
+                        depth_slice: None,
+                    })],
+                    depth_stencil_attachment: None,
+                    occlusion_query_set: None,
+                    timestamp_writes: profiler.render_timestamps(Pass::Overlay),
+                })
+                .forget_lifetime();
+            self.renderer.render(&mut render_pass, &primitives, &screen);
+        }
+        for id in &output.textures_delta.free {
+            self.renderer.free_texture(id);
+        }
+        response
+    }
+}
+
+impl Panel {
+    /// Number of frames shown in the frame time graph.
+    const FRAME_HISTORY: usize = 240;
+
+    fn new() -> Self {
+        Self {
+            frame_times: VecDeque::with_capacity(Self::FRAME_HISTORY),
+            last_frame: Instant::now(),
+            preset_name: "preset".to_string(),
+            presets: presets::names(),
+            status: String::new(),
+        }
+    }
+
+    fn record_frame(&mut self) {
+        let now = Instant::now();
+        if self.frame_times.len() == Self::FRAME_HISTORY {
+            self.frame_times.pop_front();
+        }
+        self.frame_times
+            .push_back((now - self.last_frame).as_secs_f32());
+        self.last_frame = now;
+    }
+
+    fn ui(
+        &mut self,
+        ui: &mut Ui,
+        scene: &mut Scene,
+        timings: &Timings,
+        timed_passes: bool,
+        response: &mut Response,
+    ) {
+        egui::CollapsingHeader::new("Frame time")
+            .default_open(true)
+            .show(ui, |ui| {
+                self.frame_time_graph(ui);
+                pass_times(ui, timings, timed_passes);
+            });
+
+        egui::CollapsingHeader::new("Simulation")
+            .default_open(true)
+            .show(ui, |ui| {
+                let simulation = &mut scene.simulation;
+                ComboBox::from_label("integrator")
+                    .selected_text(format!("{:?}", simulation.integrator))
+                    .show_ui(ui, |ui| {
+                        for integrator in Integrator::ALL {
+                            ui.selectable_value(
+                                &mut simulation.integrator,
+                                integrator,
+                                format!("{integrator:?}"),
+                            );
+                        }
+                    });
+                let growth = simulation.growth;
+                ui.horizontal(|ui| {
+                    ui.radio_value(&mut simulation.growth, GrowthMode::Full, "full");
+                    if ui
+                        .radio(matches!(growth, GrowthMode::Grow { .. }), "grow")
+                        .clicked()
+                        && !matches!(growth, GrowthMode::Grow { .. })
+                    {
+                        simulation.growth = GrowthMode::Grow { duration: 10.0 };
+                    }
+                    if ui
+                        .radio(matches!(growth, GrowthMode::Trail { .. }), "trail")
+                        .clicked()
+                        && !matches!(growth, GrowthMode::Trail { .. })
+                    {
+                        simulation.growth = GrowthMode::Trail {
+                            length: Pipelines::SEGMENTS_PER_STRAND as u32,
+                        };
+                    }
+                });
+                match &mut simulation.growth {
+                    GrowthMode::Full => {}
+                    GrowthMode::Grow { duration } => {
+                        ui.add(Slider::new(duration, 0.5..=60.0).text("growth seconds"));
+                    }
+                    GrowthMode::Trail { length } => {
+                        let max = Pipelines::SEGMENTS_PER_STRAND as u32;
+                        ui.add(Slider::new(length, 2..=max).text("trail length"));
+                    }
+                }
+                // Changing the duration of growth carries on from where it had got to.
+                response.restart_growth = match (growth, simulation.growth) {
+                    (GrowthMode::Grow { .. }, GrowthMode::Grow { .. }) => false,
+                    (before, after) => before != after,
+                };
+                ui.add(
+                    Slider::new(&mut simulation.step_size, 0.005..=0.2)
+                        .logarithmic(true)
+                        .text("step size"),
+                );
+                ui.add(
+                    Slider::new(&mut simulation.noise_scale, 0.05..=4.0)
+                        .logarithmic(true)
+                        .text("noise scale"),
+                );
+                ui.add_enabled(
+                    simulation.integrator == Integrator::Rk45,
+                    Slider::new(&mut simulation.tolerance, 1e-7..=1e-2)
+                        .logarithmic(true)
+                        .text("tolerance"),
+                );
+                ui.add(Slider::new(&mut simulation.drift, 0.0..=0.5).text("drift"));
+                let max = Pipelines::NUM_STRANDS as u32;
+                ui.add(Slider::new(&mut simulation.strands, 1..=max).text("strands"));
+                // Trails take their length instead.
+                let max = Pipelines::SEGMENTS_PER_STRAND as u32;
+                ui.add_enabled(
+                    !matches!(simulation.growth, GrowthMode::Trail { .. }),
+                    Slider::new(&mut simulation.segments, 1..=max).text("segments"),
+                );
+                ui.add(
+                    Slider::new(&mut scene.obstacles.influence, 0.0..=2.0)
+                        .text("obstacle influence"),
+                );
+            });
+
+        ui.collapsing("Camera", |ui| {
+            let camera = &mut scene.camera;
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut camera.mode, CameraMode::Orbit, "orbit");
+                ui.radio_value(&mut camera.mode, CameraMode::Still, "still");
+            });
+            vec3_row(ui, "centre", &mut camera.centre, 0.01);
+            vec3_row(ui, "orbit", &mut camera.orbit, 0.01);
+            ui.add(Slider::new(&mut camera.speed, -1.0..=1.0).text("speed"));
+            ui.add(Slider::new(&mut camera.field_of_view, 5.0..=120.0).text("field of view"));
+        });
+
+        ui.collapsing("Lighting", |ui| {
+            let lighting = &mut scene.lighting;
+            vec3_row(ui, "direction", &mut lighting.direction, 0.01);
+            colour_row(ui, "colour", &mut lighting.colour);
+            colour_row(ui, "ambient", &mut lighting.ambient);
+            colour_row(ui, "background", &mut lighting.background);
+        });
+
+        ui.collapsing("Presets", |ui| {
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut self.preset_name);
+                if ui.button("Save").clicked() {
+                    self.status = match presets::save(&self.preset_name, scene) {
+                        Ok(()) => format!("Saved {}", self.preset_name),
+                        Err(e) => format!("Unable to save preset, {e:#}"),
+                    };
+                    self.presets = presets::names();
+                }
+            });
+            for name in &self.presets {
+                if ui.button(name).clicked() {
+                    match presets::load(name) {
+                        Ok(preset) => {
+                            self.status = format!("Loaded {name}");
+                            self.preset_name = name.clone();
+                            response.load = Some(preset);
+                        }
+                        Err(e) => self.status = format!("Unable to load preset, {e:#}"),
+                    }
+                }
+            }
+            #[cfg(target_arch = "wasm32")]
+            if ui.button("Copy link").clicked() {
+                response.copy_link = true;
+            }
+            if !self.status.is_empty() {
+                ui.label(&self.status);
+            }
+        });
+    }
+
+    fn frame_time_graph(&self, ui: &mut Ui) {
+        let count = self.frame_times.len().max(1) as f32;
+        let mean = self.frame_times.iter().sum::<f32>() / count;
+        let worst = self.frame_times.iter().copied().fold(0.0, f32::max);
+        ui.label(format!(
+            "{:.1} ms mean, {:.1} ms worst, {:.0} fps",
+            1000.0 * mean,
+            1000.0 * worst,
+            1.0 / mean.max(1e-6)
+        ));
+
+        let (rect, _) =
+            ui.allocate_exact_size(egui::vec2(ui.available_width(), 60.0), egui::Sense::hover());
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+        // The graph is scaled to fit the worst frame, but always shows at least 30 fps.
+        let top = worst.max(1.0 / 30.0);
+        let y = |seconds: f32| rect.bottom() - rect.height() * (seconds / top).min(1.0);
+        painter.hline(
+            rect.x_range(),
+            y(1.0 / 60.0),
+            Stroke::new(1.0, Color32::DARK_GREEN),
+        );
+        let step = rect.width() / (Self::FRAME_HISTORY - 1) as f32;
+        let points = self
+            .frame_times
+            .iter()
+            .enumerate()
+            .map(|(i, &seconds)| egui::pos2(rect.left() + step * i as f32, y(seconds)))
+            .collect();
+        painter.add(egui::Shape::line(
+            points,
+            Stroke::new(1.0, ui.visuals().text_color()),
+        ));
+    }
+}
+
+/// The profiler's averages, with each pass on the GPU if it can time them.
+fn pass_times(ui: &mut Ui, timings: &Timings, timed_passes: bool) {
+    ui.label(format!("{:.2} ms recording on the CPU", timings.cpu));
+    if !timed_passes {
+        ui.label("The adapter has no timestamp queries to time passes with");
+        return;
+    }
+    for (pass, time) in Pass::ALL.iter().zip(timings.passes) {
+        if let Some(time) = time {
+            ui.label(format!("{:.2} ms {} pass on the GPU", time, pass.name()));
+        }
+    }
+}
+
+fn vec3_row(ui: &mut Ui, label: &str, value: &mut Vec3, speed: f32) {
+    ui.horizontal(|ui| {
+        ui.add(DragValue::new(&mut value.x).speed(speed).prefix("x "));
+        ui.add(DragValue::new(&mut value.y).speed(speed).prefix("y "));
+        ui.add(DragValue::new(&mut value.z).speed(speed).prefix("z "));
+        ui.label(label);
+    });
+}
+
+fn colour_row(ui: &mut Ui, label: &str, colour: &mut Vec3) {
+    ui.horizontal(|ui| {
+        let mut rgb = colour.to_array();
+        ui.color_edit_button_rgb(&mut rgb);
+        *colour = Vec3::from_array(rgb);
+        ui.label(label);
+    });
+}
+
+/// Scenes saved as TOML files in the working directory on native builds.
+#[cfg(not(target_arch = "wasm32"))]
+mod presets {
+    use std::path::PathBuf;
+
+    use crate::scene::Scene;
+
+    const DIRECTORY: &str = "presets";
+
+    fn path(name: &str) -> anyhow::Result<PathBuf> {
+        anyhow::ensure!(
+            !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | ' ')),
+            "Preset names may only contain letters, numbers, spaces, dashes and underscores"
+        );
+        Ok(PathBuf::from(DIRECTORY).join(name).with_extension("toml"))
+    }
+
+    pub fn names() -> Vec<String> {
+        let Ok(entries) = std::fs::read_dir(DIRECTORY) else {
+            return Vec::new();
+        };
+        let mut names: Vec<_> = entries
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                (path.extension()? == "toml").then_some(path.file_stem()?.to_str()?.to_string())
+            })
+            .collect();
+        names.sort();
+        names
+    }
+
+    pub fn save(name: &str, scene: &Scene) -> anyhow::Result<()> {
+        let path = path(name)?;
+        std::fs::create_dir_all(DIRECTORY)?;
+        std::fs::write(&path, toml::to_string(scene)?)?;
+        log::info!("Saved scene to {}", path.display());
+        Ok(())
+    }
+
+    pub fn load(name: &str) -> anyhow::Result<Scene> {
+        Scene::load(&path(name)?)
+    }
+}
+
+/// Scenes saved as TOML in the browser's local storage on the web.
+#[cfg(target_arch = "wasm32")]
+mod presets {
+    use anyhow::{Context, anyhow};
+
+    use crate::scene::Scene;
+
+    const PREFIX: &str = "noodles-preset:";
+
+    fn storage() -> anyhow::Result<web_sys::Storage> {
+        web_sys::window()
+            .and_then(|window| window.local_storage().ok().flatten())
+            .context("Local storage is unavailable")
+    }
+
+    pub fn names() -> Vec<String> {
+        let Ok(storage) = storage() else {
+            return Vec::new();
+        };
+        let mut names: Vec<_> = (0..storage.length().unwrap_or(0))
+            .filter_map(|i| storage.key(i).ok().flatten())
+            .filter_map(|key| key.strip_prefix(PREFIX).map(str::to_string))
+            .collect();
+        names.sort();
+        names
+    }
+
+    pub fn save(name: &str, scene: &Scene) -> anyhow::Result<()> {
+        anyhow::ensure!(!name.is_empty(), "Preset names may not be empty");
+        storage()?
+            .set_item(&format!("{PREFIX}{name}"), &toml::to_string(scene)?)
+            .map_err(|e| anyhow!("{e:?}"))
+    }
+
+    pub fn load(name: &str) -> anyhow::Result<Scene> {
+        let text = storage()?
+            .get_item(&format!("{PREFIX}{name}"))
+            .map_err(|e| anyhow!("{e:?}"))?
+            .with_context(|| format!("There is no preset called {name}"))?;
+        toml::from_str(&text).with_context(|| format!("Unable to parse preset {name}"))
+    }
+}
@@ -1,12 +1,20 @@
 mod attributes;
 
-use bytemuck::bytes_of;
-use glam::{Mat4, UVec3, Vec3, uvec3, vec3};
-use std::f32::consts::{PI, TAU};
+use anyhow::ensure;
+use bytemuck::{Zeroable, bytes_of};
+use glam::{Mat4, UVec3, Vec3, Vec4, uvec3, vec3, vec4};
+use half::f16;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::TAU;
 use wesl::include_wesl;
 use wgpu::util::DeviceExt;
 
-use self::attributes::{TubeInstance, Vertex};
+pub use self::attributes::TubeInstance;
+use self::attributes::Vertex;
+use crate::field::Precision;
+use crate::obstacles::{Obstacles, Shape};
+use crate::view::Lighting;
+use crate::volume::Volume;
 
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Zeroable, bytemuck::Pod)]
@@ -16,20 +24,334 @@ struct Uniforms {
     time: f32,
     ambient: Vec3,
     _padding_2: u32,
+    light_colour: Vec3,
+    _padding_3: u32,
+}
+
+/// Numerical scheme used to trace strands through the curl noise field.
+#[repr(u32)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Integrator {
+    /// Forward Euler, one field sample per step.
+    #[default]
+    Euler = 0,
+    /// Second order midpoint method.
+    Midpoint = 1,
+    /// Classic fourth order Runge-Kutta.
+    Rk4 = 2,
+    /// Adaptive Dormand-Prince 5(4), with the step also limited by the local curvature.
+    Rk45 = 3,
+}
+
+impl Integrator {
+    pub const ALL: [Self; 4] = [Self::Euler, Self::Midpoint, Self::Rk4, Self::Rk45];
+
+    pub fn next(self) -> Self {
+        Self::ALL[(self as usize + 1) % Self::ALL.len()]
+    }
+}
+
+/// How strands develop over time.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum GrowthMode {
+    /// Every strand is traced to full length each frame.
+    #[default]
+    Full,
+    /// Strands grow from their seeds to full length over `duration` seconds.
+    Grow { duration: f32 },
+    /// Each strand is the trail of a particle advected through the field, made of its last
+    /// `length` steps. Only the head of the trail is computed each frame.
+    Trail { length: u32 },
+}
+
+impl GrowthMode {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Full => Self::Grow { duration: 10.0 },
+            Self::Grow { .. } => Self::Trail {
+                length: Pipelines::SEGMENTS_PER_STRAND as u32,
+            },
+            Self::Trail { .. } => Self::Full,
+        }
+    }
+}
+
+/// Parameters controlling how strands are traced.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SimulationParams {
+    pub integrator: Integrator,
+    pub growth: GrowthMode,
+    /// Arc length of each segment of a strand, and the largest step the integrators take.
+    pub step_size: f32,
+    pub noise_scale: f32,
+    /// Local error allowed per step by the adaptive integrator.
+    pub tolerance: f32,
+    /// How fast the noise moves through time, in noise units per second.
+    pub drift: f32,
+    /// Number of strands traced and drawn, up to [`Pipelines::NUM_STRANDS`].
+    pub strands: u32,
+    /// Number of segments traced per strand, up to [`Pipelines::SEGMENTS_PER_STRAND`].
+    pub segments: u32,
+}
+
+impl Default for SimulationParams {
+    fn default() -> Self {
+        Self {
+            integrator: Integrator::default(),
+            growth: GrowthMode::default(),
+            step_size: 0.05,
+            noise_scale: 0.5,
+            tolerance: 1e-4,
+            drift: 0.05,
+            strands: Pipelines::NUM_STRANDS as u32,
+            segments: Pipelines::SEGMENTS_PER_STRAND as u32,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Zeroable, bytemuck::Pod)]
+struct SimulationUniforms {
+    integrator: u32,
+    step_size: f32,
+    noise_scale: f32,
+    tolerance: f32,
+    growth: f32,
+    trail_length: u32,
+    strands: u32,
+    segments: u32,
+    trail_steps: u32,
+    _padding: [u32; 3],
+}
+
+impl SimulationUniforms {
+    fn new(params: &SimulationParams, growth_progress: f32, trail_steps: u32) -> Self {
+        Self {
+            integrator: params.integrator as u32,
+            step_size: params.step_size,
+            noise_scale: params.noise_scale,
+            tolerance: params.tolerance,
+            growth: match params.growth {
+                GrowthMode::Grow { .. } => growth_progress.clamp(0.0, 1.0),
+                _ => 1.0,
+            },
+            trail_length: match params.growth {
+                GrowthMode::Trail { length } => length,
+                _ => 0,
+            },
+            strands: Self::strands(params),
+            segments: params.segments,
+            trail_steps,
+            _padding: [0; 3],
+        }
+    }
+
+    fn strands(params: &SimulationParams) -> u32 {
+        params.strands.clamp(1, Pipelines::NUM_STRANDS as u32)
+    }
+
+    /// Instances each strand takes up, one strand after another: its segments, or the ring
+    /// buffer of its trail.
+    fn segments_per_strand(params: &SimulationParams) -> u32 {
+        let segments = match params.growth {
+            GrowthMode::Trail { length } => length,
+            _ => params.segments,
+        };
+        segments.clamp(1, Pipelines::SEGMENTS_PER_STRAND as u32)
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Zeroable, bytemuck::Pod)]
+struct ObstacleUniform {
+    world_to_local: Mat4,
+    params: Vec4,
+    kind: u32,
+    scale: f32,
+    _padding: [u32; 2],
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Zeroable, bytemuck::Pod)]
+struct ObstacleUniforms {
+    volume_min: Vec3,
+    count: u32,
+    volume_max: Vec3,
+    volume_enabled: u32,
+    influence: f32,
+    _padding: [u32; 3],
+    items: [ObstacleUniform; Pipelines::MAX_OBSTACLES],
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Zeroable, bytemuck::Pod)]
+struct FieldUniforms {
+    min: Vec3,
+    enabled: u32,
+    max: Vec3,
+    scale: f32,
+}
+
+/// An imported vector field and where it sits in the world.
+pub struct VectorField<'a> {
+    pub volume: &'a Volume,
+    pub min: Vec3,
+    pub max: Vec3,
+    pub scale: f32,
+    pub precision: Precision,
+}
+
+/// Everything bound to the compute shader entry points.
+struct ComputeBindings<'a> {
+    uniform_buffer: &'a wgpu::Buffer,
+    instance_buffer: &'a wgpu::Buffer,
+    simulation_buffer: &'a wgpu::Buffer,
+    seed_buffer: &'a wgpu::Buffer,
+    trail_buffer: &'a wgpu::Buffer,
+    obstacle_buffer: &'a wgpu::Buffer,
+    sdf_volume: &'a wgpu::TextureView,
+    vector_field: &'a wgpu::TextureView,
+    field_buffer: &'a wgpu::Buffer,
+}
+
+impl ComputeBindings<'_> {
+    /// Only `advance_trails` uses the trail buffer, and automatic layouts leave out bindings
+    /// that an entry point does not use.
+    fn create_bind_group(
+        &self,
+        device: &wgpu::Device,
+        pipeline: &wgpu::ComputePipeline,
+        with_trails: bool,
+    ) -> wgpu::BindGroup {
+        let mut entries = vec![
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: self.uniform_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: self.instance_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: self.simulation_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: self.seed_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 5,
+                resource: self.obstacle_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 6,
+                resource: wgpu::BindingResource::TextureView(self.sdf_volume),
+            },
+            wgpu::BindGroupEntry {
+                binding: 7,
+                resource: wgpu::BindingResource::TextureView(self.vector_field),
+            },
+            wgpu::BindGroupEntry {
+                binding: 8,
+                resource: self.field_buffer.as_entire_binding(),
+            },
+        ];
+        if with_trails {
+            entries.push(wgpu::BindGroupEntry {
+                binding: 4,
+                resource: self.trail_buffer.as_entire_binding(),
+            });
+        }
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(if with_trails {
+                "Noodle trail bind group"
+            } else {
+                "Noodle compute bind group"
+            }),
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &entries,
+        })
+    }
+}
+
+/// The compute pipelines that trace strands, with everything bound to them.
+struct Compute {
+    pipeline: wgpu::ComputePipeline,
+    trail_pipeline: wgpu::ComputePipeline,
+    bind_group: wgpu::BindGroup,
+    trail_bind_group: wgpu::BindGroup,
+}
+
+impl Compute {
+    fn new(
+        device: &wgpu::Device,
+        compute_shader: &wgpu::ShaderModule,
+        bindings: &ComputeBindings,
+    ) -> Self {
+        let pipeline = Pipelines::create_compute_pipeline(device, compute_shader, false);
+        let trail_pipeline = Pipelines::create_compute_pipeline(device, compute_shader, true);
+        Self::bind(device, pipeline, trail_pipeline, bindings)
+    }
+
+    fn bind(
+        device: &wgpu::Device,
+        pipeline: wgpu::ComputePipeline,
+        trail_pipeline: wgpu::ComputePipeline,
+        bindings: &ComputeBindings,
+    ) -> Self {
+        Self {
+            bind_group: bindings.create_bind_group(device, &pipeline, false),
+            trail_bind_group: bindings.create_bind_group(device, &trail_pipeline, true),
+            pipeline,
+            trail_pipeline,
+        }
+    }
 }
 
 pub struct Pipelines {
     render_pipeline: wgpu::RenderPipeline,
-    compute_pipeline: wgpu::ComputePipeline,
+    /// `None` on devices without compute shaders or storage buffers in vertex shaders, such as
+    /// WebGL2 ones, which draw uploaded segments from a vertex buffer instead.
+    compute: Option<Compute>,
     render_bind_group: wgpu::BindGroup,
-    compute_bind_group: wgpu::BindGroup,
     uniform_buffer: wgpu::Buffer,
+    instance_buffer: wgpu::Buffer,
+    simulation_buffer: wgpu::Buffer,
+    seed_buffer: wgpu::Buffer,
+    trail_buffer: wgpu::Buffer,
+    obstacle_buffer: wgpu::Buffer,
+    sdf_volume: wgpu::Texture,
+    sdf_bounds: Option<(Vec3, Vec3)>,
+    vector_field: wgpu::Texture,
+    field_buffer: wgpu::Buffer,
     cylinder_vertex_buffer: wgpu::Buffer,
+    /// Sides of the tubes, around which the cylinder is made.
+    sides: usize,
+    /// Segments uploaded from the CPU, drawn in place of the computed ones, and their count.
+    uploaded_instances: Option<(wgpu::Buffer, u32)>,
+    /// The starting point of each strand, as last uploaded.
+    seeds: Vec<Vec3>,
+    camera: Mat4,
+    /// Segments of the strands traced by the compute shaders, which are the first in the
+    /// instance buffer, and how many of them each strand takes up.
+    computed_segments: usize,
+    segments_per_strand: usize,
+    /// Whether each strand's segments are the ring buffer of a trail, which wraps around.
+    trails: bool,
+    /// How far into the demo the trails have been advanced, or `None` once they are reset.
+    trail_time: Option<f32>,
+    #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+    surface_format: wgpu::TextureFormat,
+    #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+    sample_count: u32,
 }
 
 impl Pipelines {
-    const SIDES: usize = 8;
-    const VERTICES: usize = 2 * Self::SIDES + 2;
+    pub const DEFAULT_SIDES: usize = 8;
     const WORKGROUPS: UVec3 = uvec3(2, 2, 1);
     const WORKGROUP_SIZE: UVec3 = uvec3(16, 16, 1);
     const STRANDS: UVec3 = uvec3(
@@ -37,11 +359,23 @@ impl Pipelines {
         Self::WORKGROUPS.y * Self::WORKGROUP_SIZE.y,
         Self::WORKGROUPS.z * Self::WORKGROUP_SIZE.z,
     );
-    const NUM_STRANDS: usize = (Self::STRANDS.x * Self::STRANDS.y * Self::STRANDS.z) as usize;
-    const SEGMENTS_PER_STRAND: usize = 64;
+    pub const NUM_STRANDS: usize = (Self::STRANDS.x * Self::STRANDS.y * Self::STRANDS.z) as usize;
+    pub const SEGMENTS_PER_STRAND: usize = 64;
+    const MAX_OBSTACLES: usize = 16;
     const NUM_SEGMENTS: usize = Self::NUM_STRANDS * Self::SEGMENTS_PER_STRAND;
+    /// Steps the particles at the heads of the trails take each second.
+    const TRAIL_STEPS_PER_SECOND: f32 = 60.0;
 
-    pub fn new(device: &wgpu::Device, surface_format: wgpu::TextureFormat) -> Self {
+    /// Distance stored in the single texel bound when no SDF volume is in use.
+    const FAR_AWAY: f32 = 1e9;
+
+    /// Draws into `surface_format` with `sample_count` samples per pixel.
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        surface_format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> Self {
         let shaders = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("Noodles vertex shader"),
             source: wgpu::ShaderSource::Wgsl(include_wesl!("tube").into()),
@@ -54,14 +388,161 @@ impl Pipelines {
             mapped_at_creation: false,
         });
 
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        let computes = Self::supports_compute(device);
+        let render_pipeline =
+            Self::create_render_pipeline(device, &shaders, surface_format, sample_count, computes);
+
+        let instance_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Noodle instance buffer"),
+            size: (std::mem::size_of::<TubeInstance>() * Self::NUM_SEGMENTS) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        let render_bind_group = Self::create_render_bind_group(
+            device,
+            &render_pipeline,
+            &uniform_buffer,
+            computes.then_some(&instance_buffer),
+        );
+
+        let cylinder_vertex_buffer = Self::create_cylinder(device, Self::DEFAULT_SIDES);
+
+        let simulation_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Noodle simulation buffer"),
+            contents: bytes_of(&SimulationUniforms::new(
+                &SimulationParams::default(),
+                1.0,
+                0,
+            )),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let seed_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Noodle seed buffer"),
+            size: (std::mem::size_of::<Vec4>() * Self::NUM_STRANDS) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let trail_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Noodle trail buffer"),
+            size: (2 * std::mem::size_of::<u32>() * Self::NUM_STRANDS) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let obstacle_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Noodle obstacle buffer"),
+            size: std::mem::size_of::<ObstacleUniforms>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let sdf_volume = Self::create_volume_texture(
+            device,
+            queue,
+            "Noodle SDF volume",
+            wgpu::TextureFormat::R32Float,
+            UVec3::ONE,
+            bytes_of(&Self::FAR_AWAY),
+        );
+
+        let vector_field = Self::create_volume_texture(
+            device,
+            queue,
+            "Noodle vector field",
+            wgpu::TextureFormat::Rgba32Float,
+            UVec3::ONE,
+            bytes_of(&Vec4::ZERO),
+        );
+
+        let field_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Noodle field buffer"),
+            size: std::mem::size_of::<FieldUniforms>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bindings = ComputeBindings {
+            uniform_buffer: &uniform_buffer,
+            instance_buffer: &instance_buffer,
+            simulation_buffer: &simulation_buffer,
+            seed_buffer: &seed_buffer,
+            trail_buffer: &trail_buffer,
+            obstacle_buffer: &obstacle_buffer,
+            sdf_volume: &sdf_volume.create_view(&Default::default()),
+            vector_field: &vector_field.create_view(&Default::default()),
+            field_buffer: &field_buffer,
+        };
+        let compute = computes.then(|| {
+            let compute_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Noodle instance compute shader"),
+                source: wgpu::ShaderSource::Wgsl(include_wesl!("instances").into()),
+            });
+            Compute::new(device, &compute_shader, &bindings)
+        });
+
+        Self {
+            render_pipeline,
+            compute,
+            render_bind_group,
+            uniform_buffer,
+            instance_buffer,
+            simulation_buffer,
+            seed_buffer,
+            trail_buffer,
+            obstacle_buffer,
+            sdf_volume,
+            sdf_bounds: None,
+            vector_field,
+            field_buffer,
+            cylinder_vertex_buffer,
+            sides: Self::DEFAULT_SIDES,
+            uploaded_instances: None,
+            seeds: vec![Vec3::ZERO; Self::NUM_STRANDS],
+            camera: Mat4::IDENTITY,
+            computed_segments: Self::NUM_SEGMENTS,
+            segments_per_strand: Self::SEGMENTS_PER_STRAND,
+            trails: false,
+            trail_time: None,
+            #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+            surface_format,
+            #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+            sample_count,
+        }
+    }
+
+    /// Whether `device` can trace strands in compute shaders and read them from a storage buffer
+    /// in the vertex shader. WebGL2 can do neither.
+    fn supports_compute(device: &wgpu::Device) -> bool {
+        let limits = device.limits();
+        limits.max_compute_workgroups_per_dimension > 0
+            && limits.max_storage_buffers_per_shader_stage > 0
+    }
+
+    /// The pipeline reading segments from the storage buffer if `storage` is set, and from per
+    /// instance vertex attributes if not.
+    fn create_render_pipeline(
+        device: &wgpu::Device,
+        shaders: &wgpu::ShaderModule,
+        surface_format: wgpu::TextureFormat,
+        sample_count: u32,
+        storage: bool,
+    ) -> wgpu::RenderPipeline {
+        let (entry_point, buffers): (_, &[_]) = if storage {
+            ("vs_main", &[Vertex::LAYOUT])
+        } else {
+            ("vs_instanced", &[Vertex::LAYOUT, TubeInstance::LAYOUT])
+        };
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
             label: Some("Noodles render pipeline"),
             layout: None,
             vertex: wgpu::VertexState {
-                module: &shaders,
-                entry_point: Some("vs_main"),
+                module: shaders,
+                entry_point: Some(entry_point),
                 compilation_options: Default::default(),
-                buffers: &[Vertex::LAYOUT],
+                buffers,
             },
             primitive: wgpu::PrimitiveState {
                 topology: wgpu::PrimitiveTopology::TriangleStrip,
@@ -79,9 +560,12 @@ impl Pipelines {
                 stencil: Default::default(),
                 bias: Default::default(),
             }),
-            multisample: Default::default(),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
             fragment: Some(wgpu::FragmentState {
-                module: &shaders,
+                module: shaders,
                 entry_point: Some("fs_main"),
                 compilation_options: Default::default(),
                 targets: &[Some(wgpu::ColorTargetState {
@@ -92,108 +576,600 @@ impl Pipelines {
             }),
             multiview: None,
             cache: None,
-        });
-
-        let render_bind_group_layout = render_pipeline.get_bind_group_layout(0);
+        })
+    }
 
-        let instance_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("Noodle instance buffer"),
-            size: (8 * 16 * Self::NUM_SEGMENTS) as u64,
-            usage: wgpu::BufferUsages::STORAGE,
-            mapped_at_creation: false,
-        });
+    /// The pipeline for `advance_trails` if `trails` is set, and for `create_instances` if not.
+    fn create_compute_pipeline(
+        device: &wgpu::Device,
+        compute_shader: &wgpu::ShaderModule,
+        trails: bool,
+    ) -> wgpu::ComputePipeline {
+        let (label, entry_point) = if trails {
+            ("Noodle trail compute pipeline", "advance_trails")
+        } else {
+            ("Noodle instance compute pipeline", "create_instances")
+        };
+        device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some(label),
+            layout: None,
+            module: compute_shader,
+            entry_point: Some(entry_point),
+            compilation_options: Default::default(),
+            cache: None,
+        })
+    }
 
-        let render_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Noodle render bind group"),
-            layout: &render_bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: uniform_buffer.as_entire_binding(),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: instance_buffer.as_entire_binding(),
-                },
-            ],
+    /// Rebuilds the render and compute pipelines from WGSL sources, keeping the old ones if the
+    /// new shaders fail to validate.
+    #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+    pub fn reload_shaders(
+        &mut self,
+        device: &wgpu::Device,
+        tube: &str,
+        instances: &str,
+    ) -> anyhow::Result<()> {
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let shaders = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Noodles vertex shader"),
+            source: wgpu::ShaderSource::Wgsl(tube.into()),
         });
-
-        let cylinder_vertex_buffer = Self::create_cylinder(device);
-
         let compute_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("Noodle instance compute shader"),
-            source: wgpu::ShaderSource::Wgsl(include_wesl!("instances").into()),
+            source: wgpu::ShaderSource::Wgsl(instances.into()),
         });
-
-        let compute_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-            label: Some("Noodle instance compute pipeline"),
-            layout: None,
-            module: &compute_shader,
-            entry_point: Some("create_instances"),
-            compilation_options: Default::default(),
-            cache: None,
+        let render_pipeline = Self::create_render_pipeline(
+            device,
+            &shaders,
+            self.surface_format,
+            self.sample_count,
+            self.compute.is_some(),
+        );
+        // Bind groups are made here too, as automatic layouts change with the shaders and leave
+        // out the bindings they no longer use.
+        let render_bind_group = Self::create_render_bind_group(
+            device,
+            &render_pipeline,
+            &self.uniform_buffer,
+            self.compute
+                .is_some()
+                .then_some(self.drawn_instance_buffer()),
+        );
+        let compute = self.compute.is_some().then(|| {
+            let sdf_volume = self.sdf_volume.create_view(&Default::default());
+            let vector_field = self.vector_field.create_view(&Default::default());
+            Compute::new(
+                device,
+                &compute_shader,
+                &self.compute_bindings(&sdf_volume, &vector_field),
+            )
         });
+        if let Some(error) = pollster::block_on(device.pop_error_scope()) {
+            anyhow::bail!("{error}");
+        }
+        self.render_pipeline = render_pipeline;
+        self.render_bind_group = render_bind_group;
+        if compute.is_some() {
+            self.compute = compute;
+        }
+        Ok(())
+    }
 
-        let compute_bind_group_layout = compute_pipeline.get_bind_group_layout(0);
-
-        let compute_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Noodle compute bind group"),
-            layout: &compute_bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: uniform_buffer.as_entire_binding(),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: instance_buffer.as_entire_binding(),
-                },
-            ],
-        });
+    /// `instance_buffer` is `None` for pipelines that take segments as vertex attributes.
+    fn create_render_bind_group(
+        device: &wgpu::Device,
+        render_pipeline: &wgpu::RenderPipeline,
+        uniform_buffer: &wgpu::Buffer,
+        instance_buffer: Option<&wgpu::Buffer>,
+    ) -> wgpu::BindGroup {
+        let mut entries = vec![wgpu::BindGroupEntry {
+            binding: 0,
+            resource: uniform_buffer.as_entire_binding(),
+        }];
+        if let Some(instance_buffer) = instance_buffer {
+            entries.push(wgpu::BindGroupEntry {
+                binding: 1,
+                resource: instance_buffer.as_entire_binding(),
+            });
+        }
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Noodle render bind group"),
+            layout: &render_pipeline.get_bind_group_layout(0),
+            entries: &entries,
+        })
+    }
 
-        Self {
-            render_pipeline,
-            compute_pipeline,
-            render_bind_group,
-            compute_bind_group,
-            uniform_buffer,
-            cylinder_vertex_buffer,
+    /// Whichever instances are being drawn.
+    fn drawn_instance_buffer(&self) -> &wgpu::Buffer {
+        match &self.uploaded_instances {
+            Some((buffer, _)) => buffer,
+            None => &self.instance_buffer,
         }
     }
 
+    fn rebuild_render_bind_group(&mut self, device: &wgpu::Device) {
+        self.render_bind_group = Self::create_render_bind_group(
+            device,
+            &self.render_pipeline,
+            &self.uniform_buffer,
+            self.compute
+                .is_some()
+                .then_some(self.drawn_instance_buffer()),
+        );
+    }
+
+    fn compute_bindings<'a>(
+        &'a self,
+        sdf_volume: &'a wgpu::TextureView,
+        vector_field: &'a wgpu::TextureView,
+    ) -> ComputeBindings<'a> {
+        ComputeBindings {
+            uniform_buffer: &self.uniform_buffer,
+            instance_buffer: &self.instance_buffer,
+            simulation_buffer: &self.simulation_buffer,
+            seed_buffer: &self.seed_buffer,
+            trail_buffer: &self.trail_buffer,
+            obstacle_buffer: &self.obstacle_buffer,
+            sdf_volume,
+            vector_field,
+            field_buffer: &self.field_buffer,
+        }
+    }
+
+    fn rebuild_compute_bind_groups(&mut self, device: &wgpu::Device) {
+        let Some(compute) = &self.compute else {
+            return;
+        };
+        let sdf_volume = self.sdf_volume.create_view(&Default::default());
+        let vector_field = self.vector_field.create_view(&Default::default());
+        let compute = Compute::bind(
+            device,
+            compute.pipeline.clone(),
+            compute.trail_pipeline.clone(),
+            &self.compute_bindings(&sdf_volume, &vector_field),
+        );
+        self.compute = Some(compute);
+    }
+
+    /// `camera` is the world to clip space transform, and `time` moves the noise.
     pub fn update_uniforms(
-        &self,
+        &mut self,
         queue: &wgpu::Queue,
-        camera_pos: Vec3,
-        camera_target: Vec3,
-        aspect_ratio: f32,
+        camera: Mat4,
+        lighting: &Lighting,
         time: f32,
     ) {
-        let projection = Mat4::perspective_infinite_reverse_rh(PI / 6.0, aspect_ratio, 0.5);
-        let view = Mat4::look_at_rh(camera_pos, camera_target, vec3(0.0, 0.0, 1.0));
+        self.camera = camera;
         let new_uniforms = Uniforms {
-            camera: projection * view,
-            light_direction: vec3(-0.5, -0.2, 1.0).normalize(),
+            camera,
+            light_direction: lighting.direction.normalize_or(Vec3::Z),
             time,
-            ambient: vec3(0.05, 0.05, 0.07),
+            ambient: lighting.ambient,
             _padding_2: 0,
+            light_colour: lighting.colour,
+            _padding_3: 0,
         };
         queue.write_buffer(&self.uniform_buffer, 0, bytes_of(&new_uniforms));
     }
 
-    fn create_cylinder(device: &wgpu::Device) -> wgpu::Buffer {
-        let vertices: [Vertex; Self::VERTICES] = std::array::from_fn(|i| {
-            let side = i / 2;
-            let end = i % 2;
-            let angle = TAU * (side as f32) / (Self::SIDES as f32);
-            Vertex {
-                position: Vec3 {
-                    x: angle.cos(),
-                    y: angle.sin(),
-                    z: end as f32,
-                },
+    /// The world to clip space transform last passed to the shaders.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn camera(&self) -> Mat4 {
+        self.camera
+    }
+
+    /// Uploads the simulation parameters for the frame `seconds` into the demo. In trail mode
+    /// the trails take as many steps as fit in the time since they last moved, so they move at
+    /// the same speed at any frame rate. `growth_progress` is the fraction of their full length
+    /// that strands have grown to in [`GrowthMode::Grow`].
+    pub fn update_simulation(
+        &mut self,
+        queue: &wgpu::Queue,
+        params: &SimulationParams,
+        growth_progress: f32,
+        seconds: f32,
+    ) {
+        self.segments_per_strand = SimulationUniforms::segments_per_strand(params) as usize;
+        self.computed_segments =
+            SimulationUniforms::strands(params) as usize * self.segments_per_strand;
+        self.trails = matches!(params.growth, GrowthMode::Trail { .. });
+        let trail_steps = match (params.growth, self.trail_time) {
+            (GrowthMode::Trail { length }, Some(last)) if seconds >= last => {
+                let length = length.clamp(1, Self::SEGMENTS_PER_STRAND as u32);
+                let steps = ((seconds - last) * Self::TRAIL_STEPS_PER_SECOND) as u32;
+                if steps >= length {
+                    // The whole trail is replaced, so the time left over is dropped.
+                    self.trail_time = Some(seconds);
+                    length
+                } else {
+                    self.trail_time = Some(last + steps as f32 / Self::TRAIL_STEPS_PER_SECOND);
+                    steps
+                }
             }
+            // The first frame after a reset, or after time went backwards, starts the trails.
+            (GrowthMode::Trail { .. }, _) => {
+                self.trail_time = Some(seconds);
+                1
+            }
+            _ => 0,
+        };
+        queue.write_buffer(
+            &self.simulation_buffer,
+            0,
+            bytes_of(&SimulationUniforms::new(
+                params,
+                growth_progress,
+                trail_steps,
+            )),
+        );
+    }
+
+    /// Uploads the obstacles, placed as they are at `time` seconds into their animation.
+    pub fn update_obstacles(&self, queue: &wgpu::Queue, obstacles: &Obstacles, time: f32) {
+        if obstacles.shapes.len() > Self::MAX_OBSTACLES {
+            log::warn!(
+                "Only the first {} of {} obstacles are used",
+                Self::MAX_OBSTACLES,
+                obstacles.shapes.len()
+            );
+        }
+        let (volume_min, volume_max) = self.sdf_bounds.unwrap_or_default();
+        let mut uniforms = ObstacleUniforms {
+            volume_min,
+            count: obstacles.shapes.len().min(Self::MAX_OBSTACLES) as u32,
+            volume_max,
+            volume_enabled: self.sdf_bounds.is_some() as u32,
+            influence: obstacles.influence.max(1e-3),
+            _padding: [0; 3],
+            items: [ObstacleUniform::zeroed(); Self::MAX_OBSTACLES],
+        };
+        for (item, obstacle) in uniforms.items.iter_mut().zip(&obstacles.shapes) {
+            let placement = obstacle.placement_at(time);
+            let (kind, params) = match obstacle.shape {
+                Shape::Sphere { radius } => (0, vec4(radius, 0.0, 0.0, 0.0)),
+                Shape::Box { half_extents } => (1, half_extents.extend(0.0)),
+                Shape::Torus {
+                    major_radius,
+                    minor_radius,
+                } => (2, vec4(major_radius, minor_radius, 0.0, 0.0)),
+                Shape::Capsule {
+                    half_length,
+                    radius,
+                } => (3, vec4(half_length, radius, 0.0, 0.0)),
+            };
+            *item = ObstacleUniform {
+                world_to_local: placement.local_to_world().inverse(),
+                params,
+                kind,
+                scale: placement.scale,
+                _padding: [0; 2],
+            };
+        }
+        queue.write_buffer(&self.obstacle_buffer, 0, bytes_of(&uniforms));
+    }
+
+    /// Replaces the baked signed distance field obstacle, or removes it if `volume` is `None`.
+    /// `bounds` overrides the extent stored in the volume.
+    pub fn set_sdf_volume(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        volume: Option<(&Volume, Vec3, Vec3)>,
+    ) -> anyhow::Result<()> {
+        self.sdf_volume = match volume {
+            Some((volume, min, max)) => {
+                ensure!(
+                    volume.components == 1,
+                    "An SDF volume must have one component"
+                );
+                ensure!(
+                    volume.dimensions.cmpge(UVec3::splat(2)).all(),
+                    "An SDF volume must have at least two samples along each axis"
+                );
+                ensure!(min.cmplt(max).all(), "SDF volume bounds are empty");
+                Self::check_volume_size(device, volume.dimensions)?;
+                self.sdf_bounds = Some((min, max));
+                Self::create_volume_texture(
+                    device,
+                    queue,
+                    "Noodle SDF volume",
+                    wgpu::TextureFormat::R32Float,
+                    volume.dimensions,
+                    bytemuck::cast_slice(&volume.data),
+                )
+            }
+            None => {
+                self.sdf_bounds = None;
+                Self::create_volume_texture(
+                    device,
+                    queue,
+                    "Noodle SDF volume",
+                    wgpu::TextureFormat::R32Float,
+                    UVec3::ONE,
+                    bytes_of(&Self::FAR_AWAY),
+                )
+            }
+        };
+        self.rebuild_compute_bind_groups(device);
+        Ok(())
+    }
+
+    /// Traces strands through an imported vector field, or through the curl noise if `field` is
+    /// `None`.
+    pub fn set_vector_field(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        field: Option<VectorField>,
+    ) -> anyhow::Result<()> {
+        let uniforms = match &field {
+            Some(field) => {
+                ensure!(
+                    field.volume.components >= 3,
+                    "A vector field must have at least three components"
+                );
+                ensure!(
+                    field.min.cmplt(field.max).all(),
+                    "Vector field bounds are empty"
+                );
+                Self::check_volume_size(device, field.volume.dimensions)?;
+                let texels: Vec<Vec4> = field
+                    .volume
+                    .data
+                    .chunks_exact(field.volume.components as usize)
+                    .map(|vector| vec4(vector[0], vector[1], vector[2], 0.0))
+                    .collect();
+                self.vector_field = match field.precision {
+                    Precision::Full => Self::create_volume_texture(
+                        device,
+                        queue,
+                        "Noodle vector field",
+                        wgpu::TextureFormat::Rgba32Float,
+                        field.volume.dimensions,
+                        bytemuck::cast_slice(&texels),
+                    ),
+                    Precision::Half => {
+                        let texels: Vec<f16> = texels
+                            .iter()
+                            .flat_map(|texel| texel.to_array().map(f16::from_f32))
+                            .collect();
+                        Self::create_volume_texture(
+                            device,
+                            queue,
+                            "Noodle vector field",
+                            wgpu::TextureFormat::Rgba16Float,
+                            field.volume.dimensions,
+                            bytemuck::cast_slice(&texels),
+                        )
+                    }
+                };
+                FieldUniforms {
+                    min: field.min,
+                    enabled: 1,
+                    max: field.max,
+                    scale: field.scale,
+                }
+            }
+            None => FieldUniforms::zeroed(),
+        };
+        queue.write_buffer(&self.field_buffer, 0, bytes_of(&uniforms));
+        self.rebuild_compute_bind_groups(device);
+        Ok(())
+    }
+
+    /// Fails for volumes too large for a 3D texture on `device`.
+    fn check_volume_size(device: &wgpu::Device, dimensions: UVec3) -> anyhow::Result<()> {
+        let limit = device.limits().max_texture_dimension_3d;
+        ensure!(
+            dimensions.max_element() <= limit,
+            "Volume of {dimensions} samples exceeds the device's limit of {limit} along each axis"
+        );
+        Ok(())
+    }
+
+    fn create_volume_texture(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        label: &str,
+        format: wgpu::TextureFormat,
+        dimensions: UVec3,
+        data: &[u8],
+    ) -> wgpu::Texture {
+        let size = wgpu::Extent3d {
+            width: dimensions.x,
+            height: dimensions.y,
+            depth_or_array_layers: dimensions.z,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D3,
+            format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let bytes_per_texel = format.block_copy_size(None).unwrap_or(4);
+        queue.write_texture(
+            texture.as_image_copy(),
+            data,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(dimensions.x * bytes_per_texel),
+                rows_per_image: Some(dimensions.y),
+            },
+            size,
+        );
+        texture
+    }
+
+    /// Restarts every trail from its seed on the next [`GrowthMode::Trail`] dispatch.
+    pub fn reset_trails(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        encoder.clear_buffer(&self.trail_buffer, 0, None);
+        self.trail_time = None;
+    }
+
+    /// Sets the starting point of each strand. `seeds` must hold one position per strand.
+    pub fn update_seeds(&mut self, queue: &wgpu::Queue, seeds: &[Vec3]) -> anyhow::Result<()> {
+        ensure!(
+            seeds.len() == Self::NUM_STRANDS,
+            "Expected {} seeds but got {}",
+            Self::NUM_STRANDS,
+            seeds.len()
+        );
+        self.seeds = seeds.to_vec();
+        let seeds: Vec<Vec4> = seeds.iter().map(|seed| seed.extend(0.0)).collect();
+        queue.write_buffer(&self.seed_buffer, 0, bytemuck::cast_slice(&seeds));
+        Ok(())
+    }
+
+    /// The starting point of each strand.
+    pub fn seeds(&self) -> &[Vec3] {
+        &self.seeds
+    }
+
+    /// Whether the compute pass can make the segments. Devices that cannot only draw segments
+    /// uploaded with [`Self::upload_instances`].
+    pub fn computes_instances(&self) -> bool {
+        self.compute.is_some()
+    }
+
+    /// Copies the segments being drawn back to the CPU, strand by strand, blocking until the GPU
+    /// has finished with them.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn read_instances(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> anyhow::Result<Vec<TubeInstance>> {
+        let (instance_buffer, count) = match &self.uploaded_instances {
+            Some((buffer, count)) => (buffer, *count as usize),
+            None => (&self.instance_buffer, self.computed_segments),
+        };
+        let size = (count * std::mem::size_of::<TubeInstance>()) as u64;
+        let bytes = read_back(device, queue, size, |encoder, staging_buffer| {
+            encoder.copy_buffer_to_buffer(instance_buffer, 0, staging_buffer, 0, size);
+        })?;
+        Ok(bytemuck::pod_collect_to_vec(&bytes))
+    }
+
+    /// Draws `instances` from now on instead of the segments made by the compute pass, which
+    /// then need not run. The buffer is reused while the segments still fit in it.
+    pub fn upload_instances(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        instances: &[TubeInstance],
+    ) -> anyhow::Result<()> {
+        ensure!(!instances.is_empty(), "There are no segments to draw");
+        let size = std::mem::size_of_val(instances) as u64;
+        if let Some((buffer, count)) = &mut self.uploaded_instances
+            && buffer.size() >= size
+        {
+            queue.write_buffer(buffer, 0, bytemuck::cast_slice(instances));
+            *count = instances.len() as u32;
+            return Ok(());
+        }
+        let limits = device.limits();
+        let limit = match self.compute {
+            Some(_) => limits.max_storage_buffer_binding_size as u64,
+            None => limits.max_buffer_size,
+        };
+        ensure!(
+            size <= limit,
+            "{} segments need {size} bytes, more than the device's limit of {limit}",
+            instances.len()
+        );
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Noodle uploaded instance buffer"),
+            contents: bytemuck::cast_slice(instances),
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::VERTEX
+                | wgpu::BufferUsages::COPY_SRC
+                | wgpu::BufferUsages::COPY_DST,
         });
+        self.uploaded_instances = Some((buffer, instances.len() as u32));
+        self.rebuild_render_bind_group(device);
+        Ok(())
+    }
+
+    /// Overwrites the uploaded segments from the `first` onwards with `instances`, which must
+    /// not run past the end of those uploaded.
+    pub fn write_uploaded_instances(
+        &self,
+        queue: &wgpu::Queue,
+        first: usize,
+        instances: &[TubeInstance],
+    ) -> anyhow::Result<()> {
+        let Some((buffer, count)) = &self.uploaded_instances else {
+            anyhow::bail!("There are no uploaded segments to overwrite");
+        };
+        ensure!(
+            first + instances.len() <= *count as usize,
+            "Segments {first} to {} are past the {count} uploaded",
+            first + instances.len()
+        );
+        let offset = first * std::mem::size_of::<TubeInstance>();
+        queue.write_buffer(buffer, offset as u64, bytemuck::cast_slice(instances));
+        Ok(())
+    }
+
+    /// Goes back to drawing the segments made by the compute pass.
+    pub fn use_computed_instances(&mut self, device: &wgpu::Device) {
+        if self.uploaded_instances.take().is_some() {
+            self.rebuild_render_bind_group(device);
+        }
+    }
+
+    /// Segments each strand takes up among those made by the compute pass.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn segments_per_strand(&self) -> usize {
+        self.segments_per_strand
+    }
+
+    /// Whether the computed segments of each strand are a trail's ring buffer, so the strand
+    /// may wrap around from its last segment to its first.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn draws_trails(&self) -> bool {
+        self.trails
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn has_uploaded_instances(&self) -> bool {
+        self.uploaded_instances.is_some()
+    }
+
+    /// Draws the tubes with `sides` sides from now on, from 3 upwards.
+    pub fn set_sides(&mut self, device: &wgpu::Device, sides: usize) {
+        let sides = sides.max(3);
+        if sides != self.sides {
+            self.cylinder_vertex_buffer = Self::create_cylinder(device, sides);
+            self.sides = sides;
+        }
+    }
+
+    pub fn sides(&self) -> usize {
+        self.sides
+    }
+
+    /// A triangle strip around an open cylinder of `sides` sides, of radius 1 around Z from 0
+    /// to 1.
+    fn create_cylinder(device: &wgpu::Device, sides: usize) -> wgpu::Buffer {
+        let vertices: Vec<Vertex> = (0..2 * sides + 2)
+            .map(|i| {
+                let side = i / 2;
+                let end = i % 2;
+                let angle = TAU * (side as f32) / (sides as f32);
+                Vertex {
+                    position: Vec3 {
+                        x: angle.cos(),
+                        y: angle.sin(),
+                        z: end as f32,
+                    },
+                }
+            })
+            .collect();
         device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Noodle vertex buffer"),
             contents: bytemuck::cast_slice(&vertices),
@@ -201,35 +1177,46 @@ impl Pipelines {
         })
     }
 
-    fn create_sinusoid_instances(device: &wgpu::Device) -> wgpu::Buffer {
+    /// `strands` sine waves side by side along X, each one period long in Y.
+    pub fn sinusoid_instances(strands: usize) -> Vec<TubeInstance> {
         let spacing = TAU / (Self::SEGMENTS_PER_STRAND as f32);
-        let instances: [TubeInstance; Self::NUM_SEGMENTS] = std::array::from_fn(|i| {
-            let strand = i / Self::SEGMENTS_PER_STRAND;
-            let i = i % Self::SEGMENTS_PER_STRAND;
-            let t = spacing * (i as f32);
-            let t_next = spacing * ((i + 1) as f32);
-
-            TubeInstance {
-                start_position: vec3(strand as f32, t, t.sin()),
-                end_position: vec3(strand as f32, t_next, t_next.sin()),
-                start_bitangent: vec3(-1.0, 0.0, 0.0),
-                end_bitangent: vec3(-1.0, 0.0, 0.0),
-                start_normal: vec3(0.0, -t.cos(), 1.0).normalize(),
-                end_normal: vec3(0.0, -t_next.cos(), 1.0).normalize(),
-                colour: vec3(1.0, 1.0, 1.0),
-                radius: 0.05,
-            }
-        });
-        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Noodle instance buffer"),
-            contents: bytemuck::cast_slice(&instances),
-            usage: wgpu::BufferUsages::VERTEX,
-        })
+        (0..strands * Self::SEGMENTS_PER_STRAND)
+            .map(|i| {
+                let strand = i / Self::SEGMENTS_PER_STRAND;
+                let i = i % Self::SEGMENTS_PER_STRAND;
+                let t = spacing * (i as f32);
+                let t_next = spacing * ((i + 1) as f32);
+
+                TubeInstance {
+                    start_position: vec3(strand as f32, t, t.sin()),
+                    end_position: vec3(strand as f32, t_next, t_next.sin()),
+                    start_bitangent: vec3(-1.0, 0.0, 0.0),
+                    end_bitangent: vec3(-1.0, 0.0, 0.0),
+                    start_normal: vec3(0.0, -t.cos(), 1.0).normalize(),
+                    end_normal: vec3(0.0, -t_next.cos(), 1.0).normalize(),
+                    colour: vec3(1.0, 1.0, 1.0),
+                    radius: 0.05,
+                    ..Default::default()
+                }
+            })
+            .collect()
     }
 
-    pub fn compute_instances(&self, compute_pass: &mut wgpu::ComputePass) {
-        compute_pass.set_pipeline(&self.compute_pipeline);
-        compute_pass.set_bind_group(0, &self.compute_bind_group, &[]);
+    /// Does nothing on devices without compute shaders.
+    pub fn compute_instances(&self, compute_pass: &mut wgpu::ComputePass, growth: GrowthMode) {
+        let Some(compute) = &self.compute else {
+            return;
+        };
+        match growth {
+            GrowthMode::Trail { .. } => {
+                compute_pass.set_pipeline(&compute.trail_pipeline);
+                compute_pass.set_bind_group(0, &compute.trail_bind_group, &[]);
+            }
+            _ => {
+                compute_pass.set_pipeline(&compute.pipeline);
+                compute_pass.set_bind_group(0, &compute.bind_group, &[]);
+            }
+        }
         compute_pass.dispatch_workgroups(
             Self::WORKGROUPS.x,
             Self::WORKGROUPS.y,
@@ -241,7 +1228,291 @@ impl Pipelines {
         render_pass.set_pipeline(&self.render_pipeline);
         render_pass.set_bind_group(0, &self.render_bind_group, &[]);
         render_pass.set_vertex_buffer(0, self.cylinder_vertex_buffer.slice(..));
-        //render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
-        render_pass.draw(0..(Self::VERTICES as u32), 0..(Self::NUM_SEGMENTS as u32))
+        let instance_count = match (&self.uploaded_instances, &self.compute) {
+            (Some((_, count)), Some(_)) => *count,
+            (Some((buffer, count)), None) => {
+                render_pass.set_vertex_buffer(1, buffer.slice(..));
+                *count
+            }
+            (None, Some(_)) => self.computed_segments as u32,
+            // Nothing has been uploaded yet.
+            (None, None) => 0,
+        };
+        render_pass.draw(0..(2 * self.sides as u32 + 2), 0..instance_count)
+    }
+}
+
+/// Runs `copy` into a mappable buffer of `size` bytes and waits until its contents can be read.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn read_back(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    size: u64,
+    copy: impl FnOnce(&mut wgpu::CommandEncoder, &wgpu::Buffer),
+) -> anyhow::Result<Vec<u8>> {
+    let staging_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Noodle readback buffer"),
+        size,
+        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Noodle readback encoder"),
+    });
+    copy(&mut encoder, &staging_buffer);
+    queue.submit([encoder.finish()]);
+
+    let (sender, receiver) = std::sync::mpsc::channel();
+    staging_buffer
+        .slice(..)
+        .map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+    device.poll(wgpu::PollType::wait_indefinitely())?;
+    receiver.recv()??;
+
+    let bytes = staging_buffer.slice(..).get_mapped_range().to_vec();
+    staging_buffer.unmap();
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::mem::{offset_of, size_of};
+
+    /// Checks that the WGSL struct `name` in `source` has its members at the given offsets, as
+    /// naga lays them out, and the size of the Rust struct once rounded up to a multiple of
+    /// `padding`. Every member must be listed.
+    fn assert_layout(
+        source: &str,
+        name: &str,
+        size: usize,
+        padding: usize,
+        offsets: &[(&str, usize)],
+    ) {
+        let module = naga::front::wgsl::parse_str(source).unwrap();
+        let (_, ty) = module
+            .types
+            .iter()
+            // The WESL linker prefixes imported items with their module path.
+            .find(|(_, ty)| {
+                ty.name.as_deref().is_some_and(|ty_name| {
+                    ty_name == name || ty_name.ends_with(&format!("_{name}"))
+                })
+            })
+            .unwrap_or_else(|| panic!("There is no struct {name} in the shader"));
+        let naga::TypeInner::Struct { members, span } = &ty.inner else {
+            panic!("{name} is not a struct");
+        };
+        assert_eq!(
+            (*span as usize).next_multiple_of(padding),
+            size,
+            "Size of {name}"
+        );
+        let members: Vec<(&str, usize)> = members
+            .iter()
+            .map(|member| {
+                let name = member.name.as_deref().unwrap_or_default();
+                (name, member.offset as usize)
+            })
+            .collect();
+        assert_eq!(members, offsets, "Offsets of the members of {name}");
+    }
+
+    // Structs bound as uniform buffers are padded out to 16 bytes on the Rust side, and those in
+    // arrays must match exactly.
+
+    #[test]
+    fn uniforms_match_shader() {
+        assert_layout(
+            include_wesl!("tube"),
+            "Uniforms",
+            size_of::<Uniforms>(),
+            16,
+            &[
+                ("camera", offset_of!(Uniforms, camera)),
+                ("light_direction", offset_of!(Uniforms, light_direction)),
+                ("time", offset_of!(Uniforms, time)),
+                ("ambient", offset_of!(Uniforms, ambient)),
+                ("light_colour", offset_of!(Uniforms, light_colour)),
+            ],
+        );
+    }
+
+    #[test]
+    fn instances_match_shader() {
+        for source in [include_wesl!("tube"), include_wesl!("instances")] {
+            assert_layout(
+                source,
+                "Instance",
+                size_of::<TubeInstance>(),
+                1,
+                &[
+                    ("start_position", offset_of!(TubeInstance, start_position)),
+                    ("start_normal", offset_of!(TubeInstance, start_normal)),
+                    ("start_bitangent", offset_of!(TubeInstance, start_bitangent)),
+                    ("end_position", offset_of!(TubeInstance, end_position)),
+                    ("end_normal", offset_of!(TubeInstance, end_normal)),
+                    ("end_bitangent", offset_of!(TubeInstance, end_bitangent)),
+                    ("colour", offset_of!(TubeInstance, colour)),
+                    ("radius", offset_of!(TubeInstance, radius)),
+                ],
+            );
+        }
+    }
+
+    #[test]
+    fn simulation_matches_shader() {
+        assert_layout(
+            include_wesl!("instances"),
+            "Simulation",
+            size_of::<SimulationUniforms>(),
+            16,
+            &[
+                ("integrator", offset_of!(SimulationUniforms, integrator)),
+                ("step_size", offset_of!(SimulationUniforms, step_size)),
+                ("noise_scale", offset_of!(SimulationUniforms, noise_scale)),
+                ("tolerance", offset_of!(SimulationUniforms, tolerance)),
+                ("growth", offset_of!(SimulationUniforms, growth)),
+                ("trail_length", offset_of!(SimulationUniforms, trail_length)),
+                ("strands", offset_of!(SimulationUniforms, strands)),
+                ("segments", offset_of!(SimulationUniforms, segments)),
+                ("trail_steps", offset_of!(SimulationUniforms, trail_steps)),
+            ],
+        );
+    }
+
+    #[test]
+    fn obstacles_match_shader() {
+        assert_layout(
+            include_wesl!("instances"),
+            "Obstacle",
+            size_of::<ObstacleUniform>(),
+            1,
+            &[
+                (
+                    "world_to_local",
+                    offset_of!(ObstacleUniform, world_to_local),
+                ),
+                ("params", offset_of!(ObstacleUniform, params)),
+                ("kind", offset_of!(ObstacleUniform, kind)),
+                ("scale", offset_of!(ObstacleUniform, scale)),
+            ],
+        );
+        assert_layout(
+            include_wesl!("instances"),
+            "Obstacles",
+            size_of::<ObstacleUniforms>(),
+            16,
+            &[
+                ("volume_min", offset_of!(ObstacleUniforms, volume_min)),
+                ("count", offset_of!(ObstacleUniforms, count)),
+                ("volume_max", offset_of!(ObstacleUniforms, volume_max)),
+                (
+                    "volume_enabled",
+                    offset_of!(ObstacleUniforms, volume_enabled),
+                ),
+                ("influence", offset_of!(ObstacleUniforms, influence)),
+                ("items", offset_of!(ObstacleUniforms, items)),
+            ],
+        );
+    }
+
+    #[test]
+    fn field_matches_shader() {
+        assert_layout(
+            include_wesl!("instances"),
+            "Field",
+            size_of::<FieldUniforms>(),
+            16,
+            &[
+                ("min", offset_of!(FieldUniforms, min)),
+                ("enabled", offset_of!(FieldUniforms, enabled)),
+                ("max", offset_of!(FieldUniforms, max)),
+                ("scale", offset_of!(FieldUniforms, scale)),
+            ],
+        );
+    }
+
+    #[cfg(feature = "hot-reload")]
+    #[test]
+    fn reload_keeps_pipelines_on_errors() {
+        let Some((device, queue)) = crate::golden::fallback_device() else {
+            return;
+        };
+        let format = wgpu::TextureFormat::Rgba8UnormSrgb;
+        let mut pipelines = Pipelines::new(&device, &queue, format, 1);
+        let (tube, instances) = (include_wesl!("tube"), include_wesl!("instances"));
+        pipelines.reload_shaders(&device, tube, instances).unwrap();
+        assert!(pipelines.reload_shaders(&device, tube, "fn").is_err());
+        assert!(pipelines.reload_shaders(&device, "fn", instances).is_err());
+
+        // The pipelines kept from before still compute and draw the strands.
+        let params = SimulationParams::default();
+        let seeds = crate::seeding::SeedStrategy::default()
+            .generate(Pipelines::NUM_STRANDS, 0)
+            .unwrap();
+        pipelines.update_seeds(&queue, &seeds).unwrap();
+        pipelines.update_obstacles(&queue, &Obstacles::default(), 0.0);
+        pipelines.update_uniforms(&queue, Mat4::IDENTITY, &Lighting::default(), 0.0);
+        pipelines.update_simulation(&queue, &params, 1.0, 0.0);
+        let texture = |format, usage| {
+            device
+                .create_texture(&wgpu::TextureDescriptor {
+                    label: None,
+                    size: wgpu::Extent3d {
+                        width: 16,
+                        height: 16,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format,
+                    usage,
+                    view_formats: &[],
+                })
+                .create_view(&Default::default())
+        };
+        let colour = texture(format, wgpu::TextureUsages::RENDER_ATTACHMENT);
+        let depth = texture(
+            wgpu::TextureFormat::Depth32Float,
+            wgpu::TextureUsages::RENDER_ATTACHMENT,
+        );
+        let mut encoder = device.create_command_encoder(&Default::default());
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&Default::default());
+            pipelines.compute_instances(&mut compute_pass, params.growth);
+        }
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: None,
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &colour,
+                    resolve_target: None,
+                    ops: wgpu::Operations::default(),
+                    depth_slice: None,
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &depth,
+                    depth_ops: Some(wgpu::Operations::default()),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            pipelines.render(&mut render_pass);
+        }
+        queue.submit([encoder.finish()]);
+        let computed = pipelines.read_instances(&device, &queue).unwrap();
+        assert_eq!(
+            computed.len(),
+            Pipelines::NUM_STRANDS * Pipelines::SEGMENTS_PER_STRAND
+        );
+        for (strand, seed) in computed.chunks(Pipelines::SEGMENTS_PER_STRAND).zip(&seeds) {
+            assert_eq!(strand[0].start_position, *seed);
+            assert!(strand[0].radius > 0.0);
+        }
     }
 }
@@ -16,15 +16,23 @@ impl Vertex {
     };
 }
 
+/// One segment of a strand, laid out as the `Instance` struct in a WGSL storage buffer, where
+/// each `vec3` is aligned to 16 bytes.
 #[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Zeroable, bytemuck::Pod)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, bytemuck::Zeroable, bytemuck::Pod)]
 pub struct TubeInstance {
     pub start_position: Vec3,
+    pub _padding_0: u32,
     pub start_normal: Vec3,
+    pub _padding_1: u32,
     pub start_bitangent: Vec3,
+    pub _padding_2: u32,
     pub end_position: Vec3,
+    pub _padding_3: u32,
     pub end_normal: Vec3,
+    pub _padding_4: u32,
     pub end_bitangent: Vec3,
+    pub _padding_5: u32,
     pub colour: Vec3,
     pub radius: f32,
 }
@@ -33,15 +41,47 @@ impl TubeInstance {
     pub const LAYOUT: wgpu::VertexBufferLayout<'static> = wgpu::VertexBufferLayout {
         array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
         step_mode: wgpu::VertexStepMode::Instance,
-        attributes: &wgpu::vertex_attr_array![
-            1 => Float32x3,
-            2 => Float32x3,
-            3 => Float32x3,
-            4 => Float32x3,
-            5 => Float32x3,
-            6 => Float32x3,
-            7 => Float32x3,
-            8 => Float32,
+        attributes: &[
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Float32x3,
+                offset: 0,
+                shader_location: 1,
+            },
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Float32x3,
+                offset: 16,
+                shader_location: 2,
+            },
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Float32x3,
+                offset: 32,
+                shader_location: 3,
+            },
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Float32x3,
+                offset: 48,
+                shader_location: 4,
+            },
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Float32x3,
+                offset: 64,
+                shader_location: 5,
+            },
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Float32x3,
+                offset: 80,
+                shader_location: 6,
+            },
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Float32x3,
+                offset: 96,
+                shader_location: 7,
+            },
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Float32,
+                offset: 108,
+                shader_location: 8,
+            },
         ],
     };
 }
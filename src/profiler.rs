@@ -0,0 +1,428 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use web_time::Instant;
+
+/// The passes timed on the GPU, in the order they run in a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pass {
+    /// Tracing the strands into tube instances, unless they come from the CPU.
+    Compute,
+    /// Drawing the tubes.
+    Render,
+    /// Drawing the parameter overlay over them, while it is showing.
+    Overlay,
+}
+
+impl Pass {
+    pub const ALL: [Self; 3] = [Self::Compute, Self::Render, Self::Overlay];
+    const COUNT: usize = Self::ALL.len();
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Compute => "compute",
+            Self::Render => "render",
+            Self::Overlay => "overlay",
+        }
+    }
+}
+
+/// Average milliseconds per frame over the last interval.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Timings {
+    /// From the start of one frame to the start of the next.
+    pub frame: f32,
+    /// Recording and submitting a frame on the CPU.
+    pub cpu: f32,
+    /// Spent in each of [`Pass::ALL`] on the GPU, if the adapter has timestamp queries and the
+    /// pass ran.
+    pub passes: [Option<f32>; Pass::COUNT],
+}
+
+/// Times frames on the CPU and, if the adapter has timestamp queries, each pass on the GPU.
+/// Timestamps are read back asynchronously a frame or more later, and every
+/// [`Self::INTERVAL`] the averages are logged and kept for the overlay.
+pub struct Profiler {
+    timestamps: Option<Timestamps>,
+    /// How often averages are taken, or never if `None`, leaving it to [`Self::finish`].
+    interval: Option<Duration>,
+    /// When the frame being recorded started.
+    frame_start: Option<Instant>,
+    interval_start: Instant,
+    /// Totals over the current interval.
+    frames: u32,
+    frame_total: Duration,
+    cpu_total: Duration,
+    /// Milliseconds and the number of frames that ran each pass.
+    pass_totals: [(f64, u32); Pass::COUNT],
+    timings: Timings,
+}
+
+struct Timestamps {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readbacks: Vec<Readback>,
+    /// Nanoseconds per tick.
+    period: f64,
+    /// Passes given timestamp writes in the frame being recorded.
+    written: [bool; Pass::COUNT],
+    /// The readback that the frame being recorded copies its timestamps to.
+    resolved: Option<usize>,
+}
+
+/// A buffer to read one frame's timestamps back through.
+struct Readback {
+    buffer: wgpu::Buffer,
+    written: [bool; Pass::COUNT],
+    /// Whether the buffer holds a frame's timestamps, or is waiting to.
+    busy: bool,
+    /// Set by the map callback once the buffer can be read.
+    mapped: Arc<Mutex<Option<Result<(), wgpu::BufferAsyncError>>>>,
+}
+
+impl Profiler {
+    pub const INTERVAL: Duration = Duration::from_secs(2);
+    /// Frames in flight beyond this many drop their timestamps rather than waiting.
+    const MAX_READBACKS: usize = 4;
+    const QUERIES: u32 = 2 * Pass::COUNT as u32;
+    const SIZE: u64 = Self::QUERIES as u64 * size_of::<u64>() as u64;
+    /// Bytes of a pass's start and end timestamps.
+    const PASS_SIZE: u64 = 2 * size_of::<u64>() as u64;
+
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let timestamps = device
+            .features()
+            .contains(wgpu::Features::TIMESTAMP_QUERY)
+            .then(|| Timestamps {
+                query_set: device.create_query_set(&wgpu::QuerySetDescriptor {
+                    label: Some("Pass timestamps"),
+                    ty: wgpu::QueryType::Timestamp,
+                    count: Self::QUERIES,
+                }),
+                resolve_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Timestamp resolve buffer"),
+                    size: Pass::COUNT as u64 * wgpu::QUERY_RESOLVE_BUFFER_ALIGNMENT,
+                    usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+                    mapped_at_creation: false,
+                }),
+                readbacks: Vec::new(),
+                period: queue.get_timestamp_period() as f64,
+                written: [false; Pass::COUNT],
+                resolved: None,
+            });
+        if timestamps.is_none() {
+            log::info!("The adapter has no timestamp queries, so only frames are timed");
+        }
+        Self {
+            timestamps,
+            interval: Some(Self::INTERVAL),
+            frame_start: None,
+            interval_start: Instant::now(),
+            frames: 0,
+            frame_total: Duration::ZERO,
+            cpu_total: Duration::ZERO,
+            pass_totals: [(0.0, 0); Pass::COUNT],
+            timings: Timings::default(),
+        }
+    }
+
+    /// Whether passes are timed on the GPU.
+    #[cfg(any(feature = "overlay", not(target_arch = "wasm32")))]
+    pub fn has_timestamps(&self) -> bool {
+        self.timestamps.is_some()
+    }
+
+    /// The averages over the last whole interval.
+    #[cfg(feature = "overlay")]
+    pub fn timings(&self) -> Timings {
+        self.timings
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_interval(&mut self, interval: Option<Duration>) {
+        self.interval = interval;
+    }
+
+    /// Starts timing a frame, taking in the timestamps of earlier frames that have been read
+    /// back since the last.
+    pub fn begin_frame(&mut self, device: &wgpu::Device) {
+        let now = Instant::now();
+        if let Some(start) = self.frame_start {
+            self.frame_total += now - start;
+            self.frames += 1;
+        }
+        self.frame_start = Some(now);
+
+        if let Err(e) = self.collect(device, wgpu::PollType::Poll) {
+            log::warn!("Unable to poll for timestamps, {:#}", e);
+        }
+
+        if let Some(interval) = self.interval
+            && now - self.interval_start >= interval
+            && self.frames > 0
+        {
+            self.finish_interval(now);
+        }
+    }
+
+    /// Waits for the timestamps of every frame so far and returns the averages since the last
+    /// interval, starting a new one. The last frame counts as ending now.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn finish(&mut self, device: &wgpu::Device) -> anyhow::Result<Timings> {
+        self.collect(device, wgpu::PollType::wait_indefinitely())?;
+        let now = Instant::now();
+        if let Some(start) = self.frame_start.take() {
+            self.frame_total += now - start;
+            self.frames += 1;
+        }
+        self.finish_interval(now);
+        Ok(self.timings)
+    }
+
+    /// Adds up the timestamps read back so far, once `poll` has run the map callbacks.
+    fn collect(&mut self, device: &wgpu::Device, poll: wgpu::PollType) -> anyhow::Result<()> {
+        if let Some(timestamps) = &mut self.timestamps {
+            device.poll(poll)?;
+            for readback in &mut timestamps.readbacks {
+                let Some(result) = readback.mapped.lock().unwrap().take() else {
+                    continue;
+                };
+                if result.is_ok() {
+                    let bytes = readback.buffer.slice(..).get_mapped_range();
+                    let ticks: &[u64] = bytemuck::cast_slice(&bytes);
+                    for (i, _) in readback.written.iter().enumerate().filter(|(_, w)| **w) {
+                        let (start, end) = (ticks[2 * i], ticks[2 * i + 1]);
+                        if end >= start {
+                            let total = &mut self.pass_totals[i];
+                            total.0 += (end - start) as f64 * timestamps.period / 1e6;
+                            total.1 += 1;
+                        }
+                    }
+                    drop(bytes);
+                    readback.buffer.unmap();
+                }
+                readback.busy = false;
+            }
+            timestamps.written = [false; Pass::COUNT];
+            timestamps.resolved = None;
+        }
+        Ok(())
+    }
+
+    /// Where `pass` should write its timestamps, if it is timed.
+    pub fn compute_timestamps(
+        &mut self,
+        pass: Pass,
+    ) -> Option<wgpu::ComputePassTimestampWrites<'_>> {
+        let (query_set, start, end) = self.timestamp_writes(pass)?;
+        Some(wgpu::ComputePassTimestampWrites {
+            query_set,
+            beginning_of_pass_write_index: Some(start),
+            end_of_pass_write_index: Some(end),
+        })
+    }
+
+    /// Where `pass` should write its timestamps, if it is timed.
+    pub fn render_timestamps(&mut self, pass: Pass) -> Option<wgpu::RenderPassTimestampWrites<'_>> {
+        let (query_set, start, end) = self.timestamp_writes(pass)?;
+        Some(wgpu::RenderPassTimestampWrites {
+            query_set,
+            beginning_of_pass_write_index: Some(start),
+            end_of_pass_write_index: Some(end),
+        })
+    }
+
+    fn timestamp_writes(&mut self, pass: Pass) -> Option<(&wgpu::QuerySet, u32, u32)> {
+        let timestamps = self.timestamps.as_mut()?;
+        let index = pass as usize;
+        timestamps.written[index] = true;
+        let start = 2 * index as u32;
+        Some((&timestamps.query_set, start, start + 1))
+    }
+
+    /// Copies the timestamps written this frame to a readback buffer, after the last timed
+    /// pass. The frame goes untimed if every buffer is still waiting to be read.
+    pub fn resolve(&mut self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder) {
+        let Some(timestamps) = &mut self.timestamps else {
+            return;
+        };
+        if !timestamps.written.contains(&true) {
+            return;
+        }
+        let free = match timestamps
+            .readbacks
+            .iter()
+            .position(|readback| !readback.busy)
+        {
+            Some(free) => free,
+            None if timestamps.readbacks.len() < Self::MAX_READBACKS => {
+                timestamps.readbacks.push(Readback {
+                    buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                        label: Some("Timestamp readback buffer"),
+                        size: Self::SIZE,
+                        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                        mapped_at_creation: false,
+                    }),
+                    written: [false; Pass::COUNT],
+                    busy: false,
+                    mapped: Arc::new(Mutex::new(None)),
+                });
+                timestamps.readbacks.len() - 1
+            }
+            None => return,
+        };
+        let readback = &mut timestamps.readbacks[free];
+        // Queries that were never written cannot be resolved on some backends, so each pass's
+        // are resolved on their own, at the offsets resolving needs.
+        for (i, _) in timestamps.written.iter().enumerate().filter(|(_, w)| **w) {
+            let start = 2 * i as u32;
+            let offset = i as u64 * wgpu::QUERY_RESOLVE_BUFFER_ALIGNMENT;
+            encoder.resolve_query_set(
+                &timestamps.query_set,
+                start..start + 2,
+                &timestamps.resolve_buffer,
+                offset,
+            );
+            encoder.copy_buffer_to_buffer(
+                &timestamps.resolve_buffer,
+                offset,
+                &readback.buffer,
+                i as u64 * Self::PASS_SIZE,
+                Self::PASS_SIZE,
+            );
+        }
+        readback.written = timestamps.written;
+        readback.busy = true;
+        timestamps.resolved = Some(free);
+    }
+
+    /// Finishes timing the frame once it has been submitted, and starts reading its
+    /// timestamps back.
+    pub fn end_frame(&mut self) {
+        if let Some(start) = self.frame_start {
+            self.cpu_total += start.elapsed();
+        }
+        let Some(timestamps) = &mut self.timestamps else {
+            return;
+        };
+        if let Some(resolved) = timestamps.resolved.take() {
+            let readback = &timestamps.readbacks[resolved];
+            let mapped = readback.mapped.clone();
+            readback
+                .buffer
+                .slice(..)
+                .map_async(wgpu::MapMode::Read, move |result| {
+                    *mapped.lock().unwrap() = Some(result);
+                });
+        }
+    }
+
+    fn finish_interval(&mut self, now: Instant) {
+        let frames = self.frames.max(1) as f32;
+        self.timings = Timings {
+            frame: 1000.0 * self.frame_total.as_secs_f32() / frames,
+            cpu: 1000.0 * self.cpu_total.as_secs_f32() / frames,
+            passes: self
+                .pass_totals
+                .map(|(total, count)| (count > 0).then(|| (total / count as f64) as f32)),
+        };
+        let passes: String = Pass::ALL
+            .iter()
+            .zip(self.timings.passes)
+            .filter_map(|(pass, time)| Some(format!(", {} {:.2} ms", pass.name(), time?)))
+            .collect();
+        log::debug!(
+            "Frame {:.2} ms, CPU {:.2} ms{}",
+            self.timings.frame,
+            self.timings.cpu,
+            passes
+        );
+
+        self.interval_start = now;
+        self.frames = 0;
+        self.frame_total = Duration::ZERO;
+        self.cpu_total = Duration::ZERO;
+        self.pass_totals = [(0.0, 0); Pass::COUNT];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::golden::fallback_device_with_features;
+
+    /// Times a few frames of an empty compute pass and a render pass, without the overlay.
+    fn time_frames(device: &wgpu::Device, queue: &wgpu::Queue) -> (Profiler, Timings) {
+        let mut profiler = Profiler::new(device, queue);
+        profiler.set_interval(None);
+        let target = device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: None,
+                size: wgpu::Extent3d {
+                    width: 4,
+                    height: 4,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8Unorm,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            })
+            .create_view(&Default::default());
+        for _ in 0..3 {
+            profiler.begin_frame(device);
+            let mut encoder = device.create_command_encoder(&Default::default());
+            encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: None,
+                timestamp_writes: profiler.compute_timestamps(Pass::Compute),
+            });
+            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: None,
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &target,
+                    resolve_target: None,
+                    ops: wgpu::Operations::default(),
+                    depth_slice: None,
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: profiler.render_timestamps(Pass::Render),
+                occlusion_query_set: None,
+            });
+            profiler.resolve(device, &mut encoder);
+            queue.submit([encoder.finish()]);
+            profiler.end_frame();
+        }
+        let timings = profiler.finish(device).unwrap();
+        assert!(timings.frame > 0.0 && timings.frame.is_finite());
+        assert!(timings.cpu > 0.0 && timings.cpu <= timings.frame);
+        (profiler, timings)
+    }
+
+    #[test]
+    fn passes_are_timed_with_timestamps() {
+        let Some((device, queue)) = fallback_device_with_features(wgpu::Features::TIMESTAMP_QUERY)
+        else {
+            return;
+        };
+        let (profiler, timings) = time_frames(&device, &queue);
+        if !profiler.has_timestamps() {
+            eprintln!("Skipping, as the fallback adapter has no timestamp queries");
+            return;
+        }
+        let [compute, render, overlay] = timings.passes;
+        assert!(compute.is_some_and(|time| time >= 0.0 && time.is_finite()));
+        assert!(render.is_some_and(|time| time >= 0.0 && time.is_finite()));
+        assert_eq!(overlay, None);
+    }
+
+    #[test]
+    fn only_frames_are_timed_without_timestamps() {
+        let Some((device, queue)) = crate::golden::fallback_device() else {
+            return;
+        };
+        let (profiler, timings) = time_frames(&device, &queue);
+        assert!(!profiler.has_timestamps());
+        assert_eq!(timings.passes, [None; Pass::COUNT]);
+    }
+}
@@ -0,0 +1,94 @@
+use serde::{Deserialize, Serialize};
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::pipelines::SimulationParams;
+use crate::pipelines::{Integrator, Pipelines};
+
+/// Trade-offs between looks and frame rate, each setting how many strands there are, how finely
+/// they are traced and how round their tubes are.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Quality {
+    Low,
+    Medium,
+    #[default]
+    High,
+    Ultra,
+}
+
+impl Quality {
+    pub const ALL: [Self; 4] = [Self::Low, Self::Medium, Self::High, Self::Ultra];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Low => "low",
+            Self::Medium => "medium",
+            Self::High => "high",
+            Self::Ultra => "ultra",
+        }
+    }
+
+    pub fn integrator(self) -> Integrator {
+        match self {
+            Self::Low => Integrator::Euler,
+            Self::Medium => Integrator::Midpoint,
+            Self::High => Integrator::Rk4,
+            Self::Ultra => Integrator::Rk45,
+        }
+    }
+
+    pub fn strands(self) -> u32 {
+        let all = Pipelines::NUM_STRANDS as u32;
+        match self {
+            Self::Low => all / 4,
+            Self::Medium => all / 2,
+            Self::High | Self::Ultra => all,
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn segments(self) -> u32 {
+        let all = Pipelines::SEGMENTS_PER_STRAND as u32;
+        match self {
+            Self::Low => all / 2,
+            Self::Medium => all * 3 / 4,
+            Self::High | Self::Ultra => all,
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn sides(self) -> usize {
+        match self {
+            Self::Low => 4,
+            Self::Medium => 6,
+            Self::High => Pipelines::DEFAULT_SIDES,
+            Self::Ultra => 12,
+        }
+    }
+
+    /// Sets the integrator and the number of strands and segments. Segments are lengthened to
+    /// match, so strands are as long as before.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn apply(self, simulation: &mut SimulationParams) {
+        simulation.integrator = self.integrator();
+        simulation.strands = self.strands();
+        simulation.step_size *= simulation.segments.max(1) as f32 / self.segments() as f32;
+        simulation.segments = self.segments();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_keep_strand_length() {
+        let mut simulation = SimulationParams::default();
+        let length = simulation.step_size * simulation.segments as f32;
+        for quality in Quality::ALL {
+            quality.apply(&mut simulation);
+            assert_eq!(simulation.segments, quality.segments());
+            assert!((simulation.step_size * simulation.segments as f32 - length).abs() < 1e-5);
+        }
+    }
+}
@@ -0,0 +1,395 @@
+use anyhow::{Context, anyhow};
+use glam::Vec3;
+#[cfg(target_arch = "wasm32")]
+use web_sys::UrlSearchParams;
+
+#[cfg(target_arch = "wasm32")]
+use crate::State;
+use crate::pipelines::{Integrator, Pipelines};
+use crate::quality::Quality;
+use crate::view::CameraMode;
+
+/// The settings carried by the query string of a link to the demo, such as
+/// `?seed=3&palette=ffd8a8,0d0d12,101018&speed=0.5&strands=512&camera=still&quality=low`.
+/// Settings left out of the link keep their defaults.
+#[derive(Debug, PartialEq)]
+pub struct Settings {
+    pub seed: Option<u64>,
+    /// Light, ambient and background colours, in linear RGB.
+    pub palette: Option<[Vec3; 3]>,
+    pub speed: Option<f32>,
+    /// The preset the link was made with. Of it, only the integrator and strand count are
+    /// used, and they give way to ones given on their own.
+    pub quality: Option<Quality>,
+    pub strands: Option<u32>,
+    pub integrator: Option<Integrator>,
+    pub camera: Option<CameraMode>,
+}
+
+impl Settings {
+    /// Every key a link can have.
+    #[cfg(target_arch = "wasm32")]
+    const KEYS: [&str; 7] = [
+        "seed",
+        "palette",
+        "speed",
+        "quality",
+        "strands",
+        "integrator",
+        "camera",
+    ];
+
+    /// Reads the settings from the keys and values of a query string. Where a key appears
+    /// more than once, the first is used.
+    pub fn parse(pairs: &[(String, String)]) -> anyhow::Result<Self> {
+        let get = |key: &str| {
+            pairs
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value.clone())
+        };
+
+        let quality = get("quality")
+            .map(|value| {
+                Quality::ALL
+                    .into_iter()
+                    .find(|quality| quality.name() == value)
+                    .with_context(|| format!("quality={value} is not low, medium, high or ultra"))
+            })
+            .transpose()?;
+        let integrator = get("integrator")
+            .map(|value| {
+                Integrator::ALL
+                    .into_iter()
+                    .find(|integrator| integrator_name(*integrator) == value)
+                    .with_context(|| {
+                        format!("integrator={value} is not euler, midpoint, rk4 or rk45")
+                    })
+            })
+            .transpose()?;
+        let strands = get("strands")
+            .map(|value| {
+                value
+                    .parse::<u32>()
+                    .map(|strands| strands.clamp(1, Pipelines::NUM_STRANDS as u32))
+                    .with_context(|| format!("strands={value} is not a whole number"))
+            })
+            .transpose()?;
+
+        Ok(Self {
+            seed: get("seed")
+                .map(|value| {
+                    value
+                        .parse()
+                        .with_context(|| format!("seed={value} is not a whole number"))
+                })
+                .transpose()?,
+            palette: get("palette")
+                .map(|value| parse_palette(&value))
+                .transpose()?,
+            speed: get("speed")
+                .map(|value| match value.parse::<f32>() {
+                    Ok(speed) if speed.is_finite() => Ok(speed),
+                    _ => Err(anyhow!("speed={value} is not a finite number")),
+                })
+                .transpose()?,
+            quality,
+            strands: strands.or(quality.map(Quality::strands)),
+            integrator: integrator.or(quality.map(Quality::integrator)),
+            camera: get("camera")
+                .map(|value| match value.as_str() {
+                    "orbit" => Ok(CameraMode::Orbit),
+                    "still" => Ok(CameraMode::Still),
+                    _ => Err(anyhow!("camera={value} is not orbit or still")),
+                })
+                .transpose()?,
+        })
+    }
+
+    /// Reads the settings from a query string.
+    #[cfg(target_arch = "wasm32")]
+    pub fn parse_query(query: &str) -> anyhow::Result<Self> {
+        let params = UrlSearchParams::new_with_str(query)
+            .map_err(|e| anyhow!("{e:?}"))
+            .context("Unable to read the link")?;
+        let pairs: Vec<(String, String)> = Self::KEYS
+            .into_iter()
+            .filter_map(|key| params.get(key).map(|value| (key.to_string(), value)))
+            .collect();
+        Self::parse(&pairs)
+    }
+
+    /// Everything a link needs to show what `state` is showing.
+    #[cfg(target_arch = "wasm32")]
+    pub fn of(state: &State) -> Self {
+        let lighting = &state.scene.lighting;
+        Self {
+            seed: Some(state.scene.random_seed),
+            palette: Some([lighting.colour, lighting.ambient, lighting.background]),
+            speed: Some(state.clock.speed),
+            quality: state.quality,
+            strands: Some(state.scene.simulation.strands),
+            integrator: Some(state.scene.simulation.integrator),
+            camera: Some(state.scene.camera.mode),
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn apply(self, state: &mut State) {
+        let scene = &mut state.scene;
+        if let Some([light, ambient, background]) = self.palette {
+            scene.lighting.colour = light;
+            scene.lighting.ambient = ambient;
+            scene.lighting.background = background;
+        }
+        if let Some(quality) = self.quality {
+            state.quality = Some(quality);
+        }
+        if let Some(strands) = self.strands {
+            scene.simulation.strands = strands;
+        }
+        if let Some(integrator) = self.integrator {
+            scene.simulation.integrator = integrator;
+        }
+        if let Some(camera) = self.camera {
+            scene.camera.mode = camera;
+        }
+        if let Some(speed) = self.speed {
+            state.clock.speed = speed;
+        }
+        if let Some(seed) = self.seed
+            && seed != state.scene.random_seed
+        {
+            state.scene.random_seed = seed;
+            state.set_seeding(state.scene.seeding.clone());
+        }
+    }
+
+    /// The query string for these settings, starting with `?`. None of the values need
+    /// escaping.
+    pub fn to_query(&self) -> String {
+        let mut pairs = Vec::new();
+        if let Some(seed) = self.seed {
+            pairs.push(("seed", seed.to_string()));
+        }
+        if let Some(palette) = self.palette {
+            let colours: Vec<_> = palette
+                .iter()
+                .map(|colour| format_colour(*colour))
+                .collect();
+            pairs.push(("palette", colours.join(",")));
+        }
+        if let Some(speed) = self.speed {
+            pairs.push(("speed", speed.to_string()));
+        }
+        if let Some(quality) = self.quality {
+            pairs.push(("quality", quality.name().to_string()));
+        }
+        if let Some(strands) = self.strands {
+            pairs.push(("strands", strands.to_string()));
+        }
+        if let Some(integrator) = self.integrator {
+            pairs.push(("integrator", integrator_name(integrator).to_string()));
+        }
+        if let Some(camera) = self.camera {
+            let camera = match camera {
+                CameraMode::Orbit => "orbit",
+                CameraMode::Still => "still",
+            };
+            pairs.push(("camera", camera.to_string()));
+        }
+        let pairs: Vec<String> = pairs
+            .iter()
+            .map(|(key, value)| format!("{key}={value}"))
+            .collect();
+        format!("?{}", pairs.join("&"))
+    }
+}
+
+/// The settings in the address of the page.
+#[cfg(target_arch = "wasm32")]
+pub fn from_location() -> anyhow::Result<Settings> {
+    let search = web_sys::window()
+        .context("There is no window")?
+        .location()
+        .search()
+        .map_err(|e| anyhow!("{e:?}"))?;
+    Settings::parse_query(&search)
+}
+
+/// A link to the page showing `settings`.
+#[cfg(target_arch = "wasm32")]
+pub fn link(settings: &Settings) -> anyhow::Result<String> {
+    let location = web_sys::window().context("There is no window")?.location();
+    let base = location.href().map_err(|e| anyhow!("{e:?}"))?;
+    let base = base.split(['?', '#']).next().unwrap_or_default();
+    Ok(format!("{base}{}", settings.to_query()))
+}
+
+/// Copies `text` to the clipboard, logging the outcome as the browser reports it.
+#[cfg(target_arch = "wasm32")]
+pub fn copy(text: String) {
+    let Some(window) = web_sys::window() else {
+        return;
+    };
+    let promise = window.navigator().clipboard().write_text(&text);
+    wasm_bindgen_futures::spawn_local(async move {
+        match wasm_bindgen_futures::JsFuture::from(promise).await {
+            Ok(_) => log::info!("Copied link {}", text),
+            Err(e) => log::error!("Unable to copy link {}, {:?}", text, e),
+        }
+    });
+}
+
+fn integrator_name(integrator: Integrator) -> &'static str {
+    match integrator {
+        Integrator::Euler => "euler",
+        Integrator::Midpoint => "midpoint",
+        Integrator::Rk4 => "rk4",
+        Integrator::Rk45 => "rk45",
+    }
+}
+
+/// Three comma separated hex colours, with or without a leading `#`.
+fn parse_palette(text: &str) -> anyhow::Result<[Vec3; 3]> {
+    let colours = text
+        .split(',')
+        .map(|colour| parse_colour(&format!("#{}", colour.trim_start_matches('#'))))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    colours
+        .try_into()
+        .map_err(|_| anyhow!("palette={text} is not three colours: light, ambient and background"))
+}
+
+/// Parses a CSS hex colour, `#rgb` or `#rrggbb`, into linear RGB.
+pub fn parse_colour(text: &str) -> anyhow::Result<Vec3> {
+    let invalid = || anyhow!("{text} is not a hex colour such as #ff8800");
+    let digits = text.strip_prefix('#').ok_or_else(invalid)?;
+    let digits: Vec<u8> = match digits.len() {
+        3 => digits.bytes().flat_map(|digit| [digit, digit]).collect(),
+        6 => digits.bytes().collect(),
+        _ => return Err(invalid()),
+    };
+    let mut channels = [0.0; 3];
+    for (channel, pair) in channels.iter_mut().zip(digits.chunks(2)) {
+        let pair = std::str::from_utf8(pair).map_err(|_| invalid())?;
+        let value = u8::from_str_radix(pair, 16).map_err(|_| invalid())? as f32 / 255.0;
+        // sRGB transfer function
+        *channel = if value <= 0.04045 {
+            value / 12.92
+        } else {
+            ((value + 0.055) / 1.055).powf(2.4)
+        };
+    }
+    Ok(Vec3::from_array(channels))
+}
+
+/// Hex digits of a linear RGB colour in sRGB, without the `#`.
+fn format_colour(colour: Vec3) -> String {
+    colour
+        .to_array()
+        .map(|channel| {
+            let channel = channel.clamp(0.0, 1.0);
+            // sRGB transfer function
+            let encoded = if channel <= 0.0031308 {
+                channel * 12.92
+            } else {
+                1.055 * channel.powf(1.0 / 2.4) - 0.055
+            };
+            format!("{:02x}", (encoded * 255.0).round() as u8)
+        })
+        .concat()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(query: &str) -> Vec<(String, String)> {
+        query
+            .trim_start_matches('?')
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn links_round_trip() {
+        let settings = Settings {
+            seed: Some(3),
+            palette: Some(
+                ["ffd8a8", "0d0d12", "101018"]
+                    .map(|colour| parse_colour(&format!("#{colour}")).unwrap()),
+            ),
+            speed: Some(0.5),
+            quality: Some(Quality::Low),
+            strands: Some(512),
+            integrator: Some(Integrator::Rk45),
+            camera: Some(CameraMode::Still),
+        };
+        let query = settings.to_query();
+        assert_eq!(
+            query,
+            "?seed=3&palette=ffd8a8,0d0d12,101018&speed=0.5&quality=low&strands=512\
+             &integrator=rk45&camera=still"
+        );
+        assert_eq!(Settings::parse(&pairs(&query)).unwrap(), settings);
+        assert_eq!(
+            Settings::parse(&[]).unwrap().to_query(),
+            "?",
+            "Settings left out stay out"
+        );
+    }
+
+    #[test]
+    fn quality_gives_way_to_its_parts() {
+        let settings = Settings::parse(&pairs("?quality=low&integrator=rk4")).unwrap();
+        assert_eq!(settings.quality, Some(Quality::Low));
+        assert_eq!(settings.strands, Some(Quality::Low.strands()));
+        assert_eq!(settings.integrator, Some(Integrator::Rk4));
+
+        let settings = Settings::parse(&pairs("?strands=2048")).unwrap();
+        assert_eq!(settings.strands, Some(Pipelines::NUM_STRANDS as u32));
+    }
+
+    #[test]
+    fn colours_round_trip() {
+        for hex in ["000000", "ffffff", "ffd8a8", "0d0d12", "808080"] {
+            assert_eq!(
+                format_colour(parse_colour(&format!("#{hex}")).unwrap()),
+                hex
+            );
+        }
+        assert_eq!(
+            parse_colour("#f80").unwrap(),
+            parse_colour("#ff8800").unwrap()
+        );
+        assert!(parse_colour("ff8800").is_err());
+        assert!(parse_colour("#ff880").is_err());
+        assert!(parse_colour("#gg8800").is_err());
+    }
+
+    #[test]
+    fn bad_values_are_rejected() {
+        for query in [
+            "?camera=spin",
+            "?quality=extreme",
+            "?integrator=rk5",
+            "?speed=inf",
+            "?speed=NaN",
+            "?speed=fast",
+            "?strands=-1",
+            "?seed=x",
+            "?palette=ffd8a8,0d0d12",
+        ] {
+            let error = Settings::parse(&pairs(query)).unwrap_err();
+            assert!(
+                error
+                    .to_string()
+                    .starts_with(&query[1..query.find('=').unwrap() + 1]),
+                "{query} gave {error}"
+            );
+        }
+    }
+}
@@ -0,0 +1,327 @@
+mod noise;
+
+use glam::{Vec3, vec3};
+
+use self::noise::{Frame, curl_noise};
+use crate::pipelines::{GrowthMode, Integrator, Pipelines, SimulationParams, TubeInstance};
+
+const TUBE_RADIUS: f32 = 0.01;
+/// Hard cap on accepted integration steps. Steps are at least `min_step`, a hundredth of a
+/// segment, so this covers a whole strand of the shortest steps twice over, leaving room for
+/// chords shortened by tight curls.
+const MAX_STEPS: usize = Pipelines::SEGMENTS_PER_STRAND * 200;
+/// Steps the adaptive integrator may reject in a strand, counted apart from the accepted ones.
+const MAX_REJECTIONS: usize = Pipelines::SEGMENTS_PER_STRAND * 16;
+/// Largest change in direction the adaptive integrator may take in a single step, in radians.
+const MAX_TURN: f32 = 0.1;
+
+/// A port of the `create_instances` compute shader to the CPU, for checking it against and for
+/// devices without compute shaders. Only the curl noise is followed: obstacles and imported
+/// vector fields are left out.
+pub struct Tracer {
+    params: SimulationParams,
+    /// Fraction of their full length that strands have grown to.
+    growth: f32,
+    /// The time passed to the shaders, which moves the noise.
+    time: f32,
+}
+
+struct AdaptiveStep {
+    position: Vec3,
+    /// Estimated local truncation error of the fifth order solution.
+    error: f32,
+    /// Change in direction across the step per unit arc length.
+    curvature: f32,
+}
+
+impl Tracer {
+    /// Takes the parameters and growth given to [`Pipelines::update_simulation`], and the time
+    /// given to [`Pipelines::update_uniforms`].
+    pub fn new(params: SimulationParams, growth_progress: f32, time: f32) -> Self {
+        let growth = match params.growth {
+            GrowthMode::Grow { .. } => growth_progress.clamp(0.0, 1.0),
+            _ => 1.0,
+        };
+        Self {
+            params,
+            growth,
+            time,
+        }
+    }
+
+    pub fn field_frame(&self, position: Vec3) -> Frame {
+        let offset_1 = vec3(100.0, self.time, 100.0);
+        let offset_2 = vec3(-100.0, self.time, -150.0);
+        curl_noise(position, offset_1, offset_2, self.params.noise_scale)
+    }
+
+    fn velocity(&self, position: Vec3) -> Vec3 {
+        self.field_frame(position).tangent
+    }
+
+    fn midpoint_step(&self, y: Vec3, h: f32) -> Vec3 {
+        let k1 = self.velocity(y);
+        let k2 = self.velocity(y + 0.5 * h * k1);
+        y + h * k2
+    }
+
+    fn rk4_step(&self, y: Vec3, h: f32) -> Vec3 {
+        let k1 = self.velocity(y);
+        let k2 = self.velocity(y + 0.5 * h * k1);
+        let k3 = self.velocity(y + 0.5 * h * k2);
+        let k4 = self.velocity(y + h * k3);
+        y + h / 6.0 * (k1 + 2.0 * k2 + 2.0 * k3 + k4)
+    }
+
+    fn fixed_step(&self, y: Vec3, h: f32) -> Vec3 {
+        match self.params.integrator {
+            Integrator::Euler => y + h * self.velocity(y),
+            Integrator::Midpoint => self.midpoint_step(y, h),
+            Integrator::Rk4 => self.rk4_step(y, h),
+            Integrator::Rk45 => self.rk45_step(y, h).position,
+        }
+    }
+
+    /// Dormand-Prince embedded 5(4) pair.
+    fn rk45_step(&self, y: Vec3, h: f32) -> AdaptiveStep {
+        let k1 = self.velocity(y);
+        let k2 = self.velocity(y + h * (1.0 / 5.0) * k1);
+        let k3 = self.velocity(y + h * ((3.0 / 40.0) * k1 + (9.0 / 40.0) * k2));
+        let k4 =
+            self.velocity(y + h * ((44.0 / 45.0) * k1 - (56.0 / 15.0) * k2 + (32.0 / 9.0) * k3));
+        let k5 = self.velocity(
+            y + h
+                * ((19372.0 / 6561.0) * k1 - (25360.0 / 2187.0) * k2 + (64448.0 / 6561.0) * k3
+                    - (212.0 / 729.0) * k4),
+        );
+        let k6 = self.velocity(
+            y + h
+                * ((9017.0 / 3168.0) * k1 - (355.0 / 33.0) * k2
+                    + (46732.0 / 5247.0) * k3
+                    + (49.0 / 176.0) * k4
+                    - (5103.0 / 18656.0) * k5),
+        );
+        let y5 = y + h
+            * ((35.0 / 384.0) * k1 + (500.0 / 1113.0) * k3 + (125.0 / 192.0) * k4
+                - (2187.0 / 6784.0) * k5
+                + (11.0 / 84.0) * k6);
+        let k7 = self.velocity(y5);
+        let y4 = y + h
+            * ((5179.0 / 57600.0) * k1 + (7571.0 / 16695.0) * k3 + (393.0 / 640.0) * k4
+                - (92097.0 / 339200.0) * k5
+                + (187.0 / 2100.0) * k6
+                + (1.0 / 40.0) * k7);
+        AdaptiveStep {
+            position: y5,
+            error: (y5 - y4).length(),
+            curvature: (k7 - k1).length() / (y5 - y).length().max(1e-6),
+        }
+    }
+
+    /// The `segments` segments of the strand starting at `seed`, laid out as the compute shader
+    /// writes them, with those past where the strand has grown or stalled collapsed.
+    pub fn trace(&self, seed: Vec3, colour: Vec3) -> Vec<TubeInstance> {
+        let segments = self
+            .params
+            .segments
+            .clamp(1, Pipelines::SEGMENTS_PER_STRAND as u32);
+        // Every segment has the same arc length regardless of the integrator, so the strand is
+        // resampled from the integrated path as it is traced.
+        let segment_length = self.params.step_size;
+        let min_step = 0.01 * segment_length;
+        // Strands that are still growing stop part way through a segment.
+        let visible_segments = self.growth * segments as f32;
+
+        let mut instances = Vec::with_capacity(segments as usize);
+        let mut end_position = seed;
+        let frame = self.field_frame(end_position);
+        let mut end_normal = frame.normal;
+        let mut end_binormal = frame.binormal;
+
+        let mut previous = end_position;
+        let mut travelled = 0.0;
+        let mut h = segment_length;
+        let (mut steps, mut rejections) = (0, 0);
+
+        while steps < MAX_STEPS
+            && rejections < MAX_REJECTIONS
+            && (instances.len() as f32) < visible_segments
+        {
+            let next = if self.params.integrator == Integrator::Rk45 {
+                let result = self.rk45_step(previous, h);
+                let error_scale = self.params.tolerance / result.error.max(1e-12);
+                let h_error = h * (0.9 * error_scale.powf(0.2)).clamp(0.2, 5.0);
+                let h_curvature = MAX_TURN / result.curvature.max(1e-6);
+                let h_next = h_error.min(h_curvature).clamp(min_step, segment_length);
+                // A step that is too inaccurate is retried from the same point with the
+                // smaller step.
+                let rejected = result.error > self.params.tolerance && h > min_step;
+                h = h_next;
+                if rejected {
+                    rejections += 1;
+                    continue;
+                }
+                result.position
+            } else {
+                self.fixed_step(previous, h)
+            };
+            steps += 1;
+
+            let chord = (next - previous).length();
+            // A strand stuck in a null of the field stops rather than stepping in place.
+            if chord <= 1e-6 * segment_length {
+                break;
+            }
+            loop {
+                let segment = instances.len() as f32;
+                let target_length = (segment + 1.0) * segment_length;
+                if segment >= visible_segments || travelled + chord < target_length * (1.0 - 1e-4) {
+                    break;
+                }
+                let start_position = end_position;
+                let start_normal = end_normal;
+                let start_binormal = end_binormal;
+                end_position = previous.lerp(
+                    next,
+                    ((target_length - travelled) / chord.max(1e-12)).clamp(0.0, 1.0),
+                );
+                end_position =
+                    start_position.lerp(end_position, (visible_segments - segment).min(1.0));
+                let frame = self.field_frame(end_position);
+                end_normal = frame.normal;
+                end_binormal = frame.binormal;
+
+                instances.push(TubeInstance {
+                    start_position,
+                    start_normal,
+                    start_bitangent: start_binormal,
+                    end_position,
+                    end_normal,
+                    end_bitangent: end_binormal,
+                    colour,
+                    radius: TUBE_RADIUS,
+                    ..Default::default()
+                });
+            }
+            travelled += chord;
+            previous = next;
+        }
+
+        // Segments of a strand that stalled or has not grown yet collapse onto its last point.
+        instances.resize(
+            segments as usize,
+            TubeInstance {
+                start_position: end_position,
+                start_normal: end_normal,
+                start_bitangent: end_binormal,
+                end_position,
+                end_normal,
+                end_bitangent: end_binormal,
+                colour,
+                radius: 0.0,
+                ..Default::default()
+            },
+        );
+        instances
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::golden::fallback_device;
+    use crate::obstacles::Obstacles;
+    use crate::seeding::SeedStrategy;
+    use crate::view::Lighting;
+    use glam::Mat4;
+
+    #[test]
+    fn curled_strands_reach_full_length() {
+        // Fine noise turns the strands faster than the adaptive integrator may turn in a step,
+        // so it takes many short steps per segment.
+        let params = SimulationParams {
+            integrator: Integrator::Rk45,
+            noise_scale: 20.0,
+            tolerance: 1e-6,
+            ..Default::default()
+        };
+        let tracer = Tracer::new(params, 1.0, 0.0);
+        for seed in [Vec3::ZERO, vec3(0.3, -0.2, 0.1), vec3(-0.5, 0.5, 0.25)] {
+            let instances = tracer.trace(seed, Vec3::ONE);
+            assert_eq!(instances.len(), Pipelines::SEGMENTS_PER_STRAND);
+            assert!(
+                instances
+                    .iter()
+                    .all(|instance| instance.radius == TUBE_RADIUS)
+            );
+        }
+    }
+
+    #[test]
+    fn compute_shader_matches_tracer() {
+        let Some((device, queue)) = fallback_device() else {
+            return;
+        };
+        let mut pipelines = Pipelines::new(&device, &queue, wgpu::TextureFormat::Rgba8UnormSrgb, 1);
+        let seeds = SeedStrategy::default()
+            .generate(Pipelines::NUM_STRANDS, 0)
+            .unwrap();
+        pipelines.update_seeds(&queue, &seeds).unwrap();
+        pipelines.update_obstacles(&queue, &Obstacles::default(), 0.0);
+        let time = 0.3;
+        pipelines.update_uniforms(&queue, Mat4::IDENTITY, &Lighting::default(), time);
+
+        for integrator in Integrator::ALL {
+            // Fewer segments than the most a strand can have, so strands are packed closer.
+            let params = SimulationParams {
+                integrator,
+                segments: 48,
+                ..Default::default()
+            };
+            pipelines.update_simulation(&queue, &params, 1.0, time);
+            let mut encoder = device.create_command_encoder(&Default::default());
+            {
+                let mut compute_pass = encoder.begin_compute_pass(&Default::default());
+                pipelines.compute_instances(&mut compute_pass, params.growth);
+            }
+            queue.submit([encoder.finish()]);
+            let computed = pipelines.read_instances(&device, &queue).unwrap();
+
+            let tracer = Tracer::new(params, 1.0, time);
+            let (mut position_error, mut normal_error) = (0.0f32, 0.0f32);
+            for (strand, seed) in seeds.iter().enumerate() {
+                let colour = Vec3::splat(strand as f32 / Pipelines::NUM_STRANDS as f32);
+                let expected = tracer.trace(*seed, colour);
+                let segments = pipelines.segments_per_strand();
+                assert_eq!(expected.len(), segments);
+                let actual = &computed[strand * segments..(strand + 1) * segments];
+                for (expected, actual) in expected.iter().zip(actual) {
+                    position_error = position_error
+                        .max(expected.start_position.distance(actual.start_position))
+                        .max(expected.end_position.distance(actual.end_position));
+                    normal_error = normal_error
+                        .max(expected.end_normal.distance(actual.end_normal))
+                        .max(expected.end_bitangent.distance(actual.end_bitangent));
+                    assert_eq!(expected.colour, actual.colour);
+                    assert_eq!(expected.radius, actual.radius);
+                }
+            }
+            // The adaptive integrator can accept a step on one side and reject it on the other,
+            // after which the two take slightly different paths.
+            let position_tolerance = match integrator {
+                Integrator::Rk45 => 1e-2,
+                _ => 1e-3,
+            };
+            assert!(
+                position_error < position_tolerance,
+                "{integrator:?} strands differ by up to {position_error}"
+            );
+            // Normals are taken from the tangent's cross product with Y, so they amplify any
+            // difference where a strand runs nearly parallel to Y.
+            assert!(
+                normal_error < 2e-2,
+                "{integrator:?} normals differ by up to {normal_error}"
+            );
+        }
+    }
+}
@@ -0,0 +1,201 @@
+use glam::{IVec3, UVec3, Vec3, Vec3Swizzles, Vec4, Vec4Swizzles, uvec3, vec3, vec4};
+
+/// Orthonormal frame of a strand, matching `Frame` in `noise.wgsl`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frame {
+    pub normal: Vec3,
+    pub binormal: Vec3,
+    pub tangent: Vec3,
+}
+
+/// Three pseudo-random numbers in [0, 1] from the `pcg3d` hash of Jarzynski and Olano,
+/// http://www.jcgt.org/published/0009/03/02/. Arithmetic wraps, as it does in WGSL.
+pub fn hash(u: UVec3) -> Vec3 {
+    let mut v = u
+        .to_array()
+        .map(|x| x.wrapping_mul(1664525).wrapping_add(1013904223));
+    let mix = |v: &mut [u32; 3]| {
+        v[0] = v[0].wrapping_add(v[1].wrapping_mul(v[2]));
+        v[1] = v[1].wrapping_add(v[2].wrapping_mul(v[0]));
+        v[2] = v[2].wrapping_add(v[0].wrapping_mul(v[1]));
+    };
+    mix(&mut v);
+    v = v.map(|x| x ^ (x >> 16));
+    mix(&mut v);
+    Vec3::from_array(v.map(|x| x as f32)) * (1.0 / u32::MAX as f32)
+}
+
+/// Gradient noise after Inigo Quilez, https://iquilezles.org/articles/gradientnoise/, as the
+/// value followed by its analytic derivatives.
+pub fn noised(x: Vec3) -> Vec4 {
+    // grid
+    let i = x
+        .floor()
+        .as_ivec3()
+        .wrapping_add(IVec3::splat(i32::MAX))
+        .as_uvec3();
+    let corner = |offset: UVec3| hash(i.wrapping_add(offset));
+
+    let f = x.fract_gl();
+
+    // quintic interpolant
+    let u = f * f * f * (f * (f * 6.0 - 15.0) + 10.0);
+    let du = 30.0 * f * f * (f * (f - 2.0) + 1.0);
+
+    // gradients
+    let ga = corner(uvec3(0, 0, 0));
+    let gb = corner(uvec3(1, 0, 0));
+    let gc = corner(uvec3(0, 1, 0));
+    let gd = corner(uvec3(1, 1, 0));
+    let ge = corner(uvec3(0, 0, 1));
+    let gf = corner(uvec3(1, 0, 1));
+    let gg = corner(uvec3(0, 1, 1));
+    let gh = corner(uvec3(1, 1, 1));
+
+    // projections
+    let va = ga.dot(f - vec3(0.0, 0.0, 0.0));
+    let vb = gb.dot(f - vec3(1.0, 0.0, 0.0));
+    let vc = gc.dot(f - vec3(0.0, 1.0, 0.0));
+    let vd = gd.dot(f - vec3(1.0, 1.0, 0.0));
+    let ve = ge.dot(f - vec3(0.0, 0.0, 1.0));
+    let vf = gf.dot(f - vec3(1.0, 0.0, 1.0));
+    let vg = gg.dot(f - vec3(0.0, 1.0, 1.0));
+    let vh = gh.dot(f - vec3(1.0, 1.0, 1.0));
+
+    // interpolations
+    let k0 = va - vb - vc + vd;
+    let g0 = ga - gb - gc + gd;
+    let k1 = va - vc - ve + vg;
+    let g1 = ga - gc - ge + gg;
+    let k2 = va - vb - ve + vf;
+    let g2 = ga - gb - ge + gf;
+    let k3 = -va + vb + vc - vd + ve - vf - vg + vh;
+    let g3 = -ga + gb + gc - gd + ge - gf - gg + gh;
+    let k4 = vb - va;
+    let g4 = gb - ga;
+    let k5 = vc - va;
+    let g5 = gc - ga;
+    let k6 = ve - va;
+    let g6 = ge - ga;
+
+    let value = va
+        + k4 * u.x
+        + k5 * u.y
+        + k6 * u.z
+        + k0 * u.x * u.y
+        + k1 * u.y * u.z
+        + k2 * u.z * u.x
+        + k3 * u.x * u.y * u.z;
+    let derivatives = ga
+        + g4 * u.x
+        + g5 * u.y
+        + g6 * u.z
+        + g0 * u.x * u.y
+        + g1 * u.y * u.z
+        + g2 * u.z * u.x
+        + g3 * u.x * u.y * u.z
+        + du * (vec3(k4, k5, k6)
+            + vec3(k0, k1, k2) * u.yzx()
+            + vec3(k2, k0, k1) * u.zxy()
+            + k3 * u.yzx() * u.zxy());
+    vec4(value, derivatives.x, derivatives.y, derivatives.z)
+}
+
+/// The curl of the vector potential made of three offset copies of the noise, before it is
+/// normalised into the tangent of [`curl_noise`]. Derivatives are taken in noise space, so the
+/// curl in world space is `scale` times larger.
+pub fn curl(position: Vec3, offset_y: Vec3, offset_z: Vec3, scale: f32) -> Vec3 {
+    let gx = noised(position * scale).yzw();
+    let gy = noised(position * scale + offset_y).yzw();
+    let gz = noised(position * scale + offset_z).yzw();
+    vec3(gz.y - gy.z, gx.z - gz.x, gy.x - gx.y)
+}
+
+pub fn curl_noise(position: Vec3, offset_y: Vec3, offset_z: Vec3, scale: f32) -> Frame {
+    frame_from_tangent(curl(position, offset_y, offset_z, scale))
+}
+
+pub fn frame_from_tangent(tangent: Vec3) -> Frame {
+    let tangent_norm = tangent.normalize();
+    let normal = tangent_norm.cross(vec3(0.0, 1.0, 0.0)).normalize();
+    let binormal = normal.cross(tangent_norm);
+    Frame {
+        normal,
+        binormal,
+        tangent: tangent_norm,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn random_points(count: usize) -> impl Iterator<Item = Vec3> {
+        let mut rng = fastrand::Rng::with_seed(7);
+        (0..count).map(move |_| vec3(rng.f32(), rng.f32(), rng.f32()) * 20.0 - Vec3::splat(10.0))
+    }
+
+    #[test]
+    fn hash_is_in_unit_cube() {
+        for x in [0, 1, 2, u32::MAX / 2, u32::MAX] {
+            let h = hash(uvec3(x, x.wrapping_mul(7), 13));
+            assert!(h.cmpge(Vec3::ZERO).all() && h.cmple(Vec3::ONE).all(), "{h}");
+        }
+    }
+
+    #[test]
+    fn derivatives_match_finite_differences() {
+        let h = 1e-3;
+        for point in random_points(1000) {
+            let analytic = noised(point).yzw();
+            let numeric = Vec3::AXES
+                .map(|axis| (noised(point + h * axis).x - noised(point - h * axis).x) / (2.0 * h));
+            let numeric = Vec3::from_array(numeric);
+            assert!(
+                analytic.abs_diff_eq(numeric, 2e-3),
+                "at {point}: analytic {analytic}, numeric {numeric}"
+            );
+        }
+    }
+
+    #[test]
+    fn curl_is_divergence_free() {
+        let (offset_y, offset_z) = (vec3(100.0, 0.3, 100.0), vec3(-100.0, 0.3, -150.0));
+        let h = 1e-2;
+        for point in random_points(1000) {
+            let partials = Vec3::AXES.map(|axis| {
+                (curl(point + h * axis, offset_y, offset_z, 1.0)
+                    - curl(point - h * axis, offset_y, offset_z, 1.0))
+                    / (2.0 * h)
+            });
+            let divergence = partials[0].x + partials[1].y + partials[2].z;
+            let size = partials[0].x.abs() + partials[1].y.abs() + partials[2].z.abs();
+            assert!(
+                divergence.abs() <= 1e-2 * size.max(1.0),
+                "at {point}: divergence {divergence} from partials of total size {size}"
+            );
+        }
+    }
+
+    #[test]
+    fn frames_are_orthonormal() {
+        for point in random_points(100) {
+            let frame = curl_noise(
+                point,
+                vec3(100.0, 0.0, 100.0),
+                vec3(-100.0, 0.0, -150.0),
+                0.5,
+            );
+            for (a, b) in [
+                (frame.normal, frame.binormal),
+                (frame.binormal, frame.tangent),
+                (frame.tangent, frame.normal),
+            ] {
+                assert!(a.dot(b).abs() < 1e-5);
+            }
+            for axis in [frame.normal, frame.binormal, frame.tangent] {
+                assert!((axis.length() - 1.0).abs() < 1e-5);
+            }
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use anyhow::{Context, ensure};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::export::{curves::CurveOptions, plot::PlotOptions, stl::PrintOptions};
+use crate::field::Field;
+use crate::obstacles::Obstacles;
+use crate::pipelines::SimulationParams;
+use crate::seeding::{SeedStrategy, Surface};
+use crate::view::{Camera, Lighting};
+
+/// Everything that determines what the demo shows, as read from a TOML scene file. Missing
+/// tables and keys take their default values.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Scene {
+    pub simulation: SimulationParams,
+    pub seeding: SeedStrategy,
+    pub random_seed: u64,
+    pub field: Field,
+    pub obstacles: Obstacles,
+    pub camera: Camera,
+    pub lighting: Lighting,
+    /// Settings for the STL, plotter and curve exports, which only exist on native builds.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub print: PrintOptions,
+    #[cfg(not(target_arch = "wasm32"))]
+    pub plot: PlotOptions,
+    #[cfg(not(target_arch = "wasm32"))]
+    pub curves: CurveOptions,
+}
+
+impl Scene {
+    /// Relative paths in the file are taken relative to the directory containing it.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Unable to read scene {}", path.display()))?;
+        let mut scene: Self = toml::from_str(&text)
+            .with_context(|| format!("Unable to parse scene {}", path.display()))?;
+        scene
+            .validate()
+            .with_context(|| format!("Invalid scene {}", path.display()))?;
+        scene.resolve_paths(path.parent().unwrap_or(Path::new("")));
+        Ok(scene)
+    }
+
+    /// Rejects obstacles that would feed NaN into the distance field, from a transform that
+    /// cannot be inverted or keyframes that cannot be interpolated.
+    fn validate(&self) -> anyhow::Result<()> {
+        for (index, obstacle) in self.obstacles.shapes.iter().enumerate() {
+            let mut placements = std::iter::once(&obstacle.placement)
+                .chain(obstacle.keyframes.iter().map(|key| &key.placement));
+            ensure!(
+                placements.all(|placement| placement.scale > 0.0 && placement.scale.is_finite()),
+                "Obstacle {index} has a scale that is not a positive number"
+            );
+            ensure!(
+                obstacle
+                    .keyframes
+                    .windows(2)
+                    .all(|pair| pair[0].time <= pair[1].time),
+                "The keyframes of obstacle {index} are not in time order"
+            );
+        }
+        Ok(())
+    }
+
+    fn resolve_paths(&mut self, base: &Path) {
+        let resolve = |path: &mut PathBuf| *path = base.join(&*path);
+        match &mut self.seeding {
+            SeedStrategy::Mesh { path }
+            | SeedStrategy::Csv { path }
+            | SeedStrategy::PoissonDisk {
+                surface: Surface::Mesh { path },
+                ..
+            } => resolve(path),
+            _ => {}
+        }
+        if let Field::Volume { path, .. } = &mut self.field {
+            resolve(path);
+        }
+        if let Some(volume) = &mut self.obstacles.volume {
+            resolve(&mut volume.path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn obstacles_are_validated() {
+        let scene = |obstacle: &str| -> Scene {
+            toml::from_str(&format!(
+                "[[obstacles.shapes]]\nshape = {{ kind = \"sphere\", radius = 0.5 }}\n{obstacle}"
+            ))
+            .unwrap()
+        };
+        assert!(scene("placement = { scale = 2.0 }").validate().is_ok());
+        assert!(scene("placement = { scale = 0.0 }").validate().is_err());
+        assert!(scene("placement = { scale = -1.0 }").validate().is_err());
+
+        let keyframes = |times: [f32; 2], scale: f32| {
+            format!(
+                "keyframes = [{{ time = {}, scale = 1.0 }}, {{ time = {}, scale = {scale} }}]",
+                times[0], times[1]
+            )
+        };
+        assert!(scene(&keyframes([0.0, 1.0], 2.0)).validate().is_ok());
+        assert!(scene(&keyframes([1.0, 0.0], 2.0)).validate().is_err());
+        assert!(scene(&keyframes([0.0, 1.0], 0.0)).validate().is_err());
+    }
+}
@@ -0,0 +1,301 @@
+mod obj;
+
+use anyhow::{Context, bail};
+use glam::{IVec3, Vec2, Vec3, vec3};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::f32::consts::TAU;
+use std::path::PathBuf;
+
+pub use self::obj::TriangleMesh;
+
+/// Where strands start. Every strategy produces exactly one seed per strand: sources that yield
+/// fewer points than there are strands are repeated from the start, and surplus points are
+/// dropped.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SeedStrategy {
+    /// A square grid in the XZ plane, filled row by row.
+    Grid { spacing: f32 },
+    /// Uniformly random inside an axis-aligned box.
+    UniformBox { min: Vec3, max: Vec3 },
+    /// Uniformly random inside a ball.
+    UniformSphere { centre: Vec3, radius: f32 },
+    /// Points on a surface no closer to each other than `spacing`.
+    PoissonDisk { surface: Surface, spacing: f32 },
+    /// Random points on the triangles of an OBJ mesh, weighted by triangle area.
+    Mesh { path: PathBuf },
+    /// One `x,y,z` position per line of a CSV file.
+    Csv { path: PathBuf },
+}
+
+impl Default for SeedStrategy {
+    fn default() -> Self {
+        Self::Grid { spacing: 0.1 }
+    }
+}
+
+/// Surfaces that seeds can be scattered over.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Surface {
+    /// A rectangle in the XZ plane.
+    Plane {
+        centre: Vec3,
+        size: Vec2,
+    },
+    Sphere {
+        centre: Vec3,
+        radius: f32,
+    },
+    Mesh {
+        path: PathBuf,
+    },
+}
+
+impl SeedStrategy {
+    /// Built in strategies that need no external files, in the order they are cycled through.
+    pub fn presets() -> [Self; 5] {
+        [
+            Self::default(),
+            Self::PoissonDisk {
+                surface: Surface::Plane {
+                    centre: vec3(1.6, 0.0, 1.6),
+                    size: Vec2::splat(3.2),
+                },
+                spacing: 0.1,
+            },
+            Self::UniformBox {
+                min: vec3(0.0, -0.5, 0.0),
+                max: vec3(3.2, 0.5, 3.2),
+            },
+            Self::UniformSphere {
+                centre: vec3(1.6, 0.0, 1.6),
+                radius: 1.6,
+            },
+            Self::PoissonDisk {
+                surface: Surface::Sphere {
+                    centre: vec3(1.6, 0.0, 1.6),
+                    radius: 1.2,
+                },
+                spacing: 0.1,
+            },
+        ]
+    }
+
+    /// Strategy for a file dropped onto the window: OBJ meshes are scattered over, Poisson-disk
+    /// style if that is the current strategy, and CSV files are read as positions.
+    pub fn for_file(&self, path: PathBuf) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match (extension.as_str(), self) {
+            ("obj", Self::PoissonDisk { spacing, .. }) => Some(Self::PoissonDisk {
+                surface: Surface::Mesh { path },
+                spacing: *spacing,
+            }),
+            ("obj", _) => Some(Self::Mesh { path }),
+            ("csv", _) => Some(Self::Csv { path }),
+            _ => None,
+        }
+    }
+
+    pub fn generate(&self, count: usize, seed: u64) -> anyhow::Result<Vec<Vec3>> {
+        let mut rng = fastrand::Rng::with_seed(seed);
+        let points = match self {
+            Self::Grid { spacing } => {
+                let columns = (count as f32).sqrt().ceil().max(1.0) as usize;
+                (0..count)
+                    .map(|i| vec3((i % columns) as f32, 0.0, (i / columns) as f32) * *spacing)
+                    .collect()
+            }
+            Self::UniformBox { min, max } => (0..count)
+                .map(|_| *min + (*max - *min) * random_unit_cube(&mut rng))
+                .collect(),
+            Self::UniformSphere { centre, radius } => (0..count)
+                .map(|_| *centre + *radius * random_in_ball(&mut rng))
+                .collect(),
+            Self::PoissonDisk { surface, spacing } => {
+                let mesh = match surface {
+                    Surface::Mesh { path } => Some(TriangleMesh::load_obj(path)?),
+                    _ => None,
+                };
+                let sampler = AreaSampler::new(mesh.as_ref());
+                if mesh.is_some() && sampler.is_none() {
+                    bail!("Mesh has no area");
+                }
+                poisson_disk(count, *spacing, &mut rng, |rng| {
+                    surface.sample(sampler.as_ref(), rng)
+                })
+            }
+            Self::Mesh { path } => {
+                let mesh = TriangleMesh::load_obj(path)?;
+                let sampler = AreaSampler::new(Some(&mesh)).context("Mesh has no area")?;
+                (0..count).map(|_| sampler.sample(&mut rng)).collect()
+            }
+            Self::Csv { path } => {
+                let text = std::fs::read_to_string(path)
+                    .with_context(|| format!("Unable to read seeds from {}", path.display()))?;
+                parse_csv(&text)
+                    .with_context(|| format!("Unable to parse seeds in {}", path.display()))?
+            }
+        };
+        fit_to_count(points, count)
+    }
+}
+
+impl Surface {
+    fn sample(&self, mesh: Option<&AreaSampler>, rng: &mut fastrand::Rng) -> Vec3 {
+        match self {
+            Self::Plane { centre, size } => {
+                *centre + vec3((rng.f32() - 0.5) * size.x, 0.0, (rng.f32() - 0.5) * size.y)
+            }
+            Self::Sphere { centre, radius } => *centre + *radius * random_on_sphere(rng),
+            Self::Mesh { .. } => mesh.expect("mesh surfaces have a sampler").sample(rng),
+        }
+    }
+}
+
+fn fit_to_count(points: Vec<Vec3>, count: usize) -> anyhow::Result<Vec<Vec3>> {
+    if points.is_empty() {
+        bail!("Seed strategy produced no points");
+    }
+    Ok(points.iter().copied().cycle().take(count).collect())
+}
+
+fn random_unit_cube(rng: &mut fastrand::Rng) -> Vec3 {
+    vec3(rng.f32(), rng.f32(), rng.f32())
+}
+
+fn random_in_ball(rng: &mut fastrand::Rng) -> Vec3 {
+    loop {
+        let candidate = 2.0 * random_unit_cube(rng) - Vec3::ONE;
+        if candidate.length_squared() <= 1.0 {
+            return candidate;
+        }
+    }
+}
+
+fn random_on_sphere(rng: &mut fastrand::Rng) -> Vec3 {
+    let z = 2.0 * rng.f32() - 1.0;
+    let angle = TAU * rng.f32();
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    vec3(r * angle.cos(), r * angle.sin(), z)
+}
+
+/// Picks uniformly distributed points on a mesh by choosing triangles in proportion to their area.
+struct AreaSampler<'a> {
+    mesh: &'a TriangleMesh,
+    cumulative_area: Vec<f32>,
+}
+
+impl<'a> AreaSampler<'a> {
+    fn new(mesh: Option<&'a TriangleMesh>) -> Option<Self> {
+        let mesh = mesh?;
+        let cumulative_area: Vec<f32> = (0..mesh.triangles.len())
+            .scan(0.0, |total, i| {
+                *total += mesh.triangle_area(i);
+                Some(*total)
+            })
+            .collect();
+        (cumulative_area.last().copied().unwrap_or_default() > 0.0).then_some(Self {
+            mesh,
+            cumulative_area,
+        })
+    }
+
+    fn sample(&self, rng: &mut fastrand::Rng) -> Vec3 {
+        let total = self.cumulative_area.last().copied().unwrap_or_default();
+        let target = rng.f32() * total;
+        let index = self
+            .cumulative_area
+            .partition_point(|&area| area < target)
+            .min(self.cumulative_area.len() - 1);
+        let [a, b, c] = self.mesh.triangle(index);
+        let r1 = rng.f32().sqrt();
+        let r2 = rng.f32();
+        a * (1.0 - r1) + b * (r1 * (1.0 - r2)) + c * (r1 * r2)
+    }
+}
+
+/// Dart throwing: candidates from `sample` are accepted if no accepted point lies within
+/// `spacing`. Gives up after a fixed number of failed candidates per requested point, so dense
+/// requests on small surfaces return fewer points.
+fn poisson_disk(
+    count: usize,
+    spacing: f32,
+    rng: &mut fastrand::Rng,
+    mut sample: impl FnMut(&mut fastrand::Rng) -> Vec3,
+) -> Vec<Vec3> {
+    const ATTEMPTS_PER_POINT: usize = 30;
+    let cell = |p: Vec3| (p / spacing).floor().as_ivec3();
+    let mut grid: HashMap<IVec3, Vec<Vec3>> = HashMap::new();
+    let mut points = Vec::with_capacity(count);
+    let mut failures = 0;
+    while points.len() < count && failures < ATTEMPTS_PER_POINT * count {
+        let candidate = sample(rng);
+        let centre = cell(candidate);
+        let crowded = (-1..=1).any(|x| {
+            (-1..=1).any(|y| {
+                (-1..=1).any(|z| {
+                    grid.get(&(centre + IVec3::new(x, y, z)))
+                        .is_some_and(|cell| {
+                            cell.iter()
+                                .any(|p| p.distance_squared(candidate) < spacing * spacing)
+                        })
+                })
+            })
+        });
+        if crowded {
+            failures += 1;
+        } else {
+            grid.entry(centre).or_default().push(candidate);
+            points.push(candidate);
+        }
+    }
+    points
+}
+
+/// Blank lines, `#` comments and a header row before the first position are skipped.
+fn parse_csv(text: &str) -> anyhow::Result<Vec<Vec3>> {
+    let mut points = Vec::new();
+    let mut first_row = true;
+    for (line_number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields = line
+            .split(',')
+            .map(|field| field.trim().parse::<f32>())
+            .collect::<Result<Vec<_>, _>>();
+        match fields {
+            Ok(fields) if fields.len() >= 3 => points.push(vec3(fields[0], fields[1], fields[2])),
+            Err(_) if first_row => {}
+            _ => bail!("Expected x,y,z on line {}", line_number + 1),
+        }
+        first_row = false;
+    }
+    Ok(points)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_seeds_parse() {
+        let text = "# seeds\n\nx, y, z\n1,2,3\n 4.5 ,-5, 6, 0.1\n";
+        assert_eq!(
+            parse_csv(text).unwrap(),
+            [vec3(1.0, 2.0, 3.0), vec3(4.5, -5.0, 6.0)]
+        );
+        assert!(parse_csv("1,2,3\nx,y,z\n").is_err());
+        assert!(parse_csv("1,2\n").is_err());
+        assert!(parse_csv("1,2,3\n4,five,6\n").is_err());
+        assert!(parse_csv("x,y,z\n").unwrap().is_empty());
+        assert!(fit_to_count(Vec::new(), 4).is_err());
+        assert_eq!(
+            fit_to_count(vec![Vec3::X, Vec3::Y], 3).unwrap(),
+            [Vec3::X, Vec3::Y, Vec3::X]
+        );
+    }
+}
@@ -0,0 +1,100 @@
+use anyhow::{Context, bail};
+use glam::{Vec3, vec3};
+use std::path::Path;
+
+/// Triangle soup read from a Wavefront OBJ file. Only vertex positions and faces are read;
+/// polygons are triangulated as fans.
+#[derive(Debug, Default, Clone)]
+pub struct TriangleMesh {
+    pub positions: Vec<Vec3>,
+    pub triangles: Vec<[u32; 3]>,
+}
+
+impl TriangleMesh {
+    pub fn load_obj(path: &Path) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Unable to read mesh {}", path.display()))?;
+        Self::parse_obj(&text).with_context(|| format!("Unable to parse mesh {}", path.display()))
+    }
+
+    pub fn parse_obj(text: &str) -> anyhow::Result<Self> {
+        let mut mesh = Self::default();
+        for (line_number, line) in text.lines().enumerate() {
+            let mut fields = line.split_whitespace();
+            match fields.next() {
+                Some("v") => {
+                    let coordinates = fields
+                        .take(3)
+                        .map(str::parse::<f32>)
+                        .collect::<Result<Vec<_>, _>>()
+                        .with_context(|| format!("Bad vertex on line {}", line_number + 1))?;
+                    if coordinates.len() != 3 {
+                        bail!("Vertex on line {} has too few coordinates", line_number + 1);
+                    }
+                    mesh.positions
+                        .push(vec3(coordinates[0], coordinates[1], coordinates[2]));
+                }
+                Some("f") => {
+                    let indices = fields
+                        .map(|field| mesh.resolve_index(field))
+                        .collect::<anyhow::Result<Vec<_>>>()
+                        .with_context(|| format!("Bad face on line {}", line_number + 1))?;
+                    if indices.len() < 3 {
+                        bail!("Face on line {} has fewer than 3 vertices", line_number + 1);
+                    }
+                    for i in 1..indices.len() - 1 {
+                        mesh.triangles
+                            .push([indices[0], indices[i], indices[i + 1]]);
+                    }
+                }
+                _ => {}
+            }
+        }
+        if mesh.triangles.is_empty() {
+            bail!("Mesh has no faces");
+        }
+        Ok(mesh)
+    }
+
+    /// Faces refer to vertices as `v`, `v/vt`, `v//vn` or `v/vt/vn`, 1-based, or negative to
+    /// count back from the most recent vertex.
+    fn resolve_index(&self, field: &str) -> anyhow::Result<u32> {
+        let index: i64 = field.split('/').next().unwrap_or_default().parse()?;
+        let count = self.positions.len() as i64;
+        let resolved = if index < 0 { count + index } else { index - 1 };
+        if !(0..count).contains(&resolved) {
+            bail!("Vertex index {index} out of range");
+        }
+        Ok(resolved as u32)
+    }
+
+    pub fn triangle(&self, index: usize) -> [Vec3; 3] {
+        self.triangles[index].map(|i| self.positions[i as usize])
+    }
+
+    pub fn triangle_area(&self, index: usize) -> f32 {
+        let [a, b, c] = self.triangle(index);
+        0.5 * (b - a).cross(c - a).length()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn obj_faces_parse() {
+        let text =
+            "# square\nv 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvn 0 0 1\nf 1//1 2//1 3//1 -1//1\n";
+        let mesh = TriangleMesh::parse_obj(text).unwrap();
+        assert_eq!(mesh.positions.len(), 4);
+        assert_eq!(mesh.triangles, [[0, 1, 2], [0, 2, 3]]);
+        assert_eq!(mesh.triangle_area(0) + mesh.triangle_area(1), 1.0);
+
+        assert!(TriangleMesh::parse_obj("v 0 0 0\n").is_err());
+        assert!(TriangleMesh::parse_obj("v 0 0\nf 1 1 1\n").is_err());
+        assert!(TriangleMesh::parse_obj("v 0 0 0\nv 1 0 0\nf 1 2\n").is_err());
+        assert!(TriangleMesh::parse_obj("v 0 0 0\nf 1 2 3\n").is_err());
+        assert!(TriangleMesh::parse_obj("v 0 0 0\nf 1 a 1\n").is_err());
+    }
+}
@@ -93,8 +93,21 @@ fn curl_noise(position: vec3<f32>, offset_y: vec3<f32>, offset_z: vec3<f32>, sca
     let gy = noised(position * scale + offset_y).yzw;
     let gz = noised(position * scale + offset_z).yzw;
     let tangent = vec3(gz.y - gy.z, gx.z - gz.x, gy.x - gx.y);
+    return frame_from_tangent(tangent);
+}
+
+fn frame_from_tangent(tangent: vec3<f32>) -> Frame {
     let tangent_norm = normalize(tangent);
     let normal = normalize(cross(tangent_norm, vec3(0.0,1.0,0.0)));
     let binormal = cross(normal,tangent_norm);
     return Frame(normal,binormal,tangent_norm);
 }
+
+// the vector potential whose curl curl_noise follows
+fn noise_potential(position: vec3<f32>, offset_y: vec3<f32>, offset_z: vec3<f32>, scale: f32) -> vec3<f32> {
+    return vec3(
+        noised(position * scale).x,
+        noised(position * scale + offset_y).x,
+        noised(position * scale + offset_z).x,
+    );
+}
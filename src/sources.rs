@@ -0,0 +1,369 @@
+use anyhow::ensure;
+use glam::Vec3;
+use std::path::Path;
+use std::time::Duration;
+use web_time::Instant;
+
+use crate::import::{Fit, Polylines};
+use crate::pipelines::{Pipelines, SimulationParams, TubeInstance};
+use crate::profiler::{Pass, Profiler};
+use crate::reference::Tracer;
+
+/// What a strand source gets to work with each frame.
+pub struct SourceFrame<'a> {
+    pub device: &'a wgpu::Device,
+    pub queue: &'a wgpu::Queue,
+    pub encoder: &'a mut wgpu::CommandEncoder,
+    pub pipelines: &'a mut Pipelines,
+    /// To time any compute pass with.
+    pub profiler: &'a mut Profiler,
+    pub simulation: SimulationParams,
+    /// Fraction of their full length that strands have grown to in [`GrowthMode::Grow`].
+    ///
+    /// [`GrowthMode::Grow`]: crate::pipelines::GrowthMode::Grow
+    pub growth_progress: f32,
+    /// Seconds since the demo started.
+    pub time: f32,
+}
+
+/// Produces the segments that the pipelines draw, either by dispatching the compute pass or by
+/// uploading instances made on the CPU.
+pub trait StrandSource {
+    fn name(&self) -> String;
+
+    /// Called when the source becomes the active one, before its first frame.
+    fn start(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        pipelines: &mut Pipelines,
+    ) -> anyhow::Result<()>;
+
+    /// Records or uploads whatever the segments drawn this frame need.
+    fn update(&mut self, frame: SourceFrame) -> anyhow::Result<()>;
+}
+
+/// Built in sources, in the order they are cycled through. The first is the default.
+pub fn presets(centre: Vec3, size: f32, pipelines: &Pipelines) -> [Box<dyn StrandSource>; 3] {
+    [
+        curl_noise(pipelines),
+        Box::new(Fixed::sinusoid(centre, size)),
+        Box::new(Callback::ripple(centre, size)),
+    ]
+}
+
+/// Strands traced through the curl noise by the compute shaders if the device has them, and on
+/// the CPU if not.
+pub fn curl_noise(pipelines: &Pipelines) -> Box<dyn StrandSource> {
+    if pipelines.computes_instances() {
+        Box::new(CurlNoise)
+    } else {
+        Box::new(CpuCurlNoise::default())
+    }
+}
+
+/// [`curl_noise`] traced in full every frame, whatever the time it takes on the CPU, for
+/// renders that are saved rather than shown.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn curl_noise_in_full(pipelines: &Pipelines) -> Box<dyn StrandSource> {
+    if pipelines.computes_instances() {
+        Box::new(CurlNoise)
+    } else {
+        Box::new(CpuCurlNoise {
+            budget: None,
+            ..Default::default()
+        })
+    }
+}
+
+/// Strands traced through the curl noise, or the imported vector field, by the compute shaders.
+pub struct CurlNoise;
+
+impl StrandSource for CurlNoise {
+    fn name(&self) -> String {
+        "Curl noise".to_string()
+    }
+
+    fn start(
+        &mut self,
+        device: &wgpu::Device,
+        _queue: &wgpu::Queue,
+        pipelines: &mut Pipelines,
+    ) -> anyhow::Result<()> {
+        ensure!(
+            pipelines.computes_instances(),
+            "The device has no compute shaders to trace strands with"
+        );
+        pipelines.use_computed_instances(device);
+        Ok(())
+    }
+
+    fn update(&mut self, frame: SourceFrame) -> anyhow::Result<()> {
+        let mut compute_pass = frame
+            .encoder
+            .begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Compute Pass"),
+                timestamp_writes: frame.profiler.compute_timestamps(Pass::Compute),
+            });
+        frame
+            .pipelines
+            .compute_instances(&mut compute_pass, frame.simulation.growth);
+        Ok(())
+    }
+}
+
+/// Strands traced through the curl noise on the CPU, for devices without compute shaders such
+/// as WebGL2 ones. Obstacles and imported vector fields are not followed, and trails are drawn
+/// at full length.
+///
+/// Strands are traced in turn for up to [`Self::BUDGET`] each frame, those not yet drawn first,
+/// so on a slow CPU they catch up with the moving noise over several frames rather than holding
+/// every frame up. Only the segments of the strands traced are uploaded.
+pub struct CpuCurlNoise {
+    /// Time to spend tracing each frame, or `None` to trace every strand.
+    budget: Option<Duration>,
+    /// Segments of every strand, strand by strand, collapsed onto the seed until first traced.
+    instances: Vec<TubeInstance>,
+    /// Segments each strand takes up in `instances`.
+    segments: usize,
+    /// The strands before this one have been traced at least once.
+    traced: usize,
+    /// The strand to retrace first in the next frame, once all have been traced.
+    next_strand: usize,
+}
+
+impl CpuCurlNoise {
+    const BUDGET: Duration = Duration::from_millis(8);
+}
+
+impl Default for CpuCurlNoise {
+    fn default() -> Self {
+        Self {
+            budget: Some(Self::BUDGET),
+            instances: Vec::new(),
+            segments: 0,
+            traced: 0,
+            next_strand: 0,
+        }
+    }
+}
+
+impl StrandSource for CpuCurlNoise {
+    fn name(&self) -> String {
+        "Curl noise, traced on the CPU".to_string()
+    }
+
+    fn start(
+        &mut self,
+        _device: &wgpu::Device,
+        _queue: &wgpu::Queue,
+        _pipelines: &mut Pipelines,
+    ) -> anyhow::Result<()> {
+        self.instances.clear();
+        self.traced = 0;
+        Ok(())
+    }
+
+    fn update(&mut self, frame: SourceFrame) -> anyhow::Result<()> {
+        let segments = frame
+            .simulation
+            .segments
+            .clamp(1, Pipelines::SEGMENTS_PER_STRAND as u32) as usize;
+        let strands = frame
+            .simulation
+            .strands
+            .clamp(1, Pipelines::NUM_STRANDS as u32) as usize;
+        let tracer = Tracer::new(
+            frame.simulation,
+            frame.growth_progress,
+            frame.simulation.drift * frame.time,
+        );
+        let seeds = frame.pipelines.seeds();
+        // Coloured as the compute shader colours them.
+        let trace = |strand: usize| {
+            tracer.trace(seeds[strand], Vec3::splat(strand as f32 / strands as f32))
+        };
+
+        // Strands laid out with another number of segments are all traced again.
+        if segments != self.segments {
+            self.instances.clear();
+            self.segments = segments;
+            self.traced = 0;
+        }
+        let resized = self.instances.len() != strands * segments;
+        if resized {
+            self.traced = self.traced.min(strands);
+            self.instances.truncate(strands * segments);
+            for &seed in &seeds[self.instances.len() / segments..strands] {
+                let collapsed = TubeInstance {
+                    start_position: seed,
+                    start_normal: Vec3::X,
+                    start_bitangent: Vec3::Y,
+                    end_position: seed,
+                    end_normal: Vec3::X,
+                    end_bitangent: Vec3::Y,
+                    radius: 0.0,
+                    ..Default::default()
+                };
+                self.instances
+                    .extend(std::iter::repeat_n(collapsed, segments));
+            }
+        }
+
+        // At least one strand is traced each frame, so they all get drawn in the end.
+        let started = Instant::now();
+        let mut changed = Vec::new();
+        let budget = self.budget;
+        let in_budget = || budget.is_none_or(|budget| started.elapsed() <= budget);
+        while changed.len() < strands && (changed.is_empty() || in_budget()) {
+            let strand = if self.traced < strands {
+                self.traced += 1;
+                self.traced - 1
+            } else {
+                let strand = self.next_strand % strands;
+                self.next_strand = strand + 1;
+                strand
+            };
+            self.instances[strand * segments..(strand + 1) * segments]
+                .copy_from_slice(&trace(strand));
+            changed.push(strand);
+        }
+
+        if resized {
+            return frame
+                .pipelines
+                .upload_instances(frame.device, frame.queue, &self.instances);
+        }
+        // Strands are traced in order, so those changed make up a run or two.
+        for run in changed.chunk_by(|a, b| *b == a + 1) {
+            let first = run[0] * segments;
+            let end = (run[run.len() - 1] + 1) * segments;
+            frame.pipelines.write_uploaded_instances(
+                frame.queue,
+                first,
+                &self.instances[first..end],
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Segments made once on the CPU and uploaded when the source starts.
+pub struct Fixed {
+    name: String,
+    instances: Vec<TubeInstance>,
+}
+
+impl Fixed {
+    /// Side by side sine waves, scaled to fit a cube of side `size` around `centre`.
+    pub fn sinusoid(centre: Vec3, size: f32) -> Self {
+        Self {
+            name: "Sinusoid".to_string(),
+            instances: fit(Pipelines::sinusoid_instances(16), centre, size),
+        }
+    }
+
+    /// Strands from a `.hair` or polyline CSV file, scaled to fit a cube of side `size` around
+    /// `centre`.
+    pub fn load(path: &Path, centre: Vec3, size: f32) -> anyhow::Result<Self> {
+        let mut polylines = Polylines::load(path)?;
+        polylines.fit(centre, size);
+        Ok(Self {
+            name: format!("{} strands from {}", polylines.0.len(), path.display()),
+            instances: polylines.to_instances(),
+        })
+    }
+}
+
+impl StrandSource for Fixed {
+    fn name(&self) -> String {
+        format!("{} ({} segments)", self.name, self.instances.len())
+    }
+
+    fn start(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        pipelines: &mut Pipelines,
+    ) -> anyhow::Result<()> {
+        pipelines.upload_instances(device, queue, &self.instances)
+    }
+
+    fn update(&mut self, _frame: SourceFrame) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// Segments made afresh every frame by a function of the time in seconds.
+pub struct Callback {
+    name: String,
+    callback: Box<dyn FnMut(f32) -> Vec<TubeInstance>>,
+}
+
+impl Callback {
+    pub fn new(name: &str, callback: impl FnMut(f32) -> Vec<TubeInstance> + 'static) -> Self {
+        Self {
+            name: name.to_string(),
+            callback: Box::new(callback),
+        }
+    }
+
+    /// The sine waves of [`Fixed::sinusoid`], each bobbing up and down a little after the
+    /// one before it.
+    pub fn ripple(centre: Vec3, size: f32) -> Self {
+        let instances = Fixed::sinusoid(centre, size).instances;
+        Self::new("Ripple", move |time| {
+            instances
+                .iter()
+                .map(|instance| {
+                    // Strands run along Y, so the whole of each one moves together.
+                    let lift = 0.1 * size * (2.0 * time + 4.0 * instance.start_position.x).sin();
+                    TubeInstance {
+                        start_position: instance.start_position + lift * Vec3::Z,
+                        end_position: instance.end_position + lift * Vec3::Z,
+                        ..*instance
+                    }
+                })
+                .collect()
+        })
+    }
+}
+
+impl StrandSource for Callback {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn start(
+        &mut self,
+        _device: &wgpu::Device,
+        _queue: &wgpu::Queue,
+        _pipelines: &mut Pipelines,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn update(&mut self, frame: SourceFrame) -> anyhow::Result<()> {
+        let instances = (self.callback)(frame.time);
+        frame
+            .pipelines
+            .upload_instances(frame.device, frame.queue, &instances)
+    }
+}
+
+/// Scales and moves `instances` so that their segment ends fit centred in the cube of side
+/// `size` around `centre`.
+fn fit(mut instances: Vec<TubeInstance>, centre: Vec3, size: f32) -> Vec<TubeInstance> {
+    let ends = instances
+        .iter()
+        .flat_map(|instance| [instance.start_position, instance.end_position]);
+    let Some(fit) = Fit::new(ends, centre, size) else {
+        return instances;
+    };
+    for instance in &mut instances {
+        instance.start_position = fit.apply(instance.start_position);
+        instance.end_position = fit.apply(instance.end_position);
+        instance.radius *= fit.scale;
+    }
+    instances
+}
@@ -0,0 +1,93 @@
+use glam::{Mat4, Vec3, vec3};
+use serde::{Deserialize, Serialize};
+
+/// Whether the camera moves.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CameraMode {
+    #[default]
+    Orbit,
+    /// Stays where the orbit starts.
+    Still,
+}
+
+/// A camera that circles a point along a Lissajous curve, always looking at it.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Camera {
+    pub mode: CameraMode,
+    pub centre: Vec3,
+    /// Half the extent of the curve along each axis.
+    pub orbit: Vec3,
+    /// How fast the camera moves along the curve, in radians of its X cycle per second.
+    pub speed: f32,
+    /// Vertical field of view in degrees.
+    pub field_of_view: f32,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            mode: CameraMode::default(),
+            centre: vec3(0.8, 0.0, 1.6),
+            orbit: vec3(5.0, 4.0, 3.0),
+            speed: 0.1,
+            field_of_view: 30.0,
+        }
+    }
+}
+
+impl Camera {
+    pub fn position(&self, seconds: f32) -> Vec3 {
+        let phase = match self.mode {
+            CameraMode::Orbit => seconds * self.speed,
+            CameraMode::Still => 0.0,
+        };
+        self.centre + self.orbit * vec3(phase.cos(), (0.9 * phase).sin(), (0.3 * phase + 1.2).sin())
+    }
+
+    /// The world to clip space transform `seconds` after the start, with reversed depth.
+    pub fn view_projection(&self, seconds: f32, aspect_ratio: f32) -> Mat4 {
+        let projection = Mat4::perspective_infinite_reverse_rh(
+            self.field_of_view.to_radians(),
+            aspect_ratio,
+            0.5,
+        );
+        let view = Mat4::look_at_rh(self.position(seconds), self.centre, Vec3::Z);
+        projection * view
+    }
+}
+
+/// A single directional light plus a constant ambient term. Colours are linear RGB.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Lighting {
+    /// Direction towards the light. It need not be normalised.
+    pub direction: Vec3,
+    pub colour: Vec3,
+    pub ambient: Vec3,
+    /// What the strands are drawn over.
+    pub background: Vec3,
+}
+
+impl Default for Lighting {
+    fn default() -> Self {
+        Self {
+            direction: vec3(-0.5, -0.2, 1.0),
+            colour: Vec3::ONE,
+            ambient: vec3(0.05, 0.05, 0.07),
+            background: vec3(0.01, 0.01, 0.014),
+        }
+    }
+}
+
+impl Lighting {
+    pub fn clear_colour(&self) -> wgpu::Color {
+        wgpu::Color {
+            r: self.background.x as f64,
+            g: self.background.y as f64,
+            b: self.background.z as f64,
+            a: 1.0,
+        }
+    }
+}
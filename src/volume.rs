@@ -0,0 +1,318 @@
+use anyhow::{Context, bail, ensure};
+use glam::{UVec3, Vec3, uvec3, vec3};
+use std::path::Path;
+
+/// Samples on a regular grid spanning an axis-aligned box, with `x` varying fastest. The first
+/// sample sits on `min` and the last on `max`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Volume {
+    pub dimensions: UVec3,
+    /// Number of interleaved values per sample: 1 for a scalar field such as an SDF.
+    pub components: u32,
+    pub min: Vec3,
+    pub max: Vec3,
+    pub data: Vec<f32>,
+}
+
+impl Volume {
+    /// Magic bytes at the start of a raw volume file, followed by a little-endian header of
+    /// `version: u32, dimensions: [u32; 3], components: u32, min: [f32; 3], max: [f32; 3]` and
+    /// then the samples as little-endian `f32`s.
+    pub const RAW_MAGIC: &[u8; 4] = b"NVOL";
+    const RAW_VERSION: u32 = 1;
+    const RAW_HEADER_LEN: usize = 48;
+
+    /// Reads legacy VTK files by their `.vtk` extension, and anything else as a raw volume.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let bytes = std::fs::read(path)
+            .with_context(|| format!("Unable to read volume {}", path.display()))?;
+        let is_vtk = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("vtk"));
+        if is_vtk {
+            Self::parse_vtk(&bytes)
+        } else {
+            Self::parse_raw(&bytes)
+        }
+        .with_context(|| format!("Unable to parse volume {}", path.display()))
+    }
+
+    pub fn parse_raw(bytes: &[u8]) -> anyhow::Result<Self> {
+        ensure!(
+            bytes.len() >= Self::RAW_HEADER_LEN && &bytes[0..4] == Self::RAW_MAGIC,
+            "Not a raw volume file"
+        );
+        let words: Vec<[u8; 4]> = bytes
+            .chunks_exact(4)
+            .map(|word| word.try_into().unwrap())
+            .collect();
+        let u32_at = |i: usize| u32::from_le_bytes(words[i]);
+        let f32_at = |i: usize| f32::from_le_bytes(words[i]);
+
+        let version = u32_at(1);
+        if version != Self::RAW_VERSION {
+            bail!("Unsupported raw volume version {version}");
+        }
+        let dimensions = uvec3(u32_at(2), u32_at(3), u32_at(4));
+        let components = u32_at(5);
+        let min = vec3(f32_at(6), f32_at(7), f32_at(8));
+        let max = vec3(f32_at(9), f32_at(10), f32_at(11));
+        let data = words[Self::RAW_HEADER_LEN / 4..]
+            .iter()
+            .map(|word| f32::from_le_bytes(*word))
+            .collect();
+        Self::new(dimensions, components, min, max, data)
+    }
+
+    /// Legacy VTK `STRUCTURED_POINTS` datasets holding a single `VECTORS` or `SCALARS` point
+    /// attribute, in ASCII or (big-endian) binary encoding.
+    pub fn parse_vtk(bytes: &[u8]) -> anyhow::Result<Self> {
+        let mut reader = LineReader { bytes, position: 0 };
+        ensure!(
+            reader.line()?.starts_with("# vtk DataFile"),
+            "Not a legacy VTK file"
+        );
+        let _title = reader.line()?;
+        let binary = match reader.line()?.trim().to_ascii_uppercase().as_str() {
+            "ASCII" => false,
+            "BINARY" => true,
+            encoding => bail!("Unknown VTK encoding {encoding}"),
+        };
+
+        let mut dimensions = None;
+        let mut origin = Vec3::ZERO;
+        let mut spacing = Vec3::ONE;
+        let (components, value_type) = loop {
+            let line = reader.line()?;
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let Some(keyword) = fields.first() else {
+                continue;
+            };
+            match keyword.to_ascii_uppercase().as_str() {
+                "DATASET" => ensure!(
+                    fields.get(1) == Some(&"STRUCTURED_POINTS"),
+                    "Only STRUCTURED_POINTS VTK datasets are supported"
+                ),
+                "DIMENSIONS" => {
+                    let [x, y, z] = parse_triple::<u32>(&fields)?;
+                    dimensions = Some(uvec3(x, y, z));
+                }
+                "ORIGIN" => origin = Vec3::from_array(parse_triple(&fields)?),
+                "SPACING" | "ASPECT_RATIO" => spacing = Vec3::from_array(parse_triple(&fields)?),
+                "POINT_DATA" => {}
+                "VECTORS" => {
+                    let value_type = fields.get(2).context("VECTORS has no data type")?;
+                    break (3, value_type.to_string());
+                }
+                "SCALARS" => {
+                    let value_type = fields.get(2).context("SCALARS has no data type")?;
+                    let components = fields.get(3).map_or(Ok(1), |count| count.parse())?;
+                    let lookup_table = reader.line()?;
+                    ensure!(
+                        lookup_table.trim_start().starts_with("LOOKUP_TABLE"),
+                        "SCALARS must be followed by LOOKUP_TABLE"
+                    );
+                    break (components, value_type.to_string());
+                }
+                keyword => bail!("Unsupported VTK keyword {keyword}"),
+            }
+        };
+
+        let dimensions = dimensions.context("VTK file has no DIMENSIONS")?;
+        ensure!(
+            dimensions.cmpge(UVec3::ONE).all(),
+            "VTK dimensions {dimensions} must all be positive"
+        );
+        let count = Self::value_count(dimensions, components)?;
+        let rest = &bytes[reader.position..];
+        let data: Vec<f32> = if binary {
+            let size = match value_type.as_str() {
+                "float" => 4,
+                "double" => 8,
+                value_type => bail!("Unsupported binary VTK data type {value_type}"),
+            };
+            ensure!(
+                count
+                    .checked_mul(size)
+                    .is_some_and(|length| rest.len() >= length),
+                "VTK file is truncated"
+            );
+            rest.chunks_exact(size)
+                .take(count)
+                .map(|value| match size {
+                    4 => f32::from_be_bytes(value.try_into().unwrap()),
+                    _ => f64::from_be_bytes(value.try_into().unwrap()) as f32,
+                })
+                .collect()
+        } else {
+            std::str::from_utf8(rest)?
+                .split_whitespace()
+                .take(count)
+                .map(str::parse)
+                .collect::<Result<_, _>>()?
+        };
+        let max = origin + spacing * (dimensions - UVec3::ONE).as_vec3();
+        Self::new(dimensions, components, origin, max, data)
+    }
+
+    pub fn new(
+        dimensions: UVec3,
+        components: u32,
+        min: Vec3,
+        max: Vec3,
+        data: Vec<f32>,
+    ) -> anyhow::Result<Self> {
+        ensure!(
+            dimensions.cmpge(UVec3::ONE).all(),
+            "Volume dimensions {dimensions} must all be positive"
+        );
+        ensure!(components > 0, "Volume has no components");
+        let expected = Self::value_count(dimensions, components)?;
+        ensure!(
+            data.len() == expected,
+            "Volume has {} values, expected {expected}",
+            data.len()
+        );
+        Ok(Self {
+            dimensions,
+            components,
+            min,
+            max,
+            data,
+        })
+    }
+
+    /// Number of values in a volume, which fails rather than overflowing for huge dimensions.
+    fn value_count(dimensions: UVec3, components: u32) -> anyhow::Result<usize> {
+        dimensions
+            .to_array()
+            .into_iter()
+            .chain([components])
+            .try_fold(1usize, |count, size| count.checked_mul(size as usize))
+            .with_context(|| format!("Volume of {dimensions} by {components} is too large"))
+    }
+}
+
+struct LineReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> LineReader<'a> {
+    fn line(&mut self) -> anyhow::Result<&'a str> {
+        let rest = &self.bytes[self.position..];
+        ensure!(!rest.is_empty(), "Unexpected end of file");
+        let length = rest.iter().position(|&byte| byte == b'\n');
+        self.position += length.map_or(rest.len(), |length| length + 1);
+        let line = &rest[..length.unwrap_or(rest.len())];
+        Ok(std::str::from_utf8(line)?.trim_end_matches('\r'))
+    }
+}
+
+fn parse_triple<T: std::str::FromStr>(fields: &[&str]) -> anyhow::Result<[T; 3]>
+where
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    ensure!(fields.len() >= 4, "{} needs three values", fields[0]);
+    Ok([fields[1].parse()?, fields[2].parse()?, fields[3].parse()?])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw(dimensions: [u32; 3], components: u32, values: &[f32]) -> Vec<u8> {
+        let mut bytes = Volume::RAW_MAGIC.to_vec();
+        for word in [Volume::RAW_VERSION]
+            .into_iter()
+            .chain(dimensions)
+            .chain([components])
+        {
+            bytes.extend(word.to_le_bytes());
+        }
+        for value in [0.0, 0.0, 0.0, 1.0, 1.0, 1.0].iter().chain(values) {
+            bytes.extend(value.to_le_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn raw_volumes_parse() {
+        let volume = Volume::parse_raw(&raw([2, 1, 1], 1, &[0.5, -0.5])).unwrap();
+        assert_eq!(volume.dimensions, uvec3(2, 1, 1));
+        assert_eq!(volume.max, Vec3::ONE);
+        assert_eq!(volume.data, [0.5, -0.5]);
+
+        assert!(Volume::parse_raw(&raw([2, 1, 1], 1, &[0.5])).is_err());
+        assert!(Volume::parse_raw(&raw([0, 1, 1], 1, &[])).is_err());
+        assert!(Volume::parse_raw(&raw([u32::MAX; 3], u32::MAX, &[])).is_err());
+        assert!(Volume::parse_raw(&raw([2, 1, 1], 1, &[0.5, -0.5])[..40]).is_err());
+    }
+
+    fn vtk(encoding: &str, dimensions: &str, attribute: &str) -> Vec<u8> {
+        format!(
+            "# vtk DataFile Version 3.0\nfield\n{encoding}\nDATASET STRUCTURED_POINTS\n\
+             DIMENSIONS {dimensions}\nORIGIN 1 0 0\nSPACING 0.5 1 2\nPOINT_DATA 2\n{attribute}\n"
+        )
+        .into_bytes()
+    }
+
+    #[test]
+    fn ascii_vtk_parses() {
+        let mut bytes = vtk("ASCII", "2 1 1", "VECTORS v float");
+        bytes.extend(b"1 2 3\n4 5 6\n");
+        let volume = Volume::parse_vtk(&bytes).unwrap();
+        assert_eq!(volume.dimensions, uvec3(2, 1, 1));
+        assert_eq!(volume.components, 3);
+        assert_eq!(volume.min, vec3(1.0, 0.0, 0.0));
+        assert_eq!(volume.max, vec3(1.5, 0.0, 0.0));
+        assert_eq!(volume.data, [1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+
+        let mut bytes = vtk("ASCII", "2 1 1", "SCALARS d float\nLOOKUP_TABLE default");
+        bytes.extend(b"0.25 -0.25\n");
+        assert_eq!(Volume::parse_vtk(&bytes).unwrap().data, [0.25, -0.25]);
+    }
+
+    #[test]
+    fn binary_vtk_parses() {
+        let mut bytes = vtk("BINARY", "2 1 1", "SCALARS d float");
+        bytes.extend(b"LOOKUP_TABLE default\n");
+        for value in [0.25f32, -0.25] {
+            bytes.extend(value.to_be_bytes());
+        }
+        assert_eq!(Volume::parse_vtk(&bytes).unwrap().data, [0.25, -0.25]);
+
+        let mut bytes = vtk("BINARY", "2 1 1", "SCALARS d double");
+        bytes.extend(b"LOOKUP_TABLE default\n");
+        for value in [0.25f64, -0.25] {
+            bytes.extend(value.to_be_bytes());
+        }
+        assert_eq!(Volume::parse_vtk(&bytes).unwrap().data, [0.25, -0.25]);
+    }
+
+    #[test]
+    fn broken_vtk_fails() {
+        let mut truncated = vtk("BINARY", "2 1 1", "VECTORS v float");
+        truncated.extend(1.0f32.to_be_bytes());
+        assert!(Volume::parse_vtk(&truncated).is_err());
+
+        let mut short = vtk("ASCII", "2 1 1", "VECTORS v float");
+        short.extend(b"1 2 3\n");
+        assert!(Volume::parse_vtk(&short).is_err());
+
+        let zero = vtk("ASCII", "0 1 1", "VECTORS v float");
+        assert!(Volume::parse_vtk(&zero).is_err());
+
+        let oversize = vtk(
+            "BINARY",
+            "4294967295 4294967295 4294967295",
+            "VECTORS v double",
+        );
+        assert!(Volume::parse_vtk(&oversize).is_err());
+        let oversize = vtk("BINARY", "65536 65536 65536", "VECTORS v double");
+        assert!(Volume::parse_vtk(&oversize).is_err());
+
+        assert!(Volume::parse_vtk(b"# vtk DataFile Version 3.0\nfield\n").is_err());
+        assert!(Volume::parse_vtk(b"not a volume\n").is_err());
+    }
+}
@@ -0,0 +1,271 @@
+use anyhow::Context;
+use glam::Vec3;
+use std::cell::RefCell;
+use wasm_bindgen::prelude::*;
+use web_sys::HtmlCanvasElement;
+use winit::event_loop::{ControlFlow, EventLoop, EventLoopProxy};
+
+use crate::query::{self, parse_colour};
+use crate::{Demo, State, UserEvent};
+
+/// Changes asked for by the host page, carried to the event loop as user events.
+#[derive(Debug)]
+pub enum Command {
+    SetNoiseScale(f32),
+    SetColours {
+        light: Option<Vec3>,
+        ambient: Option<Vec3>,
+        background: Option<Vec3>,
+    },
+    SetSpeed(f32),
+    Pause,
+    Resume,
+    /// Settings read from the address of the page.
+    Configure(query::Settings),
+    CopyLink,
+}
+
+impl Command {
+    pub fn apply(self, state: &mut State) {
+        match self {
+            Self::SetNoiseScale(scale) => state.scene.simulation.noise_scale = scale,
+            Self::SetColours {
+                light,
+                ambient,
+                background,
+            } => {
+                let lighting = &mut state.scene.lighting;
+                lighting.colour = light.unwrap_or(lighting.colour);
+                lighting.ambient = ambient.unwrap_or(lighting.ambient);
+                lighting.background = background.unwrap_or(lighting.background);
+            }
+            Self::SetSpeed(speed) => state.clock.speed = speed,
+            Self::Pause => state.clock.paused = true,
+            Self::Resume => state.clock.paused = false,
+            Self::Configure(settings) => settings.apply(state),
+            Self::CopyLink => copy_link(state),
+        }
+    }
+}
+
+thread_local! {
+    /// The canvas being drawn to, which events for the host page are sent to.
+    static CANVAS: RefCell<Option<HtmlCanvasElement>> = const { RefCell::new(None) };
+}
+
+/// The demo running on a canvas, as seen from JavaScript.
+#[wasm_bindgen]
+pub struct Noodles {
+    proxy: EventLoopProxy<UserEvent>,
+}
+
+#[wasm_bindgen]
+impl Noodles {
+    /// Starts the demo drawing to `canvas`. Once the device has been created the canvas gets a
+    /// `noodles-ready` event, with the adapter as its detail, and after that a `noodles-error`
+    /// event for every error, with the message as its detail. Only one demo can run on a page.
+    #[wasm_bindgen(constructor)]
+    pub fn new(canvas: HtmlCanvasElement) -> Result<Noodles, JsValue> {
+        let proxy = start(canvas).map_err(|e| JsValue::from_str(&format!("{e:#}")))?;
+        Ok(Self { proxy })
+    }
+
+    #[wasm_bindgen(js_name = setNoiseScale)]
+    pub fn set_noise_scale(&self, scale: f32) -> Result<(), JsValue> {
+        if !scale.is_finite() {
+            return Err(JsValue::from_str(&format!("{scale} is not a noise scale")));
+        }
+        self.send(Command::SetNoiseScale(scale));
+        Ok(())
+    }
+
+    /// Sets any of the light, ambient and background colours, given as CSS hex colours such as
+    /// `"#ff8800"`. Colours left undefined are kept.
+    #[wasm_bindgen(js_name = setColours)]
+    pub fn set_colours(
+        &self,
+        light: Option<String>,
+        ambient: Option<String>,
+        background: Option<String>,
+    ) -> Result<(), JsValue> {
+        let parse = |colour: Option<String>| {
+            colour
+                .map(|colour| parse_colour(&colour))
+                .transpose()
+                .map_err(|e| JsValue::from_str(&format!("{e:#}")))
+        };
+        self.send(Command::SetColours {
+            light: parse(light)?,
+            ambient: parse(ambient)?,
+            background: parse(background)?,
+        });
+        Ok(())
+    }
+
+    /// Runs the animation `speed` times faster than real time.
+    #[wasm_bindgen(js_name = setSpeed)]
+    pub fn set_speed(&self, speed: f32) -> Result<(), JsValue> {
+        if !speed.is_finite() {
+            return Err(JsValue::from_str(&format!("{speed} is not a speed")));
+        }
+        self.send(Command::SetSpeed(speed));
+        Ok(())
+    }
+
+    pub fn pause(&self) {
+        self.send(Command::Pause);
+    }
+
+    pub fn resume(&self) {
+        self.send(Command::Resume);
+    }
+
+    /// Copies a link that opens the demo with the current settings to the clipboard. The canvas
+    /// also gets a `noodles-link` event with the link as its detail.
+    #[wasm_bindgen(js_name = copyLink)]
+    pub fn copy_link(&self) {
+        self.send(Command::CopyLink);
+    }
+}
+
+impl Noodles {
+    fn send(&self, command: Command) {
+        if self.proxy.send_event(UserEvent::Command(command)).is_err() {
+            log::warn!("The demo has stopped, so it cannot take commands");
+        }
+    }
+}
+
+/// Starts the demo on `canvas`, returning a way to send it commands.
+pub fn start(canvas: HtmlCanvasElement) -> anyhow::Result<EventLoopProxy<UserEvent>> {
+    console_error_panic_hook::set_once();
+    // The logger is already there if the default canvas was started first.
+    if log::set_logger(&Logger).is_ok() {
+        log::set_max_level(log::LevelFilter::Info);
+    }
+
+    CANVAS.set(Some(canvas.clone()));
+    let event_loop = EventLoop::with_user_event()
+        .build()
+        .context("Unable to start the demo")?;
+    event_loop.set_control_flow(ControlFlow::Poll);
+    let proxy = event_loop.create_proxy();
+
+    match query::from_location() {
+        Ok(settings) => {
+            let _ = proxy.send_event(UserEvent::Command(Command::Configure(settings)));
+        }
+        Err(e) => log::error!("Unable to read settings from the address, {:#}", e),
+    }
+    let demo = Demo::new(&event_loop, canvas);
+    {
+        use winit::platform::web::EventLoopExtWebSys;
+        event_loop.spawn_app(demo);
+    }
+    web_sys::console::log_1(&JsValue::from_str("Started demo."));
+    Ok(proxy)
+}
+
+/// Starts the demo on the element with id `canvas`, if the page has one. Other pages start it
+/// themselves with `new Noodles(canvas)`.
+pub fn start_on_default_canvas() {
+    const CANVAS_ID: &str = "canvas";
+    let canvas = web_sys::window()
+        .and_then(|window| window.document())
+        .and_then(|document| document.get_element_by_id(CANVAS_ID))
+        .and_then(|element| element.dyn_into::<HtmlCanvasElement>().ok());
+    if let Some(canvas) = canvas
+        && let Err(e) = start(canvas)
+    {
+        log::error!("{:#}", e);
+        show_error(&e);
+    }
+}
+
+/// Tells the host page that the device is ready.
+pub fn dispatch_ready(adapter: &wgpu::AdapterInfo) {
+    dispatch(
+        "noodles-ready",
+        &JsValue::from_str(&format!("{} ({:?})", adapter.name, adapter.backend)),
+    );
+}
+
+/// Copies a link to the current settings, and passes it on to the host page.
+pub fn copy_link(state: &State) {
+    match query::link(&query::Settings::of(state)) {
+        Ok(link) => {
+            dispatch("noodles-link", &JsValue::from_str(&link));
+            query::copy(link);
+        }
+        Err(e) => log::error!("Unable to make a link, {:#}", e),
+    }
+}
+
+/// Puts why the demo stopped over the canvas, which would otherwise be left blank. The message
+/// has the class `noodles-error`, for host pages to restyle it.
+pub fn show_error(error: &anyhow::Error) {
+    CANVAS.with_borrow(|canvas| {
+        let Some(canvas) = canvas else {
+            return;
+        };
+        let Some(message) = canvas
+            .owner_document()
+            .and_then(|document| document.create_element("div").ok())
+        else {
+            return;
+        };
+        message.set_class_name("noodles-error");
+        message.set_text_content(Some(&format!("{error:#}")));
+        let _ = message.set_attribute("role", "alert");
+        // Placed beside the canvas, it is positioned relative to the same ancestor.
+        let style = format!(
+            "position: absolute; left: {}px; top: {}px; width: {}px; height: {}px; \
+             box-sizing: border-box; display: flex; align-items: center; \
+             justify-content: center; padding: 2em; text-align: center; \
+             font: 16px sans-serif; color: #eee; background: #101018;",
+            canvas.offset_left(),
+            canvas.offset_top(),
+            canvas.offset_width(),
+            canvas.offset_height(),
+        );
+        let _ = message.set_attribute("style", &style);
+        let _ = canvas.after_with_node_1(&message);
+    });
+}
+
+fn dispatch(name: &str, detail: &JsValue) {
+    CANVAS.with_borrow(|canvas| {
+        let Some(canvas) = canvas else {
+            return;
+        };
+        let init = web_sys::CustomEventInit::new();
+        init.set_detail(detail);
+        if let Ok(event) = web_sys::CustomEvent::new_with_event_init_dict(name, &init) {
+            let _ = canvas.dispatch_event(&event);
+        }
+    });
+}
+
+/// Writes to the browser console, and passes errors on to the host page as events.
+struct Logger;
+
+impl log::Log for Logger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::Level::Info
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        console_log::log(record);
+        if record.level() == log::Level::Error {
+            dispatch(
+                "noodles-error",
+                &JsValue::from_str(&record.args().to_string()),
+            );
+        }
+    }
+
+    fn flush(&self) {}
+}